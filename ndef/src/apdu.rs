@@ -0,0 +1,135 @@
+//! ISO 7816-4 command APDUs for reading and writing the NDEF file of a
+//! Type 4 tag, for use with a PC/SC or other APDU transport.
+
+use crate::{
+    error::NdefError,
+    tag::{T4tCapabilityContainer, Type4Tag},
+    *,
+};
+
+/// AID of the NFC Forum NDEF Tag Application, version 2.0.
+pub const NDEF_APPLICATION_AID: [u8; 7] = [0xd2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01];
+pub const CC_FILE_ID: [u8; 2] = [0xe1, 0x03];
+/// Status word of a successful command.
+pub const SW_OK: [u8; 2] = [0x90, 0x00];
+
+/// Bytes of the CC file read before the NDEF file can be located.
+const CC_READ_LEN: u8 = 0x0f;
+/// Largest offset the short READ/UPDATE BINARY forms can address; P1 bit 8
+/// selects a short file identifier instead.
+const MAX_OFFSET: usize = 0x7fff;
+
+/// SELECT by name of the NDEF Tag Application.
+pub fn select_application() -> Vec<u8> {
+    let mut command = vec![0x00, 0xa4, 0x04, 0x00, NDEF_APPLICATION_AID.len() as u8];
+    command.extend_from_slice(&NDEF_APPLICATION_AID);
+    command.push(0x00);
+    command
+}
+
+/// SELECT by file identifier, first or only occurrence, no response data.
+pub fn select_file(file_id: [u8; 2]) -> Vec<u8> {
+    vec![0x00, 0xa4, 0x00, 0x0c, 0x02, file_id[0], file_id[1]]
+}
+
+pub fn read_binary(offset: u16, len: u8) -> Vec<u8> {
+    let [high, low] = offset.to_be_bytes();
+    vec![0x00, 0xb0, high, low, len]
+}
+
+/// UPDATE BINARY with at most 255 bytes of `data`.
+pub fn update_binary(offset: u16, data: &[u8]) -> Vec<u8> {
+    let [high, low] = offset.to_be_bytes();
+    let mut command = vec![0x00, 0xd6, high, low, data.len() as u8];
+    command.extend_from_slice(data);
+    command
+}
+
+/// Commands to select the application and read the CC file; the response
+/// to the last one parses with [`T4tCapabilityContainer::from_bytes`].
+pub fn read_cc() -> Vec<Vec<u8>> {
+    vec![select_application(), select_file(CC_FILE_ID), read_binary(0, CC_READ_LEN)]
+}
+
+/// Commands to select the NDEF file and read its 2-byte NLEN.
+pub fn read_nlen(cc: &T4tCapabilityContainer) -> Vec<Vec<u8>> {
+    vec![select_file(cc.file_id), read_binary(0, 2)]
+}
+
+/// READ BINARY commands for an `nlen` byte message after NLEN, each asking
+/// for at most MLe bytes. The NDEF file must already be selected.
+pub fn read_message(cc: &T4tCapabilityContainer, nlen: u16) -> Result<Vec<Vec<u8>>> {
+    let chunk_size = cc.mle.clamp(1, 0xff) as usize;
+    chunks(2, nlen as usize, chunk_size)
+        .map(|range| Ok(read_binary(check_offset(range.start)?, range.len() as u8)))
+        .collect()
+}
+
+/// Commands to write the NDEF file of `tag`: select the application and the
+/// file, clear NLEN, write the message in MLc sized pieces, then set NLEN,
+/// so an interrupted write never exposes a partial message.
+pub fn write_ndef(tag: &Type4Tag) -> Result<Vec<Vec<u8>>> {
+    let cc = tag.capability_container();
+    let file = tag.ndef_file();
+    let chunk_size = cc.mlc.clamp(1, 0xff) as usize;
+    let mut commands = vec![
+        select_application(),
+        select_file(cc.file_id),
+        update_binary(0, &[0x00, 0x00]),
+    ];
+    for range in chunks(2, file.len() - 2, chunk_size) {
+        commands.push(update_binary(check_offset(range.start)?, &file[range]));
+    }
+    commands.push(update_binary(0, &file[..2]));
+    Ok(commands)
+}
+
+fn chunks(start: usize, len: usize, chunk_size: usize) -> impl Iterator<Item = std::ops::Range<usize>> {
+    (start..start + len)
+        .step_by(chunk_size)
+        .map(move |offset| offset..(offset + chunk_size).min(start + len))
+}
+
+fn check_offset(offset: usize) -> Result<u16> {
+    if offset > MAX_OFFSET {
+        return Err(NdefError::InvalidTagMemorySize);
+    }
+    Ok(offset as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_ndef() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = Type4Tag::builder()
+            .max_ndef_size(0x80)
+            .mlc(6)
+            .message(&message)
+            .unwrap()
+            .build()
+            .unwrap();
+        let commands = write_ndef(&tag).unwrap();
+        assert_eq!("00a4040007d276000085010100", hex::encode(&commands[0]));
+        assert_eq!("00a4000c02e104", hex::encode(&commands[1]));
+        assert_eq!("00d60000020000", hex::encode(&commands[2]));
+        // 16 message bytes in pieces of 6, 6 and 4, after NLEN.
+        assert_eq!(7, commands.len());
+        assert_eq!([0x00, 0xd6, 0x00, 0x02, 0x06], commands[3][..5]);
+        assert_eq!([0x00, 0xd6, 0x00, 0x0e, 0x04], commands[5][..5]);
+        assert_eq!("00d60000020010", hex::encode(&commands[6]));
+    }
+
+    #[test]
+    fn test_read_sequence() {
+        assert_eq!("00b000000f", hex::encode(&read_cc()[2]));
+        let cc = hex::decode("000f20003b00340406e10400ff0000").unwrap();
+        let cc = T4tCapabilityContainer::from_bytes(&cc).unwrap();
+        assert_eq!(vec![select_file([0xe1, 0x04]), read_binary(0, 2)], read_nlen(&cc));
+        let reads = read_message(&cc, 100).unwrap();
+        assert_eq!(vec![read_binary(2, 0x3b), read_binary(0x3d, 41)], reads);
+        assert!(read_message(&cc, 0).unwrap().is_empty());
+    }
+}
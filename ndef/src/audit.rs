@@ -0,0 +1,455 @@
+use crate::{error::EncodeError, record::RecordHeader, *};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+/// Metadata of one record found by [`audit`]. Only offsets into the
+/// scanned buffer are kept, payloads are never copied.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecordSummary {
+    /// Offset of the record header in the scanned buffer.
+    pub offset: usize,
+    pub header: RecordHeader,
+    /// Range of the type field in the scanned buffer.
+    pub record_type: Range<usize>,
+    /// Range of the payload field in the scanned buffer.
+    pub payload: Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum AuditIssue {
+    /// The first record lacks the MB flag.
+    MissingMessageBegin,
+    /// A record other than the first has the MB flag.
+    UnexpectedMessageBegin { record: usize },
+    /// The data ended before a record with the ME flag.
+    MissingMessageEnd,
+    /// A header or record body runs past the end of the data.
+    Truncated { offset: usize },
+    /// Bytes left after the record with the ME flag.
+    TrailingBytes { offset: usize },
+    /// TNF 0x07 is reserved.
+    ReservedTnf { record: usize },
+    /// An Empty record with a non-zero type, id or payload length.
+    NonEmptyEmptyRecord { record: usize },
+    /// A chunked record (CF flag or Unchanged TNF).
+    Chunked { record: usize },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct AuditReport {
+    pub records: Vec<RecordSummary>,
+    pub issues: Vec<AuditIssue>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn total_payload_len(&self) -> usize {
+        self.records.iter().map(|r| r.payload.len()).sum()
+    }
+}
+
+/// Walks the record headers of a message in `data` without decoding or
+/// copying payloads, reporting structural problems instead of failing.
+/// Scanning stops at the ME record or the first truncation.
+pub fn audit(data: &[u8]) -> AuditReport {
+    let mut report = AuditReport::default();
+    let mut offset = 0;
+    while offset < data.len() {
+        let index = report.records.len();
+        let Ok((header, header_len)) = RecordHeader::parse(&data[offset..]) else {
+            report.issues.push(AuditIssue::Truncated { offset });
+            return report;
+        };
+        let body = offset + header_len;
+        let end = body + header.body_len();
+        if end > data.len() {
+            report.issues.push(AuditIssue::Truncated { offset });
+            return report;
+        }
+
+        let begin = header.flags.contains(RecordFlags::MB);
+        if index == 0 && !begin {
+            report.issues.push(AuditIssue::MissingMessageBegin);
+        } else if index > 0 && begin {
+            report.issues.push(AuditIssue::UnexpectedMessageBegin { record: index });
+        }
+        match header.tnf {
+            TNF::Reserved => report.issues.push(AuditIssue::ReservedTnf { record: index }),
+            TNF::Empty if header.body_len() > 0 => {
+                report.issues.push(AuditIssue::NonEmptyEmptyRecord { record: index })
+            }
+            _ => {}
+        }
+        if header.flags.contains(RecordFlags::CF) || header.tnf == TNF::Unchanged {
+            report.issues.push(AuditIssue::Chunked { record: index });
+        }
+
+        let type_end = body + header.type_len as usize;
+        report.records.push(RecordSummary {
+            offset,
+            header,
+            record_type: body..type_end,
+            payload: end - header.payload_len as usize..end,
+        });
+        offset = end;
+        if header.flags.contains(RecordFlags::ME) {
+            if offset < data.len() {
+                report.issues.push(AuditIssue::TrailingBytes { offset });
+            }
+            return report;
+        }
+    }
+    report.issues.push(AuditIssue::MissingMessageEnd);
+    report
+}
+
+/// Problem found by [`validate`] in a decoded or assembled message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ValidationIssue {
+    /// The record cannot be encoded as is: bad flags, reserved TNF or an
+    /// oversize field.
+    Encoding { record: usize, error: EncodeError },
+    /// TNF Unchanged record that does not continue a chunk sequence.
+    UnexpectedChunk { record: usize },
+    /// CF record whose chunk sequence is never terminated.
+    UnterminatedChunk { record: usize },
+    /// The record reuses the id of record `first`.
+    DuplicateId { record: usize, first: usize },
+    /// Smart Poster record whose nested message breaks the Smart Poster RTD.
+    SmartPoster { record: usize, problem: SmartPosterProblem },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SmartPosterProblem {
+    /// The payload is not a valid NDEF message.
+    NotAMessage,
+    MissingUri,
+    MultipleUris,
+    /// More than one action, size or type record.
+    Repeated(&'static str),
+    /// Two titles share a language.
+    DuplicateTitleLanguage(String),
+    /// A Smart Poster inside a Smart Poster.
+    Nested,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::Encoding { record, error } => write!(f, "record {}: {}", record, error),
+            ValidationIssue::UnexpectedChunk { record } => {
+                write!(f, "record {}: TNF Unchanged outside a chunk sequence", record)
+            }
+            ValidationIssue::UnterminatedChunk { record } => {
+                write!(f, "record {}: chunk sequence is not terminated", record)
+            }
+            ValidationIssue::DuplicateId { record, first } => {
+                write!(f, "record {}: id already used by record {}", record, first)
+            }
+            ValidationIssue::SmartPoster { record, problem } => {
+                write!(f, "record {}: Smart Poster ", record)?;
+                match problem {
+                    SmartPosterProblem::NotAMessage => write!(f, "payload is not an NDEF message"),
+                    SmartPosterProblem::MissingUri => write!(f, "has no URI record"),
+                    SmartPosterProblem::MultipleUris => write!(f, "has more than one URI record"),
+                    SmartPosterProblem::Repeated(part) => write!(f, "has more than one {} record", part),
+                    SmartPosterProblem::DuplicateTitleLanguage(language) => {
+                        write!(f, "has several titles in language {:?}", language)
+                    }
+                    SmartPosterProblem::Nested => write!(f, "contains another Smart Poster"),
+                }
+            }
+        }
+    }
+}
+
+/// Checks a message for problems that do not stop it from being used but
+/// that strict readers may reject. An empty list means the message is
+/// clean.
+pub fn validate(message: &NdefMessage) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+    let mut ids: HashMap<&[u8], usize> = HashMap::new();
+    let mut chunk_start = None;
+    for (index, record) in message.iter().enumerate() {
+        if let Err(error) = record.check_encodable() {
+            issues.push(ValidationIssue::Encoding { record: index, error });
+        }
+        if record.tnf() == TNF::Unchanged {
+            if chunk_start.is_none() {
+                issues.push(ValidationIssue::UnexpectedChunk { record: index });
+            }
+        } else if let Some(start) = chunk_start {
+            issues.push(ValidationIssue::UnterminatedChunk { record: start });
+        }
+        chunk_start = match (chunk_start, record.flags().contains(RecordFlags::CF)) {
+            (_, false) => None,
+            (Some(start), true) if record.tnf() == TNF::Unchanged => Some(start),
+            (_, true) => Some(index),
+        };
+        if let Some(id) = record.id().filter(|id| !id.is_empty()) {
+            if let Some(&first) = ids.get(id) {
+                issues.push(ValidationIssue::DuplicateId { record: index, first });
+            } else {
+                ids.insert(id, index);
+            }
+        }
+        if is_smart_poster(record) {
+            issues.extend(
+                smart_poster_problems(record.payload())
+                    .into_iter()
+                    .map(|problem| ValidationIssue::SmartPoster { record: index, problem }),
+            );
+        }
+    }
+    if let Some(start) = chunk_start {
+        issues.push(ValidationIssue::UnterminatedChunk { record: start });
+    }
+    issues
+}
+
+fn is_smart_poster(record: &NdefRecord) -> bool {
+    record.tnf() == TNF::WellKnown && record.record_type() == RTD_SMART_POSTER.as_bytes()
+}
+
+fn smart_poster_problems(payload: &[u8]) -> Vec<SmartPosterProblem> {
+    let Ok(message) = NdefMessage::decode(payload) else {
+        return vec![SmartPosterProblem::NotAMessage];
+    };
+    let mut problems = vec![];
+    let well_known = |rtd: RTD| {
+        message
+            .iter()
+            .filter(|r| r.tnf() == TNF::WellKnown && r.record_type() == rtd.as_bytes())
+            .count()
+    };
+    match well_known(RTD_URI) {
+        0 => problems.push(SmartPosterProblem::MissingUri),
+        1 => {}
+        _ => problems.push(SmartPosterProblem::MultipleUris),
+    }
+    for (rtd, part) in [(RTD_ACTION, "action"), (RTD_SIZE, "size"), (RTD_TYPE, "type")] {
+        if well_known(rtd) > 1 {
+            problems.push(SmartPosterProblem::Repeated(part));
+        }
+    }
+    let mut languages = HashSet::new();
+    for title in message.texts() {
+        if !languages.insert(title.language().clone()) {
+            problems.push(SmartPosterProblem::DuplicateTitleLanguage(title.language().to_string()));
+        }
+    }
+    if message.iter().any(is_smart_poster) {
+        problems.push(SmartPosterProblem::Nested);
+    }
+    problems
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Field {
+    Flags,
+    TypeLength,
+    PayloadLength,
+    IdLength,
+    Type,
+    Id,
+    Payload,
+    /// Bytes that could not be parsed as part of the message.
+    Unparsed,
+}
+
+/// Meaning of one byte range of an encoded message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Annotation {
+    pub range: Range<usize>,
+    /// Index of the record the range belongs to; `None` for unparsed bytes.
+    pub record: Option<usize>,
+    pub field: Field,
+    pub meaning: String,
+}
+
+/// Annotated dump of an encoded message, see [`explain`]. `Display` prints
+/// one line per field with its offsets, bytes and meaning.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Explanation<'a> {
+    data: &'a [u8],
+    pub annotations: Vec<Annotation>,
+}
+
+/// Bytes of a field shown before eliding the rest.
+const EXPLAIN_BYTES: usize = 8;
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, annotation) in self.annotations.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            let bytes = &self.data[annotation.range.clone()];
+            let mut hex: Vec<_> = bytes.iter().take(EXPLAIN_BYTES).map(|b| format!("{:02x}", b)).collect();
+            if bytes.len() > EXPLAIN_BYTES {
+                hex.push("..".to_owned());
+            }
+            write!(
+                f,
+                "{:04x}..{:04x} {:<26} ",
+                annotation.range.start,
+                annotation.range.end,
+                hex.join(" ")
+            )?;
+            if let Some(record) = annotation.record {
+                write!(f, "record {} ", record)?;
+            }
+            write!(f, "{:?}: {}", annotation.field, annotation.meaning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps every byte of `data` to the record field it encodes. Like
+/// [`audit`] this never fails; bytes after a truncation or after the ME
+/// record are reported as [`Field::Unparsed`].
+pub fn explain(data: &[u8]) -> Explanation<'_> {
+    let report = audit(data);
+    let mut annotations = vec![];
+    let mut push = |range: Range<usize>, record: Option<usize>, field: Field, meaning: String| {
+        annotations.push(Annotation {
+            range,
+            record,
+            field,
+            meaning,
+        })
+    };
+    let mut end = 0;
+    for (index, summary) in report.records.iter().enumerate() {
+        let header = &summary.header;
+        let record = Some(index);
+        let offset = summary.offset;
+        let flags: Vec<_> = header.flags.iter_names().map(|(name, _)| name).collect();
+        let meaning = format!("{} TNF={:?}", flags.join(" "), header.tnf);
+        push(offset..offset + 1, record, Field::Flags, meaning.trim_start().to_owned());
+        push(offset + 1..offset + 2, record, Field::TypeLength, header.type_len.to_string());
+        let length_end = offset + if header.is_short() { 3 } else { 6 };
+        push(offset + 2..length_end, record, Field::PayloadLength, header.payload_len.to_string());
+        if header.has_id() {
+            push(length_end..length_end + 1, record, Field::IdLength, header.id_len.to_string());
+        }
+        if !summary.record_type.is_empty() {
+            let value = String::from_utf8_lossy(&data[summary.record_type.clone()]);
+            push(summary.record_type.clone(), record, Field::Type, format!("{:?}", value));
+        }
+        let id = summary.record_type.end..summary.payload.start;
+        if !id.is_empty() {
+            let value = String::from_utf8_lossy(&data[id.clone()]);
+            push(id, record, Field::Id, format!("{:?}", value));
+        }
+        if !summary.payload.is_empty() {
+            push(summary.payload.clone(), record, Field::Payload, format!("{} bytes", summary.payload.len()));
+        }
+        end = summary.payload.end;
+    }
+    if end < data.len() {
+        let reason = if report.issues.contains(&AuditIssue::TrailingBytes { offset: end }) {
+            "trailing bytes after the ME record"
+        } else {
+            "truncated record"
+        };
+        push(end..data.len(), None, Field::Unparsed, reason.to_owned());
+    }
+    Explanation { data, annotations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::*;
+
+    #[test]
+    fn test_audit() {
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        let bytes = NdefMessage::from(&[record.clone(), record]).to_buffer().unwrap();
+        let report = audit(&bytes);
+        assert!(report.is_clean());
+        assert_eq!(2, report.records.len());
+        assert_eq!(b"U", &bytes[report.records[1].record_type.clone()]);
+        assert_eq!(24, report.total_payload_len());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        assert_eq!(vec![AuditIssue::TrailingBytes { offset: bytes.len() }], audit(&trailing).issues);
+
+        let first = &bytes[..report.records[1].offset];
+        assert_eq!(vec![AuditIssue::MissingMessageEnd], audit(first).issues);
+
+        // A 4 GiB payload length is reported, not allocated.
+        let hostile = [0xc1, 0x01, 0xff, 0xff, 0xff, 0xff, b'U'];
+        assert_eq!(vec![AuditIssue::Truncated { offset: 0 }], audit(&hostile).issues);
+    }
+
+    #[test]
+    fn test_explain() {
+        let data = hex::decode("d9010401556d03616263ff").unwrap();
+        let explanation = explain(&data);
+        let fields: Vec<_> = explanation.annotations.iter().map(|a| (a.range.clone(), a.field)).collect();
+        assert_eq!(
+            vec![
+                (0..1, Field::Flags),
+                (1..2, Field::TypeLength),
+                (2..3, Field::PayloadLength),
+                (3..4, Field::IdLength),
+                (4..5, Field::Type),
+                (5..6, Field::Id),
+                (6..10, Field::Payload),
+                (10..11, Field::Unparsed),
+            ],
+            fields
+        );
+        assert_eq!("MB ME SR IL TNF=WellKnown", explanation.annotations[0].meaning);
+        let text = explanation.to_string();
+        assert!(text.starts_with("0000..0001 d9"));
+        assert!(text.ends_with("Unparsed: trailing bytes after the ME record"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let inner = NdefMessage::builder()
+            .uri("https://a.example")
+            .uri("https://b.example")
+            .text("en", "A")
+            .text("EN", "B")
+            .build()
+            .unwrap();
+        let poster = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .nested_message(RTD_SMART_POSTER.as_bytes(), &inner)
+            .unwrap()
+            .build()
+            .unwrap();
+        let uri = NdefRecord::builder()
+            .well_known(&UriPayload::from_static("tel:1"))
+            .id(b"x".to_vec())
+            .build()
+            .unwrap();
+        let message = NdefMessage::from(&[poster, uri.clone(), uri]);
+        assert_eq!(
+            vec![
+                ValidationIssue::SmartPoster { record: 0, problem: SmartPosterProblem::MultipleUris },
+                ValidationIssue::SmartPoster {
+                    record: 0,
+                    problem: SmartPosterProblem::DuplicateTitleLanguage("en".to_owned()),
+                },
+                ValidationIssue::DuplicateId { record: 2, first: 1 },
+            ],
+            message.validate()
+        );
+        assert_eq!("record 2: id already used by record 1", message.validate()[2].to_string());
+        assert!(NdefMessage::builder().uri("tel:1").build().unwrap().validate().is_empty());
+    }
+}
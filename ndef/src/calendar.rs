@@ -0,0 +1,335 @@
+use crate::vcard::{escape, push_folded, unescape, unfold};
+use crate::{error::NdefError, payload::RecordPayload, record::NdefRecord, *};
+use std::borrow::Cow;
+use std::fmt;
+
+pub const MIME_CALENDAR: &str = "text/calendar";
+
+/// Date or date-time value in iCalendar basic format
+/// (`20241016` or `20241016T093000`, optionally with a trailing `Z` for UTC).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CalendarDateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    time: Option<(u8, u8, u8)>,
+    utc: bool,
+}
+
+impl CalendarDateTime {
+    pub fn date(year: u16, month: u8, day: u8) -> Result<Self> {
+        if year > 9999 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(NdefError::InvalidPayload);
+        }
+        Ok(Self {
+            year,
+            month,
+            day,
+            time: None,
+            utc: false,
+        })
+    }
+
+    /// Floating local date-time; use [`CalendarDateTime::utc`] to mark it as UTC.
+    pub fn date_time(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Self> {
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(NdefError::InvalidPayload);
+        }
+        let mut value = Self::date(year, month, day)?;
+        value.time = Some((hour, minute, second));
+        Ok(value)
+    }
+
+    pub fn utc(mut self) -> Self {
+        self.utc = self.time.is_some();
+        self
+    }
+
+    pub fn is_utc(&self) -> bool {
+        self.utc
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        let number = |range: std::ops::Range<usize>| -> Result<u16> {
+            value
+                .get(range)
+                .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|s| s.parse().ok())
+                .ok_or(NdefError::InvalidPayload)
+        };
+        match value.len() {
+            8 => Self::date(number(0..4)?, number(4..6)? as u8, number(6..8)? as u8),
+            15 | 16 if value.as_bytes()[8] == b'T' => {
+                let parsed = Self::date_time(
+                    number(0..4)?,
+                    number(4..6)? as u8,
+                    number(6..8)? as u8,
+                    number(9..11)? as u8,
+                    number(11..13)? as u8,
+                    number(13..15)? as u8,
+                )?;
+                match value.get(15..) {
+                    Some("Z") => Ok(parsed.utc()),
+                    Some("") => Ok(parsed),
+                    _ => Err(NdefError::InvalidPayload),
+                }
+            }
+            _ => Err(NdefError::InvalidPayload),
+        }
+    }
+}
+
+impl fmt::Display for CalendarDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}{:02}{:02}", self.year, self.month, self.day)?;
+        if let Some((hour, minute, second)) = self.time {
+            write!(f, "T{:02}{:02}{:02}", hour, minute, second)?;
+            if self.utc {
+                write!(f, "Z")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Calendar event carried as a `text/calendar` MIME record holding a single VEVENT.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CalendarEventPayload {
+    uid: String,
+    stamp: CalendarDateTime,
+    summary: String,
+    start: CalendarDateTime,
+    end: Option<CalendarDateTime>,
+    location: Option<String>,
+}
+
+impl CalendarEventPayload {
+    /// The UID defaults to a hash of `summary` and `start`, and DTSTAMP to
+    /// `start` read as UTC, so the same event always encodes the same way.
+    pub fn new<T: Into<String>>(summary: T, start: CalendarDateTime) -> Self {
+        let summary = summary.into();
+        Self {
+            uid: default_uid(&summary, &start),
+            stamp: default_stamp(&start),
+            summary,
+            start,
+            end: None,
+            location: None,
+        }
+    }
+
+    pub fn with_uid<T: Into<String>>(mut self, uid: T) -> Self {
+        self.uid = uid.into();
+        self
+    }
+
+    /// Sets DTSTAMP, the time the event was created, which must be a UTC
+    /// date-time.
+    pub fn with_stamp(mut self, stamp: CalendarDateTime) -> Result<Self> {
+        if !stamp.is_utc() {
+            return Err(NdefError::InvalidPayload);
+        }
+        self.stamp = stamp;
+        Ok(self)
+    }
+
+    pub fn with_end(mut self, end: CalendarDateTime) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    pub fn with_location<T: Into<String>>(mut self, location: T) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    pub fn uid(&self) -> &str {
+        &self.uid
+    }
+
+    pub fn stamp(&self) -> CalendarDateTime {
+        self.stamp
+    }
+
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    pub fn start(&self) -> CalendarDateTime {
+        self.start
+    }
+
+    pub fn end(&self) -> Option<CalendarDateTime> {
+        self.end
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    pub fn to_icalendar(&self) -> String {
+        let mut text = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//ndef-rs//EN\r\n");
+        text.push_str("BEGIN:VEVENT\r\n");
+        push_folded(&mut text, &format!("UID:{}", escape(&self.uid)));
+        push_folded(&mut text, &format!("DTSTAMP:{}", self.stamp));
+        push_folded(&mut text, &format!("SUMMARY:{}", escape(&self.summary)));
+        push_folded(&mut text, &format!("DTSTART{}", date_property(&self.start)));
+        if let Some(end) = self.end.as_ref() {
+            push_folded(&mut text, &format!("DTEND{}", date_property(end)));
+        }
+        if let Some(location) = self.location.as_ref() {
+            push_folded(&mut text, &format!("LOCATION:{}", escape(location)));
+        }
+        text.push_str("END:VEVENT\r\nEND:VCALENDAR\r\n");
+        text
+    }
+
+    /// Parses the first VEVENT of an iCalendar object. A missing UID or
+    /// DTSTAMP gets the default of [`CalendarEventPayload::new`].
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut in_event = false;
+        let mut uid = None;
+        let mut stamp = None;
+        let mut summary = String::new();
+        let mut start = None;
+        let mut end = None;
+        let mut location = None;
+        for line in unfold(text) {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let name = name.split(';').next().unwrap_or_default().to_ascii_uppercase();
+            match name.as_str() {
+                "BEGIN" if value.eq_ignore_ascii_case("VEVENT") => in_event = true,
+                "END" if in_event && value.eq_ignore_ascii_case("VEVENT") => {
+                    let start = start.ok_or(NdefError::InvalidPayload)?;
+                    return Ok(Self {
+                        uid: uid.unwrap_or_else(|| default_uid(&summary, &start)),
+                        stamp: stamp.unwrap_or_else(|| default_stamp(&start)),
+                        summary,
+                        start,
+                        end,
+                        location,
+                    });
+                }
+                _ if !in_event => {}
+                "UID" => uid = Some(unescape(value)),
+                "DTSTAMP" => stamp = Some(CalendarDateTime::parse(value)?),
+                "SUMMARY" => summary = unescape(value),
+                "DTSTART" => start = Some(CalendarDateTime::parse(value)?),
+                "DTEND" => end = Some(CalendarDateTime::parse(value)?),
+                "LOCATION" => location = Some(unescape(value)),
+                _ => {}
+            }
+        }
+        Err(NdefError::InvalidPayload)
+    }
+}
+
+/// FNV-1a hash of the summary and start, as a UID that is stable across
+/// builds and platforms.
+fn default_uid(summary: &str, start: &CalendarDateTime) -> String {
+    let hash = summary
+        .bytes()
+        .chain(start.to_string().bytes())
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:016x}@ndef-rs", hash)
+}
+
+/// `start` as a UTC date-time, midnight for all-day events.
+fn default_stamp(start: &CalendarDateTime) -> CalendarDateTime {
+    CalendarDateTime {
+        time: Some(start.time.unwrap_or((0, 0, 0))),
+        utc: true,
+        ..*start
+    }
+}
+
+fn date_property(value: &CalendarDateTime) -> String {
+    if value.time.is_some() {
+        format!(":{}", value)
+    } else {
+        format!(";VALUE=DATE:{}", value)
+    }
+}
+
+impl RecordPayload for CalendarEventPayload {
+    fn tnf(&self) -> TNF {
+        TNF::MimeMedia
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(MIME_CALENDAR.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.to_icalendar().into_bytes())
+    }
+}
+
+impl TryFrom<&NdefRecord> for CalendarEventPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        if record.tnf() != TNF::MimeMedia {
+            return Err(NdefError::InvalidTnf);
+        }
+        if !record.record_type().eq_ignore_ascii_case(MIME_CALENDAR.as_bytes()) {
+            return Err(NdefError::InvalidRecordType);
+        }
+        let text = std::str::from_utf8(record.payload()).map_err(|_| NdefError::InvalidEncoding)?;
+        Self::parse(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_event() {
+        let start = CalendarDateTime::date_time(2024, 10, 16, 9, 30, 0).unwrap().utc();
+        let event = CalendarEventPayload::new("Launch, day 1", start)
+            .with_end(CalendarDateTime::date_time(2024, 10, 16, 11, 0, 0).unwrap().utc())
+            .with_location("Hall A");
+        let text = event.to_icalendar();
+        assert!(text.contains("SUMMARY:Launch\\, day 1\r\n"));
+        assert!(text.contains("DTSTART:20241016T093000Z\r\n"));
+        assert!(text.contains(&format!("UID:{}\r\nDTSTAMP:20241016T093000Z\r\n", event.uid())));
+
+        let record = NdefRecord::builder()
+            .tnf(TNF::MimeMedia)
+            .payload(&event)
+            .build()
+            .unwrap();
+        assert_eq!(event, CalendarEventPayload::try_from(&record).unwrap());
+
+        let event = CalendarEventPayload::new("Fair", CalendarDateTime::date(2025, 1, 2).unwrap());
+        assert!(event.to_icalendar().contains("DTSTART;VALUE=DATE:20250102\r\n"));
+        assert_eq!(event, CalendarEventPayload::parse(&event.to_icalendar()).unwrap());
+
+        assert!(event.to_icalendar().contains("DTSTAMP:20250102T000000Z\r\n"));
+        let stamp = CalendarDateTime::date_time(2024, 12, 1, 8, 0, 0).unwrap();
+        assert!(event.clone().with_stamp(stamp).is_err());
+        let event = event.with_uid("fair-2025@example.com").with_stamp(stamp.utc()).unwrap();
+        assert_eq!(event, CalendarEventPayload::parse(&event.to_icalendar()).unwrap());
+
+        assert!(CalendarDateTime::parse("2024-10-16").is_err());
+        assert!(CalendarDateTime::date(2024, 13, 1).is_err());
+    }
+
+    #[test]
+    fn test_calendar_folding() {
+        let start = CalendarDateTime::date(2025, 3, 4).unwrap();
+        let summary = "Annual general meeting of the society, followed by dinner at the harbour café";
+        let event = CalendarEventPayload::new(summary, start).with_location("Hall B, Pier 3");
+        let text = event.to_icalendar();
+        assert!(text.split("\r\n").all(|line| line.len() <= 75));
+        assert!(text.contains("\r\n "));
+        let parsed = CalendarEventPayload::parse(&text).unwrap();
+        assert_eq!(summary, parsed.summary());
+        assert_eq!(event, parsed);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::tag::LockControl;
+use crate::tag_io::TagType;
+
+/// How a chip makes memory read-only.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LockMechanism {
+    /// Static lock bytes in page 2 only.
+    StaticLockBits,
+    /// Static lock bytes plus dynamic lock bytes after the data area.
+    StaticAndDynamicLockBits,
+    /// Per-block permanent lock commands (Type 5).
+    BlockLock,
+    /// File access rights set in the application (Type 4).
+    AccessRights,
+    /// Read-only bits in the memory configuration block (Type 3).
+    MemoryConfiguration,
+}
+
+/// Commercial NFC chips with the memory layout facts needed to plan, build
+/// and check NDEF images for them.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ChipModel {
+    Ntag210,
+    Ntag212,
+    Ntag213,
+    Ntag215,
+    Ntag216,
+    /// MIFARE Ultralight EV1, MF0UL11.
+    UltralightEv1_48,
+    /// MIFARE Ultralight EV1, MF0UL21.
+    UltralightEv1_128,
+    St25tv512,
+    St25tv02k,
+    /// ST25DV64K, 8 Kbyte with the 8-byte CC.
+    St25dv64k,
+    /// ICODE SLIX, SL2S2002.
+    IcodeSlix,
+    /// ICODE SLIX2, SL2S2602.
+    IcodeSlix2,
+    DesfireEv2K,
+    DesfireEv4K,
+    DesfireEv8K,
+    FelicaLiteS,
+}
+
+impl ChipModel {
+    pub const ALL: [ChipModel; 16] = [
+        ChipModel::Ntag210,
+        ChipModel::Ntag212,
+        ChipModel::Ntag213,
+        ChipModel::Ntag215,
+        ChipModel::Ntag216,
+        ChipModel::UltralightEv1_48,
+        ChipModel::UltralightEv1_128,
+        ChipModel::St25tv512,
+        ChipModel::St25tv02k,
+        ChipModel::St25dv64k,
+        ChipModel::IcodeSlix,
+        ChipModel::IcodeSlix2,
+        ChipModel::DesfireEv2K,
+        ChipModel::DesfireEv4K,
+        ChipModel::DesfireEv8K,
+        ChipModel::FelicaLiteS,
+    ];
+
+    pub fn tag_type(&self) -> TagType {
+        match self {
+            ChipModel::St25tv512
+            | ChipModel::St25tv02k
+            | ChipModel::St25dv64k
+            | ChipModel::IcodeSlix
+            | ChipModel::IcodeSlix2 => TagType::Type5,
+            ChipModel::DesfireEv2K | ChipModel::DesfireEv4K | ChipModel::DesfireEv8K => TagType::Type4,
+            ChipModel::FelicaLiteS => TagType::Type3,
+            _ => TagType::Type2,
+        }
+    }
+
+    /// Bytes per page or block; `None` for file based (Type 4) chips.
+    pub fn page_size(&self) -> Option<usize> {
+        match self.tag_type() {
+            TagType::Type2 | TagType::Type5 => Some(4),
+            TagType::Type3 => Some(16),
+            TagType::Type4 => None,
+        }
+    }
+
+    /// Total number of pages, including configuration pages (Type 2 only).
+    pub fn total_pages(&self) -> Option<u16> {
+        match self {
+            ChipModel::Ntag210 | ChipModel::UltralightEv1_48 => Some(20),
+            ChipModel::Ntag212 | ChipModel::UltralightEv1_128 => Some(41),
+            ChipModel::Ntag213 => Some(45),
+            ChipModel::Ntag215 => Some(135),
+            ChipModel::Ntag216 => Some(231),
+            _ => None,
+        }
+    }
+
+    /// Bytes available for NDEF data: the Type 2 data area as declared by the
+    /// factory CC (smaller than the user memory on NTAG215/216), the Type 5 user
+    /// memory after the CC, the Type 3 blocks after the attribute block, or
+    /// the Type 4 NDEF file size (including NLEN) that fits a standard
+    /// NDEF application.
+    pub fn data_area_size(&self) -> usize {
+        match self {
+            ChipModel::Ntag210 | ChipModel::UltralightEv1_48 => 48,
+            ChipModel::Ntag212 | ChipModel::UltralightEv1_128 => 128,
+            ChipModel::Ntag213 => 144,
+            ChipModel::Ntag215 => 496,
+            ChipModel::Ntag216 => 872,
+            ChipModel::St25tv512 => 64 - 4,
+            ChipModel::St25tv02k => 256 - 4,
+            ChipModel::St25dv64k => 8192 - 8,
+            ChipModel::IcodeSlix => 112 - 4,
+            ChipModel::IcodeSlix2 => 320 - 4,
+            ChipModel::DesfireEv2K => 1792,
+            ChipModel::DesfireEv4K => 3840,
+            ChipModel::DesfireEv8K => 7936,
+            ChipModel::FelicaLiteS => 13 * 16,
+        }
+    }
+
+    /// Longest NDEF message that fits, after TLV or NLEN framing.
+    pub fn ndef_capacity(&self) -> usize {
+        let area = self.data_area_size();
+        match self.tag_type() {
+            TagType::Type2 | TagType::Type5 if area - 2 > 0xfe => area - 4,
+            TagType::Type2 | TagType::Type5 => area - 2,
+            TagType::Type4 => area - 2,
+            TagType::Type3 => area,
+        }
+    }
+
+    /// Where the dynamic lock bytes are and how many pages each bit locks
+    /// (Type 2 chips above 48 bytes only).
+    pub fn dynamic_lock(&self) -> Option<LockControl> {
+        // Lock byte page, lock bits, bytes locked per bit.
+        let (page, bits, bytes_per_bit) = match self {
+            ChipModel::Ntag212 | ChipModel::UltralightEv1_128 => (0x24, 5, 16),
+            ChipModel::Ntag213 => (0x28, 12, 8),
+            ChipModel::Ntag215 => (0x82, 8, 64),
+            ChipModel::Ntag216 => (0xe2, 14, 64),
+            _ => return None,
+        };
+        LockControl::new(page * 4, bits, bytes_per_bit).ok()
+    }
+
+    /// First configuration page (CFG0), after the user memory and the
+    /// dynamic lock bytes (Type 2 only).
+    pub fn config_page(&self) -> Option<u16> {
+        match self {
+            ChipModel::Ntag210 | ChipModel::UltralightEv1_48 => Some(0x10),
+            ChipModel::Ntag212 | ChipModel::UltralightEv1_128 => Some(0x25),
+            ChipModel::Ntag213 => Some(0x29),
+            ChipModel::Ntag215 => Some(0x83),
+            ChipModel::Ntag216 => Some(0xe3),
+            _ => None,
+        }
+    }
+
+    pub fn lock_mechanism(&self) -> LockMechanism {
+        match self {
+            ChipModel::Ntag210 | ChipModel::UltralightEv1_48 => LockMechanism::StaticLockBits,
+            ChipModel::St25tv512
+            | ChipModel::St25tv02k
+            | ChipModel::St25dv64k
+            | ChipModel::IcodeSlix
+            | ChipModel::IcodeSlix2 => LockMechanism::BlockLock,
+            ChipModel::DesfireEv2K | ChipModel::DesfireEv4K | ChipModel::DesfireEv8K => {
+                LockMechanism::AccessRights
+            }
+            ChipModel::FelicaLiteS => LockMechanism::MemoryConfiguration,
+            _ => LockMechanism::StaticAndDynamicLockBits,
+        }
+    }
+
+    /// Capability container as factory programmed for an NDEF formatted
+    /// chip (Type 2, and Type 5 chips using the 4-byte form). The Type 5
+    /// MLEN counts the CC as well as the data area.
+    pub fn capability_container(&self) -> Option<[u8; 4]> {
+        match self.tag_type() {
+            TagType::Type2 => Some([0xe1, 0x10, (self.data_area_size() / 8) as u8, 0x00]),
+            TagType::Type5 if self.data_area_size() + 4 <= 0xff * 8 => {
+                Some([0xe1, 0x40, ((self.data_area_size() + 4) / 8) as u8, 0x00])
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds the Type 2 chip whose factory CC matches `cc`. Chips sharing a
+    /// CC (e.g. NTAG210 and Ultralight EV1 MF0UL11) resolve to the first.
+    pub fn from_t2_cc(cc: &[u8; 4]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|chip| chip.tag_type() == TagType::Type2 && chip.capability_container().as_ref() == Some(cc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chip_models() {
+        assert_eq!(Some([0xe1, 0x10, 0x12, 0x00]), ChipModel::Ntag213.capability_container());
+        assert_eq!(Some([0xe1, 0x10, 0x3e, 0x00]), ChipModel::Ntag215.capability_container());
+        assert_eq!(Some([0xe1, 0x10, 0x6d, 0x00]), ChipModel::Ntag216.capability_container());
+        assert_eq!(Some([0xe1, 0x40, 0x20, 0x00]), ChipModel::St25tv02k.capability_container());
+        assert_eq!(None, ChipModel::DesfireEv2K.capability_container());
+
+        assert_eq!(142, ChipModel::Ntag213.ndef_capacity());
+        assert_eq!(492, ChipModel::Ntag215.ndef_capacity());
+        assert_eq!(868, ChipModel::Ntag216.ndef_capacity());
+        assert_eq!(250, ChipModel::St25tv02k.ndef_capacity());
+        assert_eq!(Some(ChipModel::Ntag215), ChipModel::from_t2_cc(&[0xe1, 0x10, 0x3e, 0x00]));
+        assert_eq!(Some(ChipModel::Ntag210), ChipModel::from_t2_cc(&[0xe1, 0x10, 0x06, 0x00]));
+
+        for chip in ChipModel::ALL {
+            if let Some(pages) = chip.total_pages() {
+                // CC ends at page 3; configuration pages follow the data area.
+                assert!(16 + chip.data_area_size() <= pages as usize * 4);
+                // CFG0, CFG1, PWD and PACK are the last four pages.
+                assert_eq!(Some(pages - 4), chip.config_page());
+            }
+            if let Some(lock) = chip.dynamic_lock() {
+                assert!(lock.reserved_area().end <= chip.config_page().unwrap() as usize * 4);
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! NFC Forum "Type MIFARE Classic" mapping: the MIFARE Application
+//! Directory (MAD) naming the NDEF sectors, their sector trailers, and the
+//! TLV area spread over the data blocks of those sectors.
+
+use crate::{error::NdefError, tag::TlvValue, *};
+
+const BLOCK_SIZE: usize = 16;
+/// AID of an NDEF sector, stored little-endian in the MAD.
+const NDEF_AID: u16 = 0xe103;
+/// Key A of the MAD sectors.
+pub const MAD_KEY_A: [u8; 6] = [0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5];
+/// Public key A of the NDEF sectors.
+pub const NDEF_KEY_A: [u8; 6] = [0xd3, 0xf7, 0xd3, 0xf7, 0xd3, 0xf7];
+const KEY_B: [u8; 6] = [0xff; 6];
+/// Sector of MAD2 on 4K cards.
+const MAD2_SECTOR: usize = 16;
+
+/// Access bits: MAD readable with key A, writable with key B.
+const MAD_ACCESS: [u8; 3] = [0x78, 0x77, 0x88];
+/// Access bits: NDEF data readable with key A, writable with key B.
+const NDEF_ACCESS: [u8; 3] = [0x7f, 0x07, 0x88];
+/// Access bits: data readable with key A, never writable.
+const READ_ONLY_ACCESS: [u8; 3] = [0x07, 0x8f, 0x0f];
+
+/// General purpose byte of the MAD1 sector: DA (MAD available), MA
+/// (multi-application card), MAD version 1 or 2.
+const MAD_GPB_V1: u8 = 0xc1;
+const MAD_GPB_V2: u8 = 0xc2;
+/// General purpose byte of an NDEF sector: mapping version 1.0 with read
+/// access granted and write access granted or denied.
+const NDEF_GPB_READ_WRITE: u8 = 0x40;
+const NDEF_GPB_READ_ONLY: u8 = 0x43;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClassicSize {
+    /// MIFARE Classic 1K: 16 sectors of 4 blocks.
+    OneK,
+    /// MIFARE Classic 4K: 32 sectors of 4 blocks, then 8 of 16 blocks.
+    FourK,
+}
+
+impl ClassicSize {
+    pub fn sectors(&self) -> usize {
+        match self {
+            ClassicSize::OneK => 16,
+            ClassicSize::FourK => 40,
+        }
+    }
+
+    /// First block and number of blocks, trailer included, of `sector`.
+    pub fn sector_blocks(&self, sector: usize) -> (usize, usize) {
+        match sector {
+            0..32 => (sector * 4, 4),
+            _ => (128 + (sector - 32) * 16, 16),
+        }
+    }
+
+    fn is_mad_sector(&self, sector: usize) -> bool {
+        sector == 0 || (*self == ClassicSize::FourK && sector == MAD2_SECTOR)
+    }
+
+    /// Bytes of TLV area: every data block outside the MAD sectors.
+    pub fn ndef_area_size(&self) -> usize {
+        (0..self.sectors())
+            .filter(|&sector| !self.is_mad_sector(sector))
+            .map(|sector| (self.sector_blocks(sector).1 - 1) * BLOCK_SIZE)
+            .sum()
+    }
+}
+
+/// CRC-8 of a MAD (polynomial 0x1D, preset 0xC7) over the info byte and the
+/// AIDs, i.e. the directory without its leading CRC byte.
+pub fn mad_crc(data: &[u8]) -> u8 {
+    data.iter().fold(0xc7, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| if crc & 0x80 != 0 { (crc << 1) ^ 0x1d } else { crc << 1 })
+    })
+}
+
+/// A MIFARE Classic card formatted for NDEF: every sector but the MAD ones
+/// carries the NDEF AID, and the TLVs fill their data blocks in order.
+pub struct MifareClassicTag {
+    size: ClassicSize,
+    read_only: bool,
+    tlvs: Vec<TlvValue>,
+}
+
+impl MifareClassicTag {
+    pub fn builder(size: ClassicSize) -> MifareClassicBuilder {
+        MifareClassicBuilder {
+            size,
+            read_only: false,
+            tlvs: vec![],
+        }
+    }
+
+    pub fn size(&self) -> ClassicSize {
+        self.size
+    }
+
+    /// MAD1 (blocks 1 and 2) or MAD2 (blocks 64 to 66), CRC first.
+    fn mad(&self, sectors: std::ops::Range<usize>, info: u8) -> Vec<u8> {
+        let mut mad = vec![info];
+        for sector in sectors {
+            let aid = if self.size.is_mad_sector(sector) { 0 } else { NDEF_AID };
+            mad.extend_from_slice(&aid.to_le_bytes());
+        }
+        mad.insert(0, mad_crc(&mad));
+        mad
+    }
+
+    fn trailer(&self, sector: usize) -> [u8; BLOCK_SIZE] {
+        let (key_a, access, gpb) = match (self.size.is_mad_sector(sector), self.read_only) {
+            (true, read_only) => {
+                let gpb = if self.size == ClassicSize::FourK { MAD_GPB_V2 } else { MAD_GPB_V1 };
+                (MAD_KEY_A, if read_only { READ_ONLY_ACCESS } else { MAD_ACCESS }, gpb)
+            }
+            (false, false) => (NDEF_KEY_A, NDEF_ACCESS, NDEF_GPB_READ_WRITE),
+            (false, true) => (NDEF_KEY_A, READ_ONLY_ACCESS, NDEF_GPB_READ_ONLY),
+        };
+        let mut trailer = [0u8; BLOCK_SIZE];
+        trailer[..6].copy_from_slice(&key_a);
+        trailer[6..9].copy_from_slice(&access);
+        trailer[9] = gpb;
+        trailer[10..].copy_from_slice(&KEY_B);
+        trailer
+    }
+
+    /// Every block to write, numbered from block 0 of sector 0, which holds
+    /// the manufacturer data and is left out. Unused data bytes are zeroed.
+    pub fn to_blocks(&self) -> crate::Result<Vec<(u8, [u8; BLOCK_SIZE])>> {
+        let stream = self
+            .tlvs
+            .iter()
+            .map(|tlv| tlv.to_bytes())
+            .collect::<crate::Result<Vec<_>>>()?
+            .concat();
+        if stream.len() > self.size.ndef_area_size() {
+            return Err(NdefError::CapacityExceeded {
+                needed: stream.len(),
+                available: self.size.ndef_area_size(),
+            });
+        }
+        let mut data = stream.chunks(BLOCK_SIZE);
+
+        let mut mads = vec![self.mad(1..16, 0x01)];
+        if self.size == ClassicSize::FourK {
+            mads.push(self.mad(17..40, 0x00));
+        }
+        let mut blocks = vec![];
+        for sector in 0..self.size.sectors() {
+            let (first, count) = self.size.sector_blocks(sector);
+            let contents: Vec<Vec<u8>> = match sector {
+                0 => mads[0].chunks(BLOCK_SIZE).map(<[u8]>::to_vec).collect(),
+                MAD2_SECTOR if self.size.is_mad_sector(sector) => {
+                    mads[1].chunks(BLOCK_SIZE).map(<[u8]>::to_vec).collect()
+                }
+                _ => (0..count - 1).map(|_| data.next().unwrap_or_default().to_vec()).collect(),
+            };
+            // Block 0 is manufacturer data; MAD1 starts at block 1.
+            let skip = if sector == 0 { 1 } else { 0 };
+            for (index, content) in contents.into_iter().enumerate() {
+                let mut block = [0u8; BLOCK_SIZE];
+                block[..content.len()].copy_from_slice(&content);
+                blocks.push(((first + skip + index) as u8, block));
+            }
+            blocks.push(((first + count - 1) as u8, self.trailer(sector)));
+        }
+        Ok(blocks)
+    }
+}
+
+pub struct MifareClassicBuilder {
+    size: ClassicSize,
+    read_only: bool,
+    tlvs: Vec<TlvValue>,
+}
+
+impl MifareClassicBuilder {
+    /// Write the sector trailers with read-only access bits. Once written
+    /// to a card this cannot be undone.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn add_tlv(mut self, value: TlvValue) -> Self {
+        self.tlvs.push(value);
+        self
+    }
+
+    /// Adds an NDEF TLV holding `message`, followed by a Terminator TLV.
+    pub fn message(self, message: &NdefMessage) -> crate::Result<Self> {
+        Ok(self.add_tlv(TlvValue::ndef_message(message)?).add_tlv(TlvValue::Terminator))
+    }
+
+    pub fn build(self) -> MifareClassicTag {
+        MifareClassicTag {
+            size: self.size,
+            read_only: self.read_only,
+            tlvs: self.tlvs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mad() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = MifareClassicTag::builder(ClassicSize::OneK).message(&message).unwrap().build();
+        let blocks = tag.to_blocks().unwrap();
+        assert_eq!(63, blocks.len());
+        assert_eq!(1, blocks[0].0);
+        assert_eq!("140103e103e103e103e103e103e103e1", hex::encode(blocks[0].1));
+        assert_eq!("a0a1a2a3a4a5787788c1ffffffffffff", hex::encode(blocks[2].1));
+        assert_eq!((4, 0x03), (blocks[3].0, blocks[3].1[0]));
+        assert_eq!(7, blocks[6].0);
+        assert_eq!("d3f7d3f7d3f77f078840ffffffffffff", hex::encode(blocks[6].1));
+        assert_eq!(720, ClassicSize::OneK.ndef_area_size());
+
+        let tag = MifareClassicTag::builder(ClassicSize::FourK).read_only(true).build();
+        let blocks = tag.to_blocks().unwrap();
+        assert_eq!(255, blocks.len());
+        assert_eq!((255, 0x43), (blocks[254].0, blocks[254].1[9]));
+        let mad2 = [blocks[63].1, blocks[64].1, blocks[65].1].concat();
+        assert_eq!(64, blocks[63].0);
+        assert_eq!(mad_crc(&mad2[1..]), mad2[0]);
+        assert_eq!(3360, ClassicSize::FourK.ndef_area_size());
+
+        let tag = MifareClassicTag::builder(ClassicSize::OneK).add_tlv(TlvValue::Ndef(vec![0; 720])).build();
+        assert!(tag.to_blocks().is_err());
+    }
+}
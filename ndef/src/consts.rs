@@ -17,7 +17,7 @@ macro_rules! define_const_array {
     };
 }
 
-#[derive(Debug, FromRepr, PartialEq, VariantArray, Clone, Copy)]
+#[derive(Debug, FromRepr, PartialEq, Eq, Hash, VariantArray, Clone, Copy)]
 #[repr(u8)]
 pub enum TNF {
     Empty = 0x00,
@@ -107,6 +107,19 @@ define_const_array!(
     (RTD_TEXT, RTD(b"T")),
     (RTD_URI, RTD(b"U")),
     (RTD_SMART_POSTER, RTD(b"Sp")),
+    (RTD_ACTION, RTD(b"act")),
+    (RTD_SIZE, RTD(b"s")),
+    (RTD_TYPE, RTD(b"t")),
+    (RTD_SIGNATURE, RTD(b"Sig")),
+    (RTD_TNEP_SERVICE_PARAMETER, RTD(b"Tp")),
+    (RTD_TNEP_SERVICE_SELECT, RTD(b"Ts")),
+    (RTD_TNEP_STATUS, RTD(b"Te")),
+    (RTD_HANDOVER_REQUEST, RTD(b"Hr")),
+    (RTD_HANDOVER_SELECT, RTD(b"Hs")),
+    (RTD_HANDOVER_CARRIER, RTD(b"Hc")),
+    (RTD_ALTERNATIVE_CARRIER, RTD(b"ac")),
+    (RTD_COLLISION_RESOLUTION, RTD(b"cr")),
+    (RTD_HANDOVER_ERROR, RTD(b"err")),
 );
 
 impl RTD {
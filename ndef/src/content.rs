@@ -0,0 +1,189 @@
+use crate::{
+    error::NdefError,
+    payload::*,
+    tag::{NFT2Tag, T2T_CC_OFFSET},
+    text::LocalizedTextPayload,
+    vcard::VCardPayload,
+    wifi::WifiCredential,
+    *,
+};
+
+/// Everything a reader usually wants from a tag, decoded in one pass.
+#[derive(Debug, Default)]
+pub struct TagContent {
+    message: NdefMessage,
+    urls: Vec<String>,
+    texts: Vec<String>,
+    wifi: Vec<WifiCredential>,
+    contacts: Vec<VCardPayload>,
+}
+
+impl TagContent {
+    /// Reads a Type 2 tag memory dump starting at page 0, as
+    /// [`NFT2Tag::from_bytes`] reads the CC page onwards. The message is
+    /// the one in the first non-empty NDEF TLV.
+    pub fn from_dump(dump: &[u8]) -> Result<Self> {
+        let image = dump.get(T2T_CC_OFFSET..).ok_or(NdefError::InvalidTagLength)?;
+        let (_, messages) = NFT2Tag::from_bytes(image)?;
+        Ok(messages.into_iter().next().map(Self::from_message).unwrap_or_default())
+    }
+
+    /// Classifies the records of an already decoded message. Records that
+    /// fail to parse as their declared type are left out of every list.
+    pub fn from_message(message: NdefMessage) -> Self {
+        let mut content = Self::default();
+        for record in message.records() {
+            content.classify(record, false);
+        }
+        content.message = message;
+        content
+    }
+
+    pub fn message(&self) -> &NdefMessage {
+        &self.message
+    }
+
+    /// URIs of URI and absolute URI records, and of Smart Posters.
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Text records, including Smart Poster titles.
+    pub fn texts(&self) -> &[String] {
+        &self.texts
+    }
+
+    pub fn wifi_credentials(&self) -> &[WifiCredential] {
+        &self.wifi
+    }
+
+    pub fn contacts(&self) -> &[VCardPayload] {
+        &self.contacts
+    }
+
+    /// Sorts `record` into the lists. Records nested in a Smart Poster are
+    /// classified too, but a Smart Poster inside one is not descended into.
+    fn classify(&mut self, record: &NdefRecord, in_poster: bool) {
+        match (record.tnf(), record.record_type()) {
+            (TNF::WellKnown, t) if t == RTD_URI.as_bytes() => {
+                if let Ok(uri) = UriPayload::try_from(record) {
+                    self.urls.push(uri.full_uri());
+                }
+            }
+            (TNF::WellKnown, t) if t == RTD_TEXT.as_bytes() => {
+                // Spec encoded text first, then the crate's raw UTF-8 form.
+                match LocalizedTextPayload::decode(record.payload()) {
+                    Ok(text) => self.texts.push(text.text().to_owned()),
+                    Err(_) => self.texts.push(String::from_utf8_lossy(record.payload()).into_owned()),
+                }
+            }
+            (TNF::WellKnown, t) if t == RTD_SMART_POSTER.as_bytes() && !in_poster => {
+                if let Ok(message) = NdefMessage::decode(record.payload()) {
+                    for nested in message.records() {
+                        self.classify(nested, true);
+                    }
+                }
+            }
+            (TNF::AbsoluteUri, _) => {
+                self.urls.push(String::from_utf8_lossy(record.record_type()).into_owned());
+            }
+            (TNF::MimeMedia, _) => {
+                if let Ok(contact) = VCardPayload::try_from(record) {
+                    self.contacts.push(contact);
+                } else if let Ok(credential) = WifiCredential::try_from(record) {
+                    self.wifi.push(credential);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tag::TlvValue,
+        text::LanguageTag,
+        wifi::{WifiAuth, WifiCipher},
+    };
+
+    #[test]
+    fn test_from_dump() {
+        let credential = WifiCredential::new("Home", "secret12", WifiAuth::Wpa2Personal, WifiCipher::Aes);
+        let text = LocalizedTextPayload::new(LanguageTag::new("en").unwrap(), "Hello");
+        let message = NdefMessage::from(&[
+            NdefRecord::builder()
+                .tnf(TNF::WellKnown)
+                .payload(&UriPayload::from_static("https://example.com"))
+                .build()
+                .unwrap(),
+            NdefRecord::builder().tnf(TNF::WellKnown).payload(&text).build().unwrap(),
+            credential.to_record().unwrap(),
+        ]);
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(144)
+            .add_tlv(TlvValue::Null)
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let dump = [vec![0u8; T2T_CC_OFFSET], tag.to_bytes().unwrap()].concat();
+
+        let content = TagContent::from_dump(&dump).unwrap();
+        assert_eq!(["https://example.com"], content.urls());
+        assert_eq!(["Hello"], content.texts());
+        assert_eq!([credential], content.wifi_credentials());
+        assert!(content.contacts().is_empty());
+        assert_eq!(3, content.message().records().len());
+
+        let mut blank = dump.clone();
+        blank[T2T_CC_OFFSET] = 0x00;
+        assert!(TagContent::from_dump(&blank).is_err());
+        assert!(TagContent::from_dump(&dump[..T2T_CC_OFFSET + 8]).is_err());
+
+        // Reserved areas and extended CCs are read as NFT2Tag reads them.
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(144)
+            .add_tlv(TlvValue::memory_control(24, 8, 4).unwrap())
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let dump = [vec![0u8; T2T_CC_OFFSET], tag.to_bytes().unwrap()].concat();
+        assert_eq!(3, TagContent::from_dump(&dump).unwrap().message().len());
+
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(4096)
+            .add_tlv(TlvValue::Proprietary(vec![0x11; 2100]))
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let mut dump = [vec![0u8; T2T_CC_OFFSET], tag.to_bytes().unwrap()].concat();
+        dump.resize(T2T_CC_OFFSET + 4 + 4096, 0);
+        assert_eq!(["https://example.com"], TagContent::from_dump(&dump).unwrap().urls());
+    }
+
+    #[test]
+    fn test_nested_smart_poster() {
+        let uri = NdefRecord::builder()
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        let inner = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .nested_message(RTD_SMART_POSTER, &NdefMessage::from(uri.clone()))
+            .unwrap()
+            .build()
+            .unwrap();
+        let outer = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .nested_message(RTD_SMART_POSTER, &NdefMessage::from(&[uri, inner]))
+            .unwrap()
+            .build()
+            .unwrap();
+        let content = TagContent::from_message(NdefMessage::from(outer));
+        assert_eq!(["https://example.com"], content.urls());
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{error::NdefError, payload::{RecordPayload, UriPayload}, record::NdefRecord, *};
+use std::borrow::Cow;
+
+const SGTIN_96_HEADER: u8 = 0x30;
+const SSCC_96_HEADER: u8 = 0x31;
+
+/// Bit widths of the company prefix and the reference for each partition
+/// value; partition `p` means a company prefix of `12 - p` digits.
+const SGTIN_PARTITIONS: [(u32, u32); 7] = [(40, 4), (37, 7), (34, 10), (30, 14), (27, 17), (24, 20), (20, 24)];
+const SSCC_PARTITIONS: [(u32, u32); 7] = [(40, 18), (37, 21), (34, 24), (30, 28), (27, 31), (24, 34), (20, 38)];
+
+/// Company prefix plus item reference (including the indicator digit).
+const SGTIN_DIGITS: usize = 13;
+/// Company prefix plus serial reference (including the extension digit).
+const SSCC_DIGITS: usize = 17;
+
+/// Serialised GTIN in its 96-bit EPC binary form (GS1 EPC Tag Data Standard).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Sgtin96 {
+    filter: u8,
+    company_prefix: String,
+    item_reference: String,
+    serial: u64,
+}
+
+impl Sgtin96 {
+    pub const MAX_SERIAL: u64 = (1 << 38) - 1;
+
+    /// `company_prefix` (6 to 12 digits) and `item_reference` must add up to
+    /// 13 digits; leading zeros are significant.
+    pub fn new<C, I>(filter: u8, company_prefix: C, item_reference: I, serial: u64) -> Result<Self>
+    where
+        C: Into<String>,
+        I: Into<String>,
+    {
+        let (company_prefix, item_reference) = (company_prefix.into(), item_reference.into());
+        partition(&company_prefix, &item_reference, SGTIN_DIGITS)?;
+        if filter > 7 || serial > Self::MAX_SERIAL {
+            return Err(NdefError::InvalidPayload);
+        }
+        Ok(Self {
+            filter,
+            company_prefix,
+            item_reference,
+            serial,
+        })
+    }
+
+    pub fn filter(&self) -> u8 {
+        self.filter
+    }
+
+    pub fn company_prefix(&self) -> &str {
+        &self.company_prefix
+    }
+
+    pub fn item_reference(&self) -> &str {
+        &self.item_reference
+    }
+
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    pub fn encode(&self) -> [u8; 12] {
+        let p = partition(&self.company_prefix, &self.item_reference, SGTIN_DIGITS).unwrap_or_default();
+        let (company_bits, item_bits) = SGTIN_PARTITIONS[p as usize];
+        let mut value = SGTIN_96_HEADER as u128;
+        push(&mut value, 3, self.filter as u64);
+        push(&mut value, 3, p as u64);
+        push(&mut value, company_bits, self.company_prefix.parse().unwrap_or_default());
+        push(&mut value, item_bits, self.item_reference.parse().unwrap_or_default());
+        push(&mut value, 38, self.serial);
+        to_bytes(value)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data, SGTIN_96_HEADER)?;
+        let filter = reader.take(3) as u8;
+        let (company_prefix, item_reference) = reader.partitioned(&SGTIN_PARTITIONS, SGTIN_DIGITS)?;
+        let serial = reader.take(38);
+        Self::new(filter, company_prefix, item_reference, serial)
+    }
+
+    /// `urn:epc:tag:sgtin-96:<filter>.<company>.<item>.<serial>`
+    pub fn to_tag_uri(&self) -> String {
+        format!(
+            "urn:epc:tag:sgtin-96:{}.{}.{}.{}",
+            self.filter, self.company_prefix, self.item_reference, self.serial
+        )
+    }
+
+    /// `urn:epc:id:sgtin:<company>.<item>.<serial>`; drops the filter value.
+    pub fn to_pure_identity_uri(&self) -> String {
+        format!(
+            "urn:epc:id:sgtin:{}.{}.{}",
+            self.company_prefix, self.item_reference, self.serial
+        )
+    }
+
+    /// Parses a tag URI or a pure identity URI, the latter with filter 0.
+    pub fn parse(uri: &str) -> Result<Self> {
+        let (filter, fields) = fields(uri, "sgtin-96:", "sgtin:", 3)?;
+        Self::new(filter, fields[0], fields[1], serial(fields[2])?).map_err(|_| NdefError::InvalidUri)
+    }
+}
+
+impl RecordPayload for Sgtin96 {
+    fn tnf(&self) -> TNF {
+        TNF::WellKnown
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(RTD_URI.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(uri_payload(&self.to_tag_uri()))
+    }
+}
+
+impl TryFrom<&NdefRecord> for Sgtin96 {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        Self::parse(&UriPayload::try_from(record)?.full_uri())
+    }
+}
+
+/// Serial Shipping Container Code in its 96-bit EPC binary form.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Sscc96 {
+    filter: u8,
+    company_prefix: String,
+    serial_reference: String,
+}
+
+impl Sscc96 {
+    /// `company_prefix` (6 to 12 digits) and `serial_reference` must add up
+    /// to 17 digits; leading zeros are significant.
+    pub fn new<C, S>(filter: u8, company_prefix: C, serial_reference: S) -> Result<Self>
+    where
+        C: Into<String>,
+        S: Into<String>,
+    {
+        let (company_prefix, serial_reference) = (company_prefix.into(), serial_reference.into());
+        partition(&company_prefix, &serial_reference, SSCC_DIGITS)?;
+        if filter > 7 {
+            return Err(NdefError::InvalidPayload);
+        }
+        Ok(Self {
+            filter,
+            company_prefix,
+            serial_reference,
+        })
+    }
+
+    pub fn filter(&self) -> u8 {
+        self.filter
+    }
+
+    pub fn company_prefix(&self) -> &str {
+        &self.company_prefix
+    }
+
+    pub fn serial_reference(&self) -> &str {
+        &self.serial_reference
+    }
+
+    pub fn encode(&self) -> [u8; 12] {
+        let p = partition(&self.company_prefix, &self.serial_reference, SSCC_DIGITS).unwrap_or_default();
+        let (company_bits, serial_bits) = SSCC_PARTITIONS[p as usize];
+        let mut value = SSCC_96_HEADER as u128;
+        push(&mut value, 3, self.filter as u64);
+        push(&mut value, 3, p as u64);
+        push(&mut value, company_bits, self.company_prefix.parse().unwrap_or_default());
+        push(&mut value, serial_bits, self.serial_reference.parse().unwrap_or_default());
+        // 24 reserved bits.
+        push(&mut value, 24, 0);
+        to_bytes(value)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(data, SSCC_96_HEADER)?;
+        let filter = reader.take(3) as u8;
+        let (company_prefix, serial_reference) = reader.partitioned(&SSCC_PARTITIONS, SSCC_DIGITS)?;
+        Self::new(filter, company_prefix, serial_reference)
+    }
+
+    /// `urn:epc:tag:sscc-96:<filter>.<company>.<serial reference>`
+    pub fn to_tag_uri(&self) -> String {
+        format!(
+            "urn:epc:tag:sscc-96:{}.{}.{}",
+            self.filter, self.company_prefix, self.serial_reference
+        )
+    }
+
+    /// `urn:epc:id:sscc:<company>.<serial reference>`; drops the filter value.
+    pub fn to_pure_identity_uri(&self) -> String {
+        format!("urn:epc:id:sscc:{}.{}", self.company_prefix, self.serial_reference)
+    }
+
+    /// Parses a tag URI or a pure identity URI, the latter with filter 0.
+    pub fn parse(uri: &str) -> Result<Self> {
+        let (filter, fields) = fields(uri, "sscc-96:", "sscc:", 2)?;
+        Self::new(filter, fields[0], fields[1]).map_err(|_| NdefError::InvalidUri)
+    }
+}
+
+impl RecordPayload for Sscc96 {
+    fn tnf(&self) -> TNF {
+        TNF::WellKnown
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(RTD_URI.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(uri_payload(&self.to_tag_uri()))
+    }
+}
+
+impl TryFrom<&NdefRecord> for Sscc96 {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        Self::parse(&UriPayload::try_from(record)?.full_uri())
+    }
+}
+
+fn uri_payload(tag_uri: &str) -> Vec<u8> {
+    let mut buffer = vec![URN_EPC_TAG.as_byte()];
+    buffer.extend_from_slice(&tag_uri.as_bytes()[URN_EPC_TAG.as_uri().len()..]);
+    buffer
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Partition value for a company prefix and reference of `total` digits.
+fn partition(company_prefix: &str, reference: &str, total: usize) -> Result<u8> {
+    if !is_digits(company_prefix)
+        || !is_digits(reference)
+        || !(6..=12).contains(&company_prefix.len())
+        || company_prefix.len() + reference.len() != total
+    {
+        return Err(NdefError::InvalidPayload);
+    }
+    Ok((12 - company_prefix.len()) as u8)
+}
+
+fn push(value: &mut u128, bits: u32, field: u64) {
+    *value = (*value << bits) | (field as u128 & ((1 << bits) - 1));
+}
+
+fn to_bytes(value: u128) -> [u8; 12] {
+    let mut bytes = [0u8; 12];
+    bytes.copy_from_slice(&value.to_be_bytes()[4..]);
+    bytes
+}
+
+struct BitReader {
+    value: u128,
+    remaining: u32,
+}
+
+impl BitReader {
+    fn new(data: &[u8], header: u8) -> Result<Self> {
+        if data.len() != 12 || data[0] != header {
+            return Err(NdefError::InvalidPayload);
+        }
+        let mut bytes = [0u8; 16];
+        bytes[4..].copy_from_slice(data);
+        Ok(Self {
+            value: u128::from_be_bytes(bytes),
+            remaining: 88,
+        })
+    }
+
+    fn take(&mut self, bits: u32) -> u64 {
+        self.remaining -= bits;
+        ((self.value >> self.remaining) & ((1 << bits) - 1)) as u64
+    }
+
+    /// Reads the partition value followed by the company prefix and
+    /// reference fields it describes, as zero-padded digit strings.
+    fn partitioned(&mut self, partitions: &[(u32, u32); 7], total: usize) -> Result<(String, String)> {
+        let p = self.take(3) as usize;
+        let &(company_bits, reference_bits) = partitions.get(p).ok_or(NdefError::InvalidPayload)?;
+        let company_digits = 12 - p;
+        let company = self.take(company_bits);
+        let reference = self.take(reference_bits);
+        let (company, reference) = (
+            format!("{:0width$}", company, width = company_digits),
+            format!("{:0width$}", reference, width = total - company_digits),
+        );
+        if company.len() != company_digits || reference.len() != total - company_digits {
+            return Err(NdefError::InvalidPayload);
+        }
+        Ok((company, reference))
+    }
+}
+
+/// Splits a `urn:epc:tag:<tag_scheme>` or `urn:epc:id:<id_scheme>` URI into
+/// its filter value and the remaining `count` dot-separated fields.
+fn fields<'a>(uri: &'a str, tag_scheme: &str, id_scheme: &str, count: usize) -> Result<(u8, Vec<&'a str>)> {
+    let strip = |prefix: &str| {
+        uri.get(..prefix.len())
+            .filter(|head| head.eq_ignore_ascii_case(prefix))
+            .map(|_| &uri[prefix.len()..])
+    };
+    let (filter, rest) = if let Some(rest) = strip(&format!("urn:epc:tag:{}", tag_scheme)) {
+        let (filter, rest) = rest.split_once('.').ok_or(NdefError::InvalidUri)?;
+        if filter.len() != 1 || !is_digits(filter) {
+            return Err(NdefError::InvalidUri);
+        }
+        (filter.parse().map_err(|_| NdefError::InvalidUri)?, rest)
+    } else if let Some(rest) = strip(&format!("urn:epc:id:{}", id_scheme)) {
+        (0, rest)
+    } else {
+        return Err(NdefError::InvalidUri);
+    };
+    let fields: Vec<&str> = rest.split('.').collect();
+    if fields.len() != count {
+        return Err(NdefError::InvalidUri);
+    }
+    Ok((filter, fields))
+}
+
+/// Numeric serial without leading zeros, as SGTIN-96 requires.
+fn serial(value: &str) -> Result<u64> {
+    if !is_digits(value) || (value.len() > 1 && value.starts_with('0')) {
+        return Err(NdefError::InvalidUri);
+    }
+    value.parse().map_err(|_| NdefError::InvalidUri)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sgtin_96() {
+        let sgtin = Sgtin96::new(3, "0614141", "812345", 6789).unwrap();
+        assert_eq!("3074257bf7194e4000001a85", hex::encode(sgtin.encode()));
+        assert_eq!(sgtin, Sgtin96::decode(&sgtin.encode()).unwrap());
+        assert_eq!("urn:epc:tag:sgtin-96:3.0614141.812345.6789", sgtin.to_tag_uri());
+        assert_eq!("urn:epc:id:sgtin:0614141.812345.6789", sgtin.to_pure_identity_uri());
+
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&sgtin)
+            .build()
+            .unwrap();
+        assert_eq!(URN_EPC_TAG.as_byte(), record.payload()[0]);
+        assert_eq!(sgtin, Sgtin96::try_from(&record).unwrap());
+
+        let parsed = Sgtin96::parse("urn:epc:id:sgtin:0614141.812345.6789").unwrap();
+        assert_eq!(0, parsed.filter());
+        assert!(Sgtin96::parse("urn:epc:id:sgtin:0614141.812345.06789").is_err());
+        assert!(Sgtin96::new(3, "0614141", "81234", 1).is_err());
+        assert!(Sgtin96::new(8, "0614141", "812345", 1).is_err());
+        assert!(Sgtin96::new(0, "0614141", "812345", Sgtin96::MAX_SERIAL + 1).is_err());
+    }
+
+    #[test]
+    fn test_sscc_96() {
+        let sscc = Sscc96::new(3, "0614141", "1234567890").unwrap();
+        assert_eq!("3174257bf4499602d2000000", hex::encode(sscc.encode()));
+        assert_eq!(sscc, Sscc96::decode(&sscc.encode()).unwrap());
+        assert_eq!("urn:epc:tag:sscc-96:3.0614141.1234567890", sscc.to_tag_uri());
+        assert_eq!(sscc, Sscc96::parse(&sscc.to_tag_uri()).unwrap());
+        assert_eq!(
+            "urn:epc:id:sscc:0614141.1234567890",
+            Sscc96::parse("urn:epc:id:sscc:0614141.1234567890").unwrap().to_pure_identity_uri()
+        );
+        assert!(Sscc96::decode(&Sgtin96::new(0, "0614141", "812345", 1).unwrap().encode()).is_err());
+    }
+}
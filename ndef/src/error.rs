@@ -1,3 +1,4 @@
+use crate::{audit::Field, TNF};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -22,6 +23,8 @@ pub enum NdefError {
     InvalidFlags,
     #[error("Invalid record")]
     InvalidRecord,
+    #[error("Invalid version")]
+    InvalidVersion,
     #[error("Invalid message")]
     InvalidMessage,
     #[error("Invalid tag")]
@@ -36,6 +39,93 @@ pub enum NdefError {
     InvalidTagVersion,
     #[error("Invalid tag memory size")]
     InvalidTagMemorySize,
+    #[error("Buffer too small: {needed} bytes needed, {available} available")]
+    BufferTooSmall { needed: usize, available: usize },
+    #[error("Capacity exceeded: {needed} bytes needed, {available} available")]
+    CapacityExceeded { needed: usize, available: usize },
+    #[error("Invalid TLV: {0}")]
+    InvalidTlv(#[from] TlvError),
+    #[error("Decode error: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("Encode error: {0}")]
+    Encode(#[from] EncodeError),
     #[error("Other error: {0}")]
     Other(#[from] anyhow::Error),
-}
\ No newline at end of file
+}
+
+/// Structural problems in a TLV block of a tag data area.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum TlvError {
+    #[error("no TLV in an empty buffer")]
+    Empty,
+    #[error("TLV 0x{tag:02x} is truncated")]
+    Truncated { tag: u8 },
+    #[error("TLV 0x{tag:02x} has length {len}, expected {expected}")]
+    InvalidLength { tag: u8, len: usize, expected: usize },
+    #[error("TLV value of {0} bytes exceeds 65534 bytes")]
+    TooLong(usize),
+    #[error("invalid control area: {0}")]
+    InvalidControlArea(&'static str),
+}
+
+/// Where and why decoding a message failed.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+#[error("{kind} at byte {offset} (record {record})")]
+pub struct DecodeError {
+    /// Offset into the decoded buffer: the start of the truncated field, or
+    /// of the offending record for the other kinds.
+    pub offset: usize,
+    /// Index of the record on the wire, counting chunks separately. For
+    /// trailing bytes, the index a record starting there would have.
+    pub record: usize,
+    pub kind: DecodeErrorKind,
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum DecodeErrorKind {
+    #[error("truncated {field:?} field, expected {expected} bytes, found {actual}")]
+    Truncated { field: Field, expected: usize, actual: usize },
+    #[error("MB flag expected {expected}, found {actual}")]
+    MessageBegin { expected: bool, actual: bool },
+    #[error("ME flag missing on the last record")]
+    MissingMessageEnd,
+    #[error("{0} bytes after the record with the ME flag")]
+    TrailingBytes(usize),
+    #[error("reserved TNF")]
+    ReservedTnf,
+    #[error("invalid chunk: {0}")]
+    InvalidChunk(&'static str),
+    #[error("payload of {len} bytes exceeds the limit of {limit}")]
+    PayloadTooLarge { len: usize, limit: usize },
+    #[error("more than {0} records")]
+    TooManyRecords(usize),
+    #[error("message exceeds the limit of {0} bytes")]
+    MessageTooLarge(usize),
+}
+
+/// Inconsistencies found in a record or message before it is serialized.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum EncodeError {
+    #[error("reserved TNF")]
+    ReservedTnf,
+    #[error("TNF Empty record with a type, id or payload")]
+    NonEmptyEmptyRecord,
+    #[error("TNF {0:?} requires an empty type")]
+    UnexpectedType(TNF),
+    #[error("TNF {0:?} requires a type")]
+    MissingType(TNF),
+    #[error("type of {0} bytes exceeds 255 bytes")]
+    TypeTooLong(usize),
+    #[error("id of {0} bytes exceeds 255 bytes")]
+    IdTooLong(usize),
+    #[error("payload of {0} bytes exceeds 4 GiB")]
+    PayloadTooLong(usize),
+    #[error("SR flag set on a {0} byte payload")]
+    ShortRecordTooLong(usize),
+    #[error("IL flag does not match the presence of an id")]
+    IdFlagMismatch,
+    #[error("TNF Unchanged outside a chunk sequence")]
+    UnexpectedChunk,
+    #[error("chunk sequence is not terminated")]
+    UnterminatedChunk,
+}
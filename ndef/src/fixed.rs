@@ -0,0 +1,185 @@
+//! Fixed-capacity records and messages backed by `heapless::Vec`, sized
+//! at compile time. Encoding writes into a caller supplied buffer and
+//! decoding copies out of a borrowed [`NdefMessageRef`], so neither path
+//! allocates when it succeeds. The crate itself still depends on `std`.
+
+use crate::{error::NdefError, record::RecordHeader, *};
+use heapless::Vec;
+
+/// Longest record type a [`FixedRecord`] can hold.
+pub const MAX_TYPE_LEN: usize = 64;
+/// Longest record ID a [`FixedRecord`] can hold.
+pub const MAX_ID_LEN: usize = 32;
+
+/// Record with at most `N` payload bytes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FixedRecord<const N: usize> {
+    tnf: TNF,
+    record_type: Vec<u8, MAX_TYPE_LEN>,
+    id: Vec<u8, MAX_ID_LEN>,
+    payload: Vec<u8, N>,
+}
+
+impl<const N: usize> FixedRecord<N> {
+    pub fn new(tnf: TNF, record_type: &[u8], payload: &[u8]) -> Result<Self> {
+        Ok(Self {
+            tnf,
+            record_type: Vec::from_slice(record_type).map_err(|_| NdefError::InvalidRecordType)?,
+            id: Vec::new(),
+            payload: Vec::from_slice(payload).map_err(|_| NdefError::InvalidPayload)?,
+        })
+    }
+
+    pub fn with_id(mut self, id: &[u8]) -> Result<Self> {
+        self.id = Vec::from_slice(id).map_err(|_| NdefError::InvalidId)?;
+        Ok(self)
+    }
+
+    /// Copies a borrowed record, failing if a field exceeds its capacity.
+    pub fn from_ref(record: &NdefRecordRef<'_>) -> Result<Self> {
+        Self::new(record.tnf(), record.record_type(), record.payload())?.with_id(record.id().unwrap_or_default())
+    }
+
+    pub fn tnf(&self) -> TNF {
+        self.tnf
+    }
+
+    pub fn record_type(&self) -> &[u8] {
+        &self.record_type
+    }
+
+    pub fn id(&self) -> Option<&[u8]> {
+        (!self.id.is_empty()).then_some(self.id.as_slice())
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    fn header(&self, flag: RecordFlags) -> RecordHeader {
+        let mut flags = flag & (RecordFlags::MB | RecordFlags::ME);
+        flags.set(RecordFlags::SR, self.payload.len() < 256);
+        flags.set(RecordFlags::IL, !self.id.is_empty());
+        RecordHeader {
+            flags,
+            tnf: self.tnf,
+            type_len: self.record_type.len() as u8,
+            payload_len: self.payload.len() as u32,
+            id_len: self.id.len() as u8,
+        }
+    }
+}
+
+/// Message of at most `R` records, each with at most `N` payload bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct FixedMessage<const R: usize, const N: usize> {
+    records: Vec<FixedRecord<N>, R>,
+}
+
+impl<const R: usize, const N: usize> FixedMessage<R, N> {
+    pub fn new() -> Self {
+        Self { records: Vec::new() }
+    }
+
+    /// Appends `record`, failing once the message holds `R` records.
+    pub fn push(&mut self, record: FixedRecord<N>) -> Result<()> {
+        self.records.push(record).map_err(|_| NdefError::CapacityExceeded {
+            needed: R + 1,
+            available: R,
+        })
+    }
+
+    pub fn records(&self) -> &[FixedRecord<N>] {
+        &self.records
+    }
+
+    /// Copies the records of a borrowed message.
+    pub fn from_ref(message: &NdefMessageRef<'_>) -> Result<Self> {
+        let mut fixed = Self::new();
+        for record in message.records() {
+            fixed.push(FixedRecord::from_ref(&record)?)?;
+        }
+        Ok(fixed)
+    }
+
+    /// Exact size of the [`FixedMessage::encode_into`] output.
+    pub fn encoded_len(&self) -> usize {
+        (0..self.records.len())
+            .map(|index| {
+                let header = self.records[index].header(self.record_flag(index));
+                header.encoded_len() + header.body_len()
+            })
+            .sum()
+    }
+
+    /// Encodes the message into `buffer`, returning the number of bytes
+    /// written. Nothing is written if `buffer` is too small.
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize> {
+        let needed = self.encoded_len();
+        if buffer.len() < needed {
+            return Err(NdefError::BufferTooSmall {
+                needed,
+                available: buffer.len(),
+            });
+        }
+        let mut writer = &mut buffer[..needed];
+        for (index, record) in self.records.iter().enumerate() {
+            record.header(self.record_flag(index)).write(&mut writer)?;
+            for field in [record.record_type(), &record.id, record.payload()] {
+                let (head, rest) = std::mem::take(&mut writer).split_at_mut(field.len());
+                head.copy_from_slice(field);
+                writer = rest;
+            }
+        }
+        Ok(needed)
+    }
+
+    /// MB and ME bits of the record at `index`.
+    fn record_flag(&self, index: usize) -> RecordFlags {
+        let mut flag = RecordFlags::empty();
+        flag.set(RecordFlags::MB, index == 0);
+        flag.set(RecordFlags::ME, index + 1 == self.records.len());
+        flag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::*;
+
+    #[test]
+    fn test_fixed_message() {
+        let uri = UriPayload::from_static("https://example.com");
+        let mut message = FixedMessage::<2, 32>::new();
+        message
+            .push(FixedRecord::new(TNF::WellKnown, b"U", &uri.payload()).unwrap())
+            .unwrap();
+        message
+            .push(FixedRecord::new(TNF::Unknown, b"", &[1, 2, 3]).unwrap().with_id(b"x").unwrap())
+            .unwrap();
+        assert!(matches!(
+            message.push(FixedRecord::new(TNF::Unknown, b"", &[]).unwrap()),
+            Err(NdefError::CapacityExceeded { needed: 3, available: 2 })
+        ));
+        assert!(FixedRecord::<2>::new(TNF::Unknown, b"", &[1, 2, 3]).is_err());
+
+        let mut buffer = [0u8; 64];
+        let len = message.encode_into(&mut buffer).unwrap();
+        let expected = NdefMessage::from(&[
+            NdefRecord::builder().tnf(TNF::WellKnown).payload(&uri).build().unwrap(),
+            NdefRecord::builder().tnf(TNF::Unknown).id(b"x".to_vec()).unknown(vec![1, 2, 3]).build().unwrap(),
+        ]);
+        assert_eq!(expected.to_buffer().unwrap(), &buffer[..len]);
+        assert_eq!(len, message.encoded_len());
+        let mut short = [0u8; 64];
+        assert!(matches!(
+            message.encode_into(&mut short[..len - 1]),
+            Err(NdefError::BufferTooSmall { needed, available }) if needed == len && available == len - 1
+        ));
+        assert_eq!([0u8; 64], short);
+
+        let parsed = NdefMessageRef::parse(&buffer[..len]).unwrap();
+        assert_eq!(message, FixedMessage::from_ref(&parsed).unwrap());
+    }
+}
@@ -0,0 +1,92 @@
+//! `Arbitrary` implementations generating well-formed records and messages,
+//! so `NdefMessage::decode(msg.to_buffer())` round trips can be fuzzed.
+
+use crate::{payload::*, *};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// TNFs a standalone record may carry; Unchanged only appears in chunks and
+/// Reserved is never valid.
+const RECORD_TNFS: [TNF; 6] = [
+    TNF::Empty,
+    TNF::WellKnown,
+    TNF::MimeMedia,
+    TNF::AbsoluteUri,
+    TNF::External,
+    TNF::Unknown,
+];
+const MAX_FIELD_LEN: usize = 0xff;
+const MAX_RECORDS: usize = 8;
+
+fn field(u: &mut Unstructured<'_>) -> Result<Vec<u8>> {
+    let mut value = Vec::<u8>::arbitrary(u)?;
+    value.truncate(MAX_FIELD_LEN);
+    Ok(value)
+}
+
+impl<'a> Arbitrary<'a> for TNF {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&RECORD_TNFS)?)
+    }
+}
+
+impl<'a> Arbitrary<'a> for NdefRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let tnf = TNF::arbitrary(u)?;
+        let record_type = field(u)?;
+        let id = field(u)?;
+        let payload = Vec::<u8>::arbitrary(u)?;
+        Ok(NdefRecord::sanitized(tnf, record_type, id, payload).unwrap())
+    }
+}
+
+impl<'a> Arbitrary<'a> for NdefMessage {
+    /// One to eight records, since an empty message has no encoding.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let count = u.int_in_range(1..=MAX_RECORDS)?;
+        let records = (0..count).map(|_| NdefRecord::arbitrary(u)).collect::<Result<Vec<_>>>()?;
+        Ok(NdefMessage::from_vec(records))
+    }
+}
+
+impl<'a> Arbitrary<'a> for UriPayload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let abbrev = *u.choose(&URI_ABBREVIATIONS)?;
+        Ok(UriPayload::with_abbrev(abbrev, String::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for TextPayload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(TextPayload::from_string(String::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ExternalPayload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut record_type = field(u)?;
+        if record_type.is_empty() {
+            record_type.extend_from_slice(b"example.com:t");
+        }
+        Ok(ExternalPayload::from_raw(record_type, Vec::<u8>::arbitrary(u)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arbitrary_round_trip() {
+        let data: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        let mut u = Unstructured::new(&data);
+        while !u.is_empty() {
+            let message = NdefMessage::arbitrary(&mut u).unwrap();
+            let bytes = message.to_buffer().unwrap();
+            assert_eq!(message.records(), NdefMessage::decode(&bytes).unwrap().records());
+
+            let uri = UriPayload::arbitrary(&mut u).unwrap();
+            let record = NdefRecord::builder().tnf(TNF::WellKnown).payload(&uri).build().unwrap();
+            assert_eq!(uri, UriPayload::try_from(&record).unwrap());
+        }
+    }
+}
@@ -0,0 +1,815 @@
+use crate::{error::NdefError, payload::RecordPayload, record::NdefRecord, *};
+use std::borrow::Cow;
+
+/// Connection Handover version implemented by this crate.
+pub const HANDOVER_VERSION: HandoverVersion = HandoverVersion::new(1, 5);
+
+/// Version byte carried as the first octet of Hr/Hs payloads.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct HandoverVersion {
+    major: u8,
+    minor: u8,
+}
+
+impl HandoverVersion {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self {
+            major: major & 0x0f,
+            minor: minor & 0x0f,
+        }
+    }
+
+    pub fn from_byte(value: u8) -> Self {
+        Self::new(value >> 4, value & 0x0f)
+    }
+
+    pub fn as_byte(&self) -> u8 {
+        (self.major << 4) | self.minor
+    }
+
+    pub fn major(&self) -> u8 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u8 {
+        self.minor
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum CarrierPowerState {
+    Inactive = 0x00,
+    Active = 0x01,
+    Activating = 0x02,
+    Unknown = 0x03,
+}
+
+impl CarrierPowerState {
+    fn from_bits(value: u8) -> Self {
+        match value & 0x03 {
+            0x00 => CarrierPowerState::Inactive,
+            0x01 => CarrierPowerState::Active,
+            0x02 => CarrierPowerState::Activating,
+            _ => CarrierPowerState::Unknown,
+        }
+    }
+}
+
+/// Alternative Carrier ("ac") record.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AlternativeCarrier {
+    power_state: CarrierPowerState,
+    carrier_data_reference: Vec<u8>,
+    auxiliary_data_references: Vec<Vec<u8>>,
+}
+
+impl AlternativeCarrier {
+    pub fn new<T: Into<Vec<u8>>>(
+        power_state: CarrierPowerState,
+        carrier_data_reference: T,
+    ) -> Result<Self> {
+        let carrier_data_reference = carrier_data_reference.into();
+        if carrier_data_reference.len() > 0xff {
+            return Err(NdefError::InvalidId);
+        }
+        Ok(Self {
+            power_state,
+            carrier_data_reference,
+            auxiliary_data_references: vec![],
+        })
+    }
+
+    pub fn add_auxiliary_reference<T: Into<Vec<u8>>>(mut self, reference: T) -> Result<Self> {
+        let reference = reference.into();
+        if reference.len() > 0xff || self.auxiliary_data_references.len() == 0xff {
+            return Err(NdefError::InvalidId);
+        }
+        self.auxiliary_data_references.push(reference);
+        Ok(self)
+    }
+
+    pub fn power_state(&self) -> CarrierPowerState {
+        self.power_state
+    }
+
+    pub fn carrier_data_reference(&self) -> &[u8] {
+        &self.carrier_data_reference
+    }
+
+    pub fn auxiliary_data_references(&self) -> &[Vec<u8>] {
+        &self.auxiliary_data_references
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![self.power_state as u8];
+        buffer.push(self.carrier_data_reference.len() as u8);
+        buffer.extend_from_slice(&self.carrier_data_reference);
+        buffer.push(self.auxiliary_data_references.len() as u8);
+        for reference in self.auxiliary_data_references.iter() {
+            buffer.push(reference.len() as u8);
+            buffer.extend_from_slice(reference);
+        }
+        buffer
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader(data);
+        let power_state = CarrierPowerState::from_bits(reader.byte()?);
+        let len = reader.byte()? as usize;
+        let carrier_data_reference = reader.take(len)?.to_vec();
+        let count = reader.byte()?;
+        let mut auxiliary_data_references = vec![];
+        for _ in 0..count {
+            let len = reader.byte()? as usize;
+            auxiliary_data_references.push(reader.take(len)?.to_vec());
+        }
+        Ok(Self {
+            power_state,
+            carrier_data_reference,
+            auxiliary_data_references,
+        })
+    }
+
+    fn to_record(&self) -> Result<NdefRecord> {
+        raw_record(RTD_ALTERNATIVE_CARRIER, self.to_bytes())
+    }
+}
+
+/// Error ("err") record of a Handover Select message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HandoverError {
+    reason: u8,
+    data: Vec<u8>,
+}
+
+impl HandoverError {
+    pub const TEMPORARY_MEMORY_CONSTRAINTS: u8 = 0x01;
+    pub const PERMANENT_MEMORY_CONSTRAINTS: u8 = 0x02;
+    pub const CARRIER_SPECIFIC_CONSTRAINTS: u8 = 0x03;
+
+    pub fn new<T: Into<Vec<u8>>>(reason: u8, data: T) -> Self {
+        Self {
+            reason,
+            data: data.into(),
+        }
+    }
+
+    pub fn reason(&self) -> u8 {
+        self.reason
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn to_record(&self) -> Result<NdefRecord> {
+        let mut payload = vec![self.reason];
+        payload.extend_from_slice(&self.data);
+        raw_record(RTD_HANDOVER_ERROR, payload)
+    }
+}
+
+/// Differences found while mapping a handover message into the current model.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CompatibilityNote {
+    /// The message was written for an older minor version.
+    LegacyVersion(HandoverVersion),
+    /// The message declares a newer minor version; it was read as the current one.
+    NewerMinorVersion(HandoverVersion),
+    /// Handover Request without a collision resolution record (allowed before 1.2).
+    MissingCollisionResolution,
+    /// A nested record the current model does not know about was skipped.
+    IgnoredRecord(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CompatibilityReport {
+    source_version: HandoverVersion,
+    notes: Vec<CompatibilityNote>,
+}
+
+impl CompatibilityReport {
+    fn new(source_version: HandoverVersion) -> Self {
+        let mut notes = vec![];
+        if source_version < HANDOVER_VERSION {
+            notes.push(CompatibilityNote::LegacyVersion(source_version));
+        } else if source_version > HANDOVER_VERSION {
+            notes.push(CompatibilityNote::NewerMinorVersion(source_version));
+        }
+        Self {
+            source_version,
+            notes,
+        }
+    }
+
+    pub fn source_version(&self) -> HandoverVersion {
+        self.source_version
+    }
+
+    pub fn notes(&self) -> &[CompatibilityNote] {
+        &self.notes
+    }
+
+    /// Returns true when the message matched the current model without any shims.
+    pub fn is_exact(&self) -> bool {
+        self.notes.is_empty()
+    }
+}
+
+/// Handover Request ("Hr") record payload.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HandoverRequestPayload {
+    collision_resolution: Option<u16>,
+    carriers: Vec<AlternativeCarrier>,
+}
+
+impl HandoverRequestPayload {
+    pub fn new(collision_resolution: u16) -> Self {
+        Self {
+            collision_resolution: Some(collision_resolution),
+            carriers: vec![],
+        }
+    }
+
+    pub fn add_carrier(mut self, carrier: AlternativeCarrier) -> Self {
+        self.carriers.push(carrier);
+        self
+    }
+
+    pub fn version(&self) -> HandoverVersion {
+        HANDOVER_VERSION
+    }
+
+    pub fn collision_resolution(&self) -> Option<u16> {
+        self.collision_resolution
+    }
+
+    pub fn carriers(&self) -> &[AlternativeCarrier] {
+        &self.carriers
+    }
+
+    /// Reads a Handover Request record of any 1.x version, reporting the
+    /// shims that were needed to map it into the current model.
+    pub fn read_compat(record: &NdefRecord) -> Result<(Self, CompatibilityReport)> {
+        let (version, records) = split_handover(record, RTD_HANDOVER_REQUEST)?;
+        let mut report = CompatibilityReport::new(version);
+        let mut collision_resolution = None;
+        let mut carriers = vec![];
+        for nested in records.iter() {
+            if nested.record_type() == RTD_COLLISION_RESOLUTION.as_bytes() {
+                let value = nested.payload();
+                if value.len() != 2 {
+                    return Err(NdefError::InvalidPayload);
+                }
+                collision_resolution = Some(u16::from_be_bytes([value[0], value[1]]));
+            } else if nested.record_type() == RTD_ALTERNATIVE_CARRIER.as_bytes() {
+                carriers.push(AlternativeCarrier::decode(nested.payload())?);
+            } else {
+                report
+                    .notes
+                    .push(CompatibilityNote::IgnoredRecord(nested.record_type().to_vec()));
+            }
+        }
+        if collision_resolution.is_none() {
+            if version >= HandoverVersion::new(1, 2) {
+                return Err(NdefError::InvalidPayload);
+            }
+            report.notes.push(CompatibilityNote::MissingCollisionResolution);
+        }
+        Ok((
+            Self {
+                collision_resolution,
+                carriers,
+            },
+            report,
+        ))
+    }
+
+    /// The Hr record, with the collision resolution and alternative
+    /// carrier records nested in its payload.
+    pub fn to_record(&self) -> Result<NdefRecord> {
+        handover_record(RTD_HANDOVER_REQUEST, &self.nested_message()?)
+    }
+
+    fn nested_message(&self) -> Result<NdefMessage> {
+        let mut message = NdefMessage::default();
+        if let Some(value) = self.collision_resolution {
+            message.add_record(raw_record(RTD_COLLISION_RESOLUTION, value.to_be_bytes())?);
+        }
+        for carrier in self.carriers.iter() {
+            message.add_record(carrier.to_record()?);
+        }
+        Ok(message)
+    }
+}
+
+impl TryFrom<&NdefRecord> for HandoverRequestPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        Self::read_compat(record).map(|(payload, _)| payload)
+    }
+}
+
+/// Handover Select ("Hs") record payload.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HandoverSelectPayload {
+    carriers: Vec<AlternativeCarrier>,
+    error: Option<HandoverError>,
+}
+
+impl HandoverSelectPayload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_carrier(mut self, carrier: AlternativeCarrier) -> Self {
+        self.carriers.push(carrier);
+        self
+    }
+
+    pub fn with_error(mut self, error: HandoverError) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn version(&self) -> HandoverVersion {
+        HANDOVER_VERSION
+    }
+
+    pub fn carriers(&self) -> &[AlternativeCarrier] {
+        &self.carriers
+    }
+
+    pub fn error(&self) -> Option<&HandoverError> {
+        self.error.as_ref()
+    }
+
+    /// Reads a Handover Select record of any 1.x version, reporting the
+    /// shims that were needed to map it into the current model.
+    pub fn read_compat(record: &NdefRecord) -> Result<(Self, CompatibilityReport)> {
+        let (version, records) = split_handover(record, RTD_HANDOVER_SELECT)?;
+        let mut report = CompatibilityReport::new(version);
+        let mut payload = Self::default();
+        for nested in records.iter() {
+            if nested.record_type() == RTD_ALTERNATIVE_CARRIER.as_bytes() {
+                payload.carriers.push(AlternativeCarrier::decode(nested.payload())?);
+            } else if nested.record_type() == RTD_HANDOVER_ERROR.as_bytes() {
+                let (reason, data) = nested
+                    .payload()
+                    .split_first()
+                    .ok_or(NdefError::InvalidPayload)?;
+                payload.error = Some(HandoverError::new(*reason, data));
+            } else {
+                report
+                    .notes
+                    .push(CompatibilityNote::IgnoredRecord(nested.record_type().to_vec()));
+            }
+        }
+        Ok((payload, report))
+    }
+
+    /// The Hs record, with the alternative carrier and error records
+    /// nested in its payload.
+    pub fn to_record(&self) -> Result<NdefRecord> {
+        handover_record(RTD_HANDOVER_SELECT, &self.nested_message()?)
+    }
+
+    fn nested_message(&self) -> Result<NdefMessage> {
+        let mut message = NdefMessage::default();
+        for carrier in self.carriers.iter() {
+            message.add_record(carrier.to_record()?);
+        }
+        if let Some(error) = self.error.as_ref() {
+            message.add_record(error.to_record()?);
+        }
+        Ok(message)
+    }
+}
+
+impl TryFrom<&NdefRecord> for HandoverSelectPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        Self::read_compat(record).map(|(payload, _)| payload)
+    }
+}
+
+pub const MIME_BLUETOOTH_EP_OOB: &str = "application/vnd.bluetooth.ep.oob";
+pub const MIME_BLUETOOTH_LE_OOB: &str = "application/vnd.bluetooth.le.oob";
+pub const MIME_WIFI_WSC: &str = "application/vnd.wfa.wsc";
+
+/// Carrier configuration record referenced by an alternative carrier.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CarrierConfiguration {
+    mime_type: Cow<'static, str>,
+    data: Vec<u8>,
+}
+
+impl CarrierConfiguration {
+    pub fn new<T: Into<Vec<u8>>>(mime_type: &'static str, data: T) -> Self {
+        Self {
+            mime_type: Cow::Borrowed(mime_type),
+            data: data.into(),
+        }
+    }
+
+    /// Bluetooth BR/EDR OOB data. `address` is given in display order
+    /// (00:11:22:33:44:55) and stored little-endian as the spec requires;
+    /// `eir` holds the optional EIR data structures.
+    pub fn bluetooth_oob(address: [u8; 6], eir: &[u8]) -> Result<Self> {
+        let total = 2 + address.len() + eir.len();
+        if total > u16::MAX as usize {
+            return Err(NdefError::InvalidPayload);
+        }
+        let mut data = (total as u16).to_le_bytes().to_vec();
+        data.extend(address.iter().rev());
+        data.extend_from_slice(eir);
+        Ok(Self::new(MIME_BLUETOOTH_EP_OOB, data))
+    }
+
+    /// Bluetooth LE OOB data (a sequence of AD structures).
+    pub fn bluetooth_le_oob<T: Into<Vec<u8>>>(data: T) -> Self {
+        Self::new(MIME_BLUETOOTH_LE_OOB, data)
+    }
+
+    /// Wi-Fi Simple Configuration data (WSC attributes).
+    pub fn wsc<T: Into<Vec<u8>>>(data: T) -> Self {
+        Self::new(MIME_WIFI_WSC, data)
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl RecordPayload for CarrierConfiguration {
+    fn tnf(&self) -> TNF {
+        TNF::MimeMedia
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.mime_type.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.data)
+    }
+}
+
+impl TryFrom<&NdefRecord> for CarrierConfiguration {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        if record.tnf() != TNF::MimeMedia {
+            return Err(NdefError::InvalidTnf);
+        }
+        let mime_type = std::str::from_utf8(record.record_type())
+            .map_err(|_| NdefError::InvalidEncoding)?;
+        Ok(Self {
+            mime_type: Cow::Owned(mime_type.to_string()),
+            data: record.payload().to_vec(),
+        })
+    }
+}
+
+/// Builds a static handover message: a Handover Select record followed by
+/// the carrier configuration records it references.
+#[derive(Debug, Default)]
+pub struct StaticHandover {
+    carriers: Vec<(CarrierPowerState, CarrierConfiguration)>,
+}
+
+impl StaticHandover {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn carrier(mut self, power_state: CarrierPowerState, config: CarrierConfiguration) -> Self {
+        self.carriers.push((power_state, config));
+        self
+    }
+
+    /// Assigns payload IDs ("0", "1", ...) to the carrier records and wires
+    /// the matching alternative carrier references into the Hs record.
+    pub fn build(self) -> Result<NdefMessage> {
+        if self.carriers.is_empty() {
+            return Err(NdefError::InvalidMessage);
+        }
+        select_message(self.carriers.iter().map(|(state, config)| (*state, config)))
+    }
+}
+
+/// Role taken after both devices sent a Handover Request.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NegotiationRole {
+    /// Keep the requester role and wait for the peer's Handover Select.
+    Requester,
+    /// Answer the peer's request with a Handover Select message.
+    Selector,
+    /// Both random numbers are equal; send a new request with a fresh number.
+    Retry,
+}
+
+/// Resolves a handover request collision from the local and remote
+/// collision resolution numbers as described in Connection Handover 1.2+.
+pub fn resolve_collision(local: u16, remote: u16) -> NegotiationRole {
+    if local == remote {
+        return NegotiationRole::Retry;
+    }
+    let same_parity = (local & 1) == (remote & 1);
+    if same_parity == (local > remote) {
+        NegotiationRole::Selector
+    } else {
+        NegotiationRole::Requester
+    }
+}
+
+/// Device-side negotiated handover: answers a received Handover Request
+/// with a Handover Select listing the local carriers the peer asked for.
+#[derive(Debug, Default)]
+pub struct HandoverResponder {
+    carriers: Vec<(CarrierPowerState, CarrierConfiguration)>,
+}
+
+impl HandoverResponder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn carrier(mut self, power_state: CarrierPowerState, config: CarrierConfiguration) -> Self {
+        self.carriers.push((power_state, config));
+        self
+    }
+
+    /// Carrier types requested by `request`, in the requester's order of preference.
+    pub fn requested_carriers(request: &NdefMessage) -> Result<Vec<Vec<u8>>> {
+        let first = request.records().first().ok_or(NdefError::InvalidMessage)?;
+        let hr = HandoverRequestPayload::try_from(first)?;
+        let mut types = vec![];
+        for carrier in hr.carriers() {
+            let record = request
+                .records()
+                .iter()
+                .find(|r| r.id() == Some(carrier.carrier_data_reference()))
+                .ok_or(NdefError::InvalidMessage)?;
+            types.push(carrier_type(record)?);
+        }
+        Ok(types)
+    }
+
+    /// Builds the Handover Select reply. Local carriers matching the
+    /// requested carrier types are listed in the requester's order; an
+    /// empty select message is returned when nothing matches.
+    pub fn respond(&self, request: &NdefMessage) -> Result<NdefMessage> {
+        let requested = Self::requested_carriers(request)?;
+        let selected = requested.iter().flat_map(|carrier| {
+            self.carriers
+                .iter()
+                .filter(move |(_, config)| config.mime_type().as_bytes() == carrier.as_slice())
+                .map(|(state, config)| (*state, config))
+        });
+        select_message(selected)
+    }
+}
+
+fn carrier_type(record: &NdefRecord) -> Result<Vec<u8>> {
+    match record.tnf() {
+        TNF::MimeMedia => Ok(record.record_type().to_vec()),
+        TNF::WellKnown if record.record_type() == RTD_HANDOVER_CARRIER.as_bytes() => {
+            // Hc payload: CTF, carrier type length, carrier type, carrier data.
+            let mut reader = ByteReader(record.payload());
+            reader.byte()?;
+            let len = reader.byte()? as usize;
+            Ok(reader.take(len)?.to_vec())
+        }
+        _ => Err(NdefError::InvalidRecordType),
+    }
+}
+
+fn select_message<'a, I>(carriers: I) -> Result<NdefMessage>
+where
+    I: Iterator<Item = (CarrierPowerState, &'a CarrierConfiguration)>,
+{
+    let mut select = HandoverSelectPayload::new();
+    let mut records = vec![];
+    for (index, (power_state, config)) in carriers.enumerate() {
+        let id = index.to_string().into_bytes();
+        select = select.add_carrier(AlternativeCarrier::new(power_state, id.clone())?);
+        records.push(
+            NdefRecord::builder()
+                .tnf(TNF::MimeMedia)
+                .id(id)
+                .payload(config)
+                .build()?,
+        );
+    }
+    let mut message = NdefMessage::from(select.to_record()?);
+    for record in records {
+        message.add_record(record);
+    }
+    Ok(message)
+}
+
+fn raw_record<T: Into<Vec<u8>>>(record_type: RTD, payload: T) -> Result<NdefRecord> {
+    NdefRecord::builder()
+        .tnf(TNF::WellKnown)
+        .payload_raw(record_type, payload)
+        .build()
+}
+
+fn split_handover(record: &NdefRecord, rtd: RTD) -> Result<(HandoverVersion, Vec<NdefRecord>)> {
+    if record.tnf() != TNF::WellKnown {
+        return Err(NdefError::InvalidTnf);
+    }
+    if record.record_type() != rtd.as_bytes() {
+        return Err(NdefError::InvalidRecordType);
+    }
+    let (version, nested) = record
+        .payload()
+        .split_first()
+        .ok_or(NdefError::InvalidPayload)?;
+    let version = HandoverVersion::from_byte(*version);
+    if version.major() != HANDOVER_VERSION.major() {
+        return Err(NdefError::InvalidVersion);
+    }
+    if nested.is_empty() {
+        return Ok((version, vec![]));
+    }
+    let message = NdefMessage::decode(nested)?;
+    Ok((version, message.records().to_vec()))
+}
+
+/// Handover record of type `rtd`: the version byte, then `nested` unless
+/// it has no records, as an empty message would encode as a lone empty
+/// record.
+fn handover_record(rtd: RTD, nested: &NdefMessage) -> Result<NdefRecord> {
+    let mut payload = vec![HANDOVER_VERSION.as_byte()];
+    if !nested.records().is_empty() {
+        payload.extend(nested.to_buffer()?);
+    }
+    NdefRecord::builder().tnf(TNF::WellKnown).payload_raw(rtd, payload).build()
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(NdefError::InvalidPayload);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_round_trip() {
+        let hs = HandoverSelectPayload::new()
+            .add_carrier(AlternativeCarrier::new(CarrierPowerState::Active, b"0".to_vec()).unwrap());
+        let record = hs.to_record().unwrap();
+        assert_eq!(
+            "15d10204616301013000",
+            hex::encode(record.payload())
+        );
+        let (decoded, report) = HandoverSelectPayload::read_compat(&record).unwrap();
+        assert_eq!(hs, decoded);
+        assert!(report.is_exact());
+    }
+
+    #[test]
+    fn test_static_handover() {
+        let address = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let message = StaticHandover::new()
+            .carrier(
+                CarrierPowerState::Active,
+                CarrierConfiguration::bluetooth_oob(address, &[]).unwrap(),
+            )
+            .carrier(CarrierPowerState::Inactive, CarrierConfiguration::wsc(vec![0x10, 0x4a]))
+            .build()
+            .unwrap();
+        let message = NdefMessage::decode(message.to_buffer().unwrap()).unwrap();
+        assert_eq!(3, message.records().len());
+
+        let hs = HandoverSelectPayload::try_from(&message.records()[0]).unwrap();
+        assert_eq!(2, hs.carriers().len());
+        for (carrier, record) in hs.carriers().iter().zip(&message.records()[1..]) {
+            assert_eq!(Some(carrier.carrier_data_reference()), record.id());
+        }
+
+        let bt = CarrierConfiguration::try_from(&message.records()[1]).unwrap();
+        assert_eq!(MIME_BLUETOOTH_EP_OOB, bt.mime_type());
+        assert_eq!("0800554433221100", hex::encode(bt.data()));
+        assert_eq!(CarrierPowerState::Inactive, hs.carriers()[1].power_state());
+
+        assert!(StaticHandover::new().build().is_err());
+    }
+
+    #[test]
+    fn test_negotiated_handover() {
+        let hr = HandoverRequestPayload::new(0x1234)
+            .add_carrier(AlternativeCarrier::new(CarrierPowerState::Active, b"w".to_vec()).unwrap())
+            .add_carrier(AlternativeCarrier::new(CarrierPowerState::Active, b"b".to_vec()).unwrap());
+        let (decoded, report) = HandoverRequestPayload::read_compat(&hr.to_record().unwrap()).unwrap();
+        assert_eq!((hr.clone(), true), (decoded, report.is_exact()));
+        let mut request = NdefMessage::from(hr.to_record().unwrap());
+        request.add_record(
+            NdefRecord::builder()
+                .tnf(TNF::MimeMedia)
+                .id(b"w".to_vec())
+                .payload(&CarrierConfiguration::wsc(vec![]))
+                .build()
+                .unwrap(),
+        );
+        request.add_record(
+            NdefRecord::builder()
+                .tnf(TNF::MimeMedia)
+                .id(b"b".to_vec())
+                .payload(&CarrierConfiguration::bluetooth_le_oob(vec![]))
+                .build()
+                .unwrap(),
+        );
+
+        let responder = HandoverResponder::new().carrier(
+            CarrierPowerState::Activating,
+            CarrierConfiguration::bluetooth_le_oob(vec![0x02, 0x1c, 0x00]),
+        );
+        let reply = responder.respond(&request).unwrap();
+        assert_eq!(2, reply.records().len());
+        let hs = HandoverSelectPayload::try_from(&reply.records()[0]).unwrap();
+        assert_eq!(1, hs.carriers().len());
+        assert_eq!(CarrierPowerState::Activating, hs.carriers()[0].power_state());
+        let config = CarrierConfiguration::try_from(&reply.records()[1]).unwrap();
+        assert_eq!(MIME_BLUETOOTH_LE_OOB, config.mime_type());
+
+        let reply = HandoverResponder::new().respond(&request).unwrap();
+        assert_eq!(1, reply.records().len());
+        assert_eq!([0x15], reply.records()[0].payload());
+        let (hs, report) = HandoverSelectPayload::read_compat(&reply.records()[0]).unwrap();
+        assert!(hs.carriers().is_empty());
+        assert!(report.is_exact());
+    }
+
+    #[test]
+    fn test_resolve_collision() {
+        assert_eq!(NegotiationRole::Retry, resolve_collision(7, 7));
+        assert_eq!(NegotiationRole::Selector, resolve_collision(9, 7));
+        assert_eq!(NegotiationRole::Requester, resolve_collision(7, 9));
+        assert_eq!(NegotiationRole::Selector, resolve_collision(6, 9));
+        assert_eq!(NegotiationRole::Requester, resolve_collision(9, 6));
+    }
+
+    #[test]
+    fn test_legacy_request() {
+        // Version 1.1 Handover Request without a collision resolution record.
+        let payload = hex::decode("11d10204616301013000").unwrap();
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload_raw(RTD_HANDOVER_REQUEST, payload)
+            .build()
+            .unwrap();
+        let (hr, report) = HandoverRequestPayload::read_compat(&record).unwrap();
+        assert_eq!(HANDOVER_VERSION, hr.version());
+        assert_eq!(None, hr.collision_resolution());
+        assert_eq!(1, hr.carriers().len());
+        assert_eq!(b"0", hr.carriers()[0].carrier_data_reference());
+        assert_eq!(HandoverVersion::new(1, 1), report.source_version());
+        assert_eq!(
+            &[
+                CompatibilityNote::LegacyVersion(HandoverVersion::new(1, 1)),
+                CompatibilityNote::MissingCollisionResolution,
+            ],
+            report.notes()
+        );
+
+        let payload = hex::decode("12d10204616301013000").unwrap();
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload_raw(RTD_HANDOVER_REQUEST, payload)
+            .build()
+            .unwrap();
+        assert!(HandoverRequestPayload::read_compat(&record).is_err());
+
+        let payload = hex::decode("2100").unwrap();
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload_raw(RTD_HANDOVER_REQUEST, payload)
+            .build()
+            .unwrap();
+        assert!(HandoverRequestPayload::read_compat(&record).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{error::NdefError, *};
+
+// Punycode parameters, RFC 3492 section 5.
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+const ACE_PREFIX: &str = "xn--";
+
+/// Converts an IRI to a URI that every reader can open: non-ASCII host
+/// labels become punycode (`xn--`) labels, other non-ASCII characters are
+/// percent-encoded as UTF-8. ASCII input is returned unchanged.
+pub fn to_uri(iri: &str) -> Result<String> {
+    let (before, host, after) = split_host(iri);
+    let host = host
+        .split('.')
+        .map(|label| {
+            if label.is_ascii() {
+                Ok(label.to_owned())
+            } else {
+                Ok(format!("{}{}", ACE_PREFIX, punycode_encode(&label.to_lowercase())?))
+            }
+        })
+        .collect::<Result<Vec<_>>>()?
+        .join(".");
+    Ok(format!("{}{}{}", encode_non_ascii(before), host, encode_non_ascii(after)))
+}
+
+/// Reverse of [`to_uri`] for display: punycode host labels and
+/// percent-encoded UTF-8 sequences are decoded. Escapes of ASCII
+/// characters are kept since decoding them could change the meaning.
+pub fn to_iri(uri: &str) -> String {
+    let (before, host, after) = split_host(uri);
+    let host = host
+        .split('.')
+        .map(|label| {
+            label
+                .get(..ACE_PREFIX.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(ACE_PREFIX))
+                .and_then(|_| punycode_decode(&label[ACE_PREFIX.len()..]))
+                .unwrap_or_else(|| label.to_owned())
+        })
+        .collect::<Vec<_>>()
+        .join(".");
+    format!("{}{}{}", decode_non_ascii(before), host, decode_non_ascii(after))
+}
+
+/// Splits `scheme://userinfo@` / host / `:port/path?query#fragment`. URIs
+/// without an authority, and IP literal hosts, yield an empty host.
+fn split_host(uri: &str) -> (&str, &str, &str) {
+    let Some(scheme_end) = uri.find("://") else {
+        return (uri, "", "");
+    };
+    let authority_start = scheme_end + 3;
+    let authority_end = uri[authority_start..]
+        .find(['/', '?', '#'])
+        .map_or(uri.len(), |i| authority_start + i);
+    let host_start = uri[authority_start..authority_end]
+        .rfind('@')
+        .map_or(authority_start, |i| authority_start + i + 1);
+    if uri[host_start..].starts_with('[') {
+        return (uri, "", "");
+    }
+    let host_end = uri[host_start..authority_end]
+        .find(':')
+        .map_or(authority_end, |i| host_start + i);
+    (&uri[..host_start], &uri[host_start..host_end], &uri[host_end..])
+}
+
+fn encode_non_ascii(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii() {
+            encoded.push(c);
+        } else {
+            let mut buffer = [0u8; 4];
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes runs of `%XX` escapes of non-ASCII bytes that form valid UTF-8.
+fn decode_non_ascii(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = String::with_capacity(value.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let mut run = vec![];
+        let mut end = index;
+        while let Some(byte) = bytes
+            .get(end..end + 3)
+            .filter(|escape| escape[0] == b'%')
+            .and_then(|escape| std::str::from_utf8(&escape[1..]).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .filter(|byte| *byte >= 0x80)
+        {
+            run.push(byte);
+            end += 3;
+        }
+        match String::from_utf8(run) {
+            Ok(text) if !text.is_empty() => {
+                decoded.push_str(&text);
+                index = end;
+            }
+            _ => {
+                // Copy one whole character so multi-byte input stays intact.
+                let c = value[index..].chars().next().unwrap();
+                decoded.push(c);
+                index += c.len_utf8();
+            }
+        }
+    }
+    decoded
+}
+
+fn adapt(mut delta: u32, points: u32, first: bool) -> u32 {
+    delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta /= BASE - TMIN;
+        k += BASE;
+    }
+    k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        TMIN
+    } else if k >= bias + TMAX {
+        TMAX
+    } else {
+        k - bias
+    }
+}
+
+fn encode_digit(digit: u32) -> char {
+    if digit < 26 {
+        (b'a' + digit as u8) as char
+    } else {
+        (b'0' + (digit - 26) as u8) as char
+    }
+}
+
+fn decode_digit(c: char) -> Option<u32> {
+    match c {
+        'a'..='z' => Some(c as u32 - 'a' as u32),
+        'A'..='Z' => Some(c as u32 - 'A' as u32),
+        '0'..='9' => Some(c as u32 - '0' as u32 + 26),
+        _ => None,
+    }
+}
+
+fn punycode_encode(label: &str) -> Result<String> {
+    let input = label.chars().map(|c| c as u32).collect::<Vec<_>>();
+    let mut output = label.chars().filter(char::is_ascii).collect::<String>();
+    let basic = output.len() as u32;
+    if basic > 0 {
+        output.push('-');
+    }
+    let (mut n, mut delta, mut bias, mut handled) = (INITIAL_N, 0u32, INITIAL_BIAS, basic);
+    while (handled as usize) < input.len() {
+        let m = input.iter().copied().filter(|&c| c >= n).min().unwrap();
+        delta = (m - n)
+            .checked_mul(handled + 1)
+            .and_then(|d| delta.checked_add(d))
+            .ok_or(NdefError::InvalidUri)?;
+        n = m;
+        for &c in &input {
+            if c < n {
+                delta = delta.checked_add(1).ok_or(NdefError::InvalidUri)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta += 1;
+        n += 1;
+    }
+    Ok(output)
+}
+
+fn punycode_decode(encoded: &str) -> Option<String> {
+    let (basic, digits) = match encoded.rfind('-') {
+        Some(i) => (&encoded[..i], &encoded[i + 1..]),
+        None => ("", encoded),
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut output = basic.chars().collect::<Vec<_>>();
+    let (mut n, mut i, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+    let mut digits = digits.chars();
+    while digits.as_str() != "" {
+        let old_i = i;
+        let mut w = 1u32;
+        let mut k = BASE;
+        loop {
+            let digit = decode_digit(digits.next()?)?;
+            i = i.checked_add(digit.checked_mul(w)?)?;
+            let t = threshold(k, bias);
+            if digit < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+        let points = output.len() as u32 + 1;
+        bias = adapt(i - old_i, points, old_i == 0);
+        n = n.checked_add(i / points)?;
+        i %= points;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iri_round_trip() {
+        assert_eq!("mnchen-3ya", punycode_encode("münchen").unwrap());
+        assert_eq!("münchen", punycode_decode("mnchen-3ya").unwrap());
+
+        let iri = "https://例え.テスト/パス?q=1";
+        let uri = to_uri(iri).unwrap();
+        assert_eq!(
+            "https://xn--r8jz45g.xn--zckzah/%E3%83%91%E3%82%B9?q=1",
+            uri
+        );
+        assert_eq!(iri, to_iri(&uri));
+
+        let uri = "https://user@xn--bcher-kva.example:8080/a%20b";
+        assert_eq!("https://user@bücher.example:8080/a%20b", to_iri(uri));
+        assert_eq!("mailto:j%C3%BCrgen@example.com", to_uri("mailto:jürgen@example.com").unwrap());
+        assert_eq!("https://example.com/", to_uri("https://example.com/").unwrap());
+    }
+}
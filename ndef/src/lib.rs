@@ -3,13 +3,51 @@ pub mod record;
 pub mod payload;
 pub mod message;
 pub mod tag;
+pub mod tag_io;
+pub mod apdu;
+pub mod classic;
+pub mod ntag;
 pub mod error;
+pub mod handover;
+pub mod vcard;
+pub mod calendar;
+pub mod signature;
+pub mod tnep;
+pub mod epc;
+pub mod text;
+pub mod stream;
+pub mod chip;
+pub mod audit;
+pub mod wifi;
+pub mod content;
+pub mod iri;
+#[cfg(feature = "heapless")]
+pub mod fixed;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+#[cfg(feature = "proptest")]
+pub mod testing;
+pub mod prelude;
 mod consts;
 
 
-pub use consts::*;
+pub use consts::{get_tnf_from_repr, RecordFlags, TNF};
+pub use consts::{get_uri_abbreviation, UriAbbrev, URI_ABBREVIATIONS};
+pub use consts::{
+    NONE_ABBRE, HTTP_WWW, HTTPS_WWW, HTTP, HTTPS, TEL, MAILTO, FTP_ANONYMOUS, FTP_FTP, FTPS, SFTP,
+    SMB, NFS, FTP, DAV, NEWS, TELNET, IMAP, RTSP, URN, POP, SIP, SIPS, TFTP, BTSPP, BTL2CAP,
+    BTGOEP, TCPOBEX, IRDAOBEX, FILE, URN_EPC_ID, URN_EPC_TAG, URN_EPC_PAT, URN_EPC_RAW, URN_EPC,
+    URN_NFC,
+};
+pub use consts::{RTD, RTD_PRE_DEFINED};
+pub use consts::{
+    RTD_TEXT, RTD_URI, RTD_SMART_POSTER, RTD_ACTION, RTD_SIZE, RTD_TYPE, RTD_SIGNATURE,
+    RTD_TNEP_SERVICE_PARAMETER, RTD_TNEP_SERVICE_SELECT, RTD_TNEP_STATUS, RTD_HANDOVER_REQUEST,
+    RTD_HANDOVER_SELECT, RTD_HANDOVER_CARRIER, RTD_ALTERNATIVE_CARRIER, RTD_COLLISION_RESOLUTION,
+    RTD_HANDOVER_ERROR,
+};
 
 pub type Result<T> = std::result::Result<T, error::NdefError>;
 
-pub use record::NdefRecord;
-pub use message::NdefMessage;
\ No newline at end of file
+pub use record::{NdefRecord, NdefRecordRef};
+pub use message::{DecodeOptions, NdefMessage, NdefMessageRef, TrailingBytes};
\ No newline at end of file
@@ -1,8 +1,18 @@
-use crate::{record::NdefRecord, *};
-use anyhow::{bail, Result};
-use std::io::Cursor;
+use crate::{
+    error::{DecodeError, DecodeErrorKind, EncodeError, NdefError},
+    payload::{ExternalPayload, UriPayload},
+    record::{NdefRecord, NdefRecordRef},
+    text::{LanguageTag, LocalizedTextPayload},
+    *,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+use std::ops::Index;
 
-#[derive(Default, Debug)]
+/// Messages are equal when their records are, in order; like
+/// [`NdefRecord`] equality this ignores header flags.
+#[derive(Default, Debug, PartialEq, Eq, Hash)]
 pub struct NdefMessage {
     records: Vec<NdefRecord>,
 }
@@ -15,18 +25,139 @@ impl From<NdefRecord> for NdefMessage {
     }
 }
 
-impl<T> From<T> for NdefMessage
-where
-    T: AsRef<[NdefRecord]>,
-{
-    fn from(records: T) -> Self {
+// Concrete impls rather than one over `AsRef<[NdefRecord]>`, which would
+// overlap with the `TryFrom` impls for byte buffers below.
+impl From<Vec<NdefRecord>> for NdefMessage {
+    fn from(records: Vec<NdefRecord>) -> Self {
+        Self { records }
+    }
+}
+
+impl From<&Vec<NdefRecord>> for NdefMessage {
+    fn from(records: &Vec<NdefRecord>) -> Self {
+        Self::from(records.as_slice())
+    }
+}
+
+impl From<&[NdefRecord]> for NdefMessage {
+    fn from(records: &[NdefRecord]) -> Self {
         Self {
-            records: records.as_ref().to_vec(),
+            records: records.to_vec(),
+        }
+    }
+}
+
+impl<const N: usize> From<[NdefRecord; N]> for NdefMessage {
+    fn from(records: [NdefRecord; N]) -> Self {
+        Self {
+            records: records.into(),
+        }
+    }
+}
+
+impl<const N: usize> From<&[NdefRecord; N]> for NdefMessage {
+    fn from(records: &[NdefRecord; N]) -> Self {
+        Self::from(records.as_slice())
+    }
+}
+
+impl TryFrom<&[u8]> for NdefMessage {
+    type Error = NdefError;
+
+    fn try_from(data: &[u8]) -> crate::Result<Self> {
+        Self::decode(data)
+    }
+}
+
+impl TryFrom<Vec<u8>> for NdefMessage {
+    type Error = NdefError;
+
+    fn try_from(data: Vec<u8>) -> crate::Result<Self> {
+        Self::decode(data)
+    }
+}
+
+impl FromIterator<NdefRecord> for NdefMessage {
+    fn from_iter<I: IntoIterator<Item = NdefRecord>>(records: I) -> Self {
+        Self::from_vec(records.into_iter().collect())
+    }
+}
+
+impl Extend<NdefRecord> for NdefMessage {
+    fn extend<I: IntoIterator<Item = NdefRecord>>(&mut self, records: I) {
+        self.records.extend(records);
+    }
+}
+
+impl IntoIterator for NdefMessage {
+    type Item = NdefRecord;
+    type IntoIter = std::vec::IntoIter<NdefRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a NdefMessage {
+    type Item = &'a NdefRecord;
+    type IntoIter = std::slice::Iter<'a, NdefRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.iter()
+    }
+}
+
+impl Index<usize> for NdefMessage {
+    type Output = NdefRecord;
+
+    fn index(&self, index: usize) -> &NdefRecord {
+        &self.records[index]
+    }
+}
+
+/// One line per record, prefixed with its index.
+impl fmt::Display for NdefMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, record) in self.records.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {}", index, record)?;
         }
+        Ok(())
     }
 }
 
 impl NdefMessage {
+    /// Takes ownership of `records` without copying them.
+    pub fn from_vec(records: Vec<NdefRecord>) -> Self {
+        Self { records }
+    }
+
+    pub fn builder() -> NdefMessageBuilder {
+        NdefMessageBuilder::default()
+    }
+
+    /// The standard "no content" message, a single empty record, as
+    /// written to blank a tag.
+    pub fn empty() -> Self {
+        Self::from(NdefRecord::empty())
+    }
+
+    /// Whether the message carries no content: no records, or only a
+    /// single TNF Empty record.
+    pub fn is_blank(&self) -> bool {
+        match self.records.as_slice() {
+            [] => true,
+            [record] => record.tnf() == TNF::Empty,
+            _ => false,
+        }
+    }
+
+    pub fn into_records(self) -> Vec<NdefRecord> {
+        self.records
+    }
+
     pub fn add_record(&mut self, record: NdefRecord) {
         self.records.push(record);
     }
@@ -35,45 +166,550 @@ impl NdefMessage {
         &self.records
     }
 
-    pub fn to_buffer(&self) -> Result<Vec<u8>> {
-        let mut buffer = vec![];
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Record by record [`NdefRecord::eq_semantic`] comparison, ignoring
+    /// MB/ME/SR/IL header differences.
+    pub fn eq_semantic(&self, other: &Self) -> bool {
+        self.records.len() == other.records.len()
+            && self.records.iter().zip(&other.records).all(|(a, b)| a.eq_semantic(b))
+    }
+
+    /// First record that converts to `T`, e.g.
+    /// `message.find_first::<UriPayload>()`.
+    pub fn find_first<'a, T: TryFrom<&'a NdefRecord>>(&'a self) -> Option<T> {
+        self.records.iter().find_map(|record| T::try_from(record).ok())
+    }
+
+    /// Records with the given TNF and type.
+    pub fn records_of_type<'a>(
+        &'a self,
+        tnf: TNF,
+        record_type: &'a [u8],
+    ) -> impl Iterator<Item = &'a NdefRecord> + 'a {
+        self.records
+            .iter()
+            .filter(move |record| record.tnf() == tnf && record.record_type() == record_type)
+    }
+
+    /// Payloads of the URI records that decode.
+    pub fn uris(&self) -> impl Iterator<Item = UriPayload> + '_ {
+        self.records.iter().filter_map(|record| UriPayload::try_from(record).ok())
+    }
+
+    /// Payloads of the Text records that decode.
+    pub fn texts(&self) -> impl Iterator<Item = LocalizedTextPayload> + '_ {
+        self.records
+            .iter()
+            .filter_map(|record| LocalizedTextPayload::try_from(record).ok())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&NdefRecord> {
+        self.records.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, NdefRecord> {
+        self.records.iter()
+    }
+
+    /// Removes records that are identical (same TNF, type, id and payload)
+    /// to an earlier record, keeping the first occurrence.
+    pub fn dedup(&mut self) {
+        let mut seen = HashSet::new();
+        self.records.retain(|record| seen.insert(record.clone()));
+    }
+
+    pub fn to_buffer(&self) -> crate::Result<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(self.encoded_len());
+        self.write_to(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Exact size of [`NdefMessage::to_buffer`] output, for checking tag
+    /// capacity without serializing.
+    pub fn encoded_len(&self) -> usize {
+        if self.records.is_empty() {
+            return NdefRecord::empty().encoded_len(RecordFlags::MB | RecordFlags::ME);
+        }
+        (0..self.records.len())
+            .map(|index| self.records[index].encoded_len(self.record_flag(index)))
+            .sum()
+    }
+
+    /// Encodes the message into the start of `buffer` without allocating,
+    /// returning the number of bytes written. Nothing is written if
+    /// `buffer` is too small.
+    pub fn to_buffer_into(&self, buffer: &mut [u8]) -> crate::Result<usize> {
+        let needed = self.encoded_len();
+        if buffer.len() < needed {
+            return Err(NdefError::BufferTooSmall {
+                needed,
+                available: buffer.len(),
+            });
+        }
+        self.write_to(&mut &mut buffer[..])?;
+        Ok(needed)
+    }
+
+    /// Serializes the message record by record into `writer`, without
+    /// building the whole message in memory first.
+    /// Records are validated as they are written, and TNF Unchanged
+    /// records must continue a chunk sequence started by a CF record. A
+    /// message without records is written as [`NdefMessage::empty`].
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> crate::Result<()> {
+        if self.records.is_empty() {
+            NdefRecord::empty().write_to(writer, RecordFlags::MB | RecordFlags::ME)?;
+            return Ok(());
+        }
+        let mut in_chunk = false;
         for (index, record) in self.records.iter().enumerate() {
-            let flag = if self.records.len() == 1 {
-                RecordFlags::ME | RecordFlags::MB
-            } else if index == 0 && self.records.len() > 1 {
-                RecordFlags::MB
-            } else if index == self.records.len() - 1 {
-                RecordFlags::ME
+            if (record.tnf() == TNF::Unchanged) != in_chunk {
+                return Err(if in_chunk {
+                    EncodeError::UnterminatedChunk
+                } else {
+                    EncodeError::UnexpectedChunk
+                }
+                .into());
+            }
+            record.write_to(writer, self.record_flag(index))?;
+            in_chunk = record.flags().contains(RecordFlags::CF);
+        }
+        if in_chunk {
+            return Err(EncodeError::UnterminatedChunk.into());
+        }
+        Ok(())
+    }
+
+    /// MB and ME bits of the record at `index`.
+    fn record_flag(&self, index: usize) -> RecordFlags {
+        if self.records.len() == 1 {
+            RecordFlags::ME | RecordFlags::MB
+        } else if index == 0 && self.records.len() > 1 {
+            RecordFlags::MB
+        } else if index == self.records.len() - 1 {
+            RecordFlags::ME
+        } else {
+            RecordFlags::empty()
+        }
+    }
+
+    /// Canonical form of the message: chunk sequences reassembled and every
+    /// header flag derived from the record fields, so equal content always
+    /// encodes to the same bytes whichever stack produced it.
+    pub fn canonicalize(&self) -> crate::Result<NdefMessage> {
+        let mut records: Vec<NdefRecord> = vec![];
+        let mut in_chunk = false;
+        for record in &self.records {
+            if record.tnf() == TNF::Unchanged {
+                let head = records.last_mut().filter(|_| in_chunk).ok_or(EncodeError::UnexpectedChunk)?;
+                head.append_chunk(record.clone()).map_err(|_| EncodeError::UnexpectedChunk)?;
+            } else if in_chunk {
+                return Err(EncodeError::UnterminatedChunk.into());
             } else {
-                RecordFlags::empty()
-            };
-            buffer.extend_from_slice(&record.to_buffer(flag)?);
+                records.push(record.clone());
+            }
+            in_chunk = record.flags().contains(RecordFlags::CF);
         }
-        Ok(buffer)
+        if in_chunk {
+            return Err(EncodeError::UnterminatedChunk.into());
+        }
+        Ok(Self::from_vec(records.iter().map(NdefRecord::normalized).collect()))
+    }
+
+    /// Encoding of [`NdefMessage::canonicalize`], the stable byte form to
+    /// hash when signing.
+    pub fn to_canonical_buffer(&self) -> crate::Result<Vec<u8>> {
+        self.canonicalize()?.to_buffer()
     }
 
-    pub fn decode<T: AsRef<[u8]>>(data: T) -> Result<Self> {
-        let total = data.as_ref().len() as u64;
-        let mut reader = Cursor::new(data.as_ref());
+    /// Problems strict readers may reject, such as bad flag combinations,
+    /// duplicate ids or malformed Smart Posters, without failing.
+    pub fn validate(&self) -> Vec<crate::audit::ValidationIssue> {
+        crate::audit::validate(self)
+    }
+
+    /// Annotated dump mapping each byte range of `data` to the header field,
+    /// type, id or payload it encodes. Malformed input is explained as far
+    /// as it can be parsed.
+    pub fn explain(data: &[u8]) -> crate::audit::Explanation<'_> {
+        crate::audit::explain(data)
+    }
+
+    /// Decodes a message with the default (strict flag) [`DecodeOptions`].
+    /// Chunked records (a CF-flagged record followed by TNF Unchanged
+    /// chunks) are reassembled into one record.
+    pub fn decode<T: AsRef<[u8]>>(data: T) -> crate::Result<Self> {
+        Self::decode_with(data, &DecodeOptions::default())
+    }
+
+    /// Decodes a message, applying `options` to decide which deviations
+    /// from the NDEF specification are rejected. Failures are reported as
+    /// [`NdefError::Decode`] with the byte offset and record index.
+    pub fn decode_with<T: AsRef<[u8]>>(data: T, options: &DecodeOptions) -> crate::Result<Self> {
+        let data = data.as_ref();
         let mut records = vec![];
-        loop {
-            let record = NdefRecord::decode(&mut reader)?;
-            if record.flags() & RecordFlags::MB == RecordFlags::MB && !records.is_empty() {
-                bail!("record MB flag is set , but not first record");
+        let mut chunked: Option<NdefRecord> = None;
+        let mut offset = 0;
+        for index in 0.. {
+            let start = offset;
+            let error = move |kind| DecodeError {
+                offset: start,
+                record: index,
+                kind,
+            };
+            if index == options.max_records {
+                return Err(error(DecodeErrorKind::TooManyRecords(options.max_records)).into());
+            }
+            let (record, len) = NdefRecordRef::parse_at(data, offset, index, options)?;
+            if offset + len > options.max_message_len {
+                return Err(error(DecodeErrorKind::MessageTooLarge(options.max_message_len)).into());
             }
             let flags = record.flags();
-            records.push(record);
-            if reader.position() >= total {
-                if flags & RecordFlags::ME != RecordFlags::ME {
-                    bail!("record ME flag is not set")
-                } 
+            if options.reject_reserved_tnf && record.tnf() == TNF::Reserved {
+                return Err(error(DecodeErrorKind::ReservedTnf).into());
+            }
+            if options.strict_flags && flags.contains(RecordFlags::MB) != (index == 0) {
+                return Err(error(DecodeErrorKind::MessageBegin {
+                    expected: index == 0,
+                    actual: index != 0,
+                })
+                .into());
+            }
+            let record = record.to_owned();
+            match chunked.take() {
+                Some(mut head) => {
+                    head.append_chunk(record).map_err(error)?;
+                    if head.payload().len() > options.max_payload_len {
+                        return Err(error(DecodeErrorKind::PayloadTooLarge {
+                            len: head.payload().len(),
+                            limit: options.max_payload_len,
+                        })
+                        .into());
+                    }
+                    if head.flags().contains(RecordFlags::CF) {
+                        chunked = Some(head);
+                    } else {
+                        records.push(head);
+                    }
+                }
+                None if record.tnf() == TNF::Unchanged => {
+                    return Err(error(DecodeErrorKind::InvalidChunk("chunk without an initial chunk")).into())
+                }
+                None if flags.contains(RecordFlags::CF) => {
+                    if flags.contains(RecordFlags::ME) {
+                        return Err(error(DecodeErrorKind::InvalidChunk("ME flag set on an initial chunk")).into());
+                    }
+                    chunked = Some(record);
+                }
+                None => records.push(record),
+            }
+            offset += len;
+            if flags.contains(RecordFlags::ME) {
+                if offset < data.len() && options.trailing_bytes == TrailingBytes::Reject {
+                    return Err(DecodeError {
+                        offset,
+                        record: index + 1,
+                        kind: DecodeErrorKind::TrailingBytes(data.len() - offset),
+                    }
+                    .into());
+                }
+                break;
+            }
+            if offset >= data.len() {
+                if chunked.is_some() {
+                    return Err(error(DecodeErrorKind::InvalidChunk("chunked record is not terminated")).into());
+                }
+                if options.strict_flags {
+                    return Err(error(DecodeErrorKind::MissingMessageEnd).into());
+                }
                 break;
             }
         }
         Ok(Self { records })
     }
+
+    /// Decodes as much of a damaged message as possible, returning the
+    /// intact records together with every problem found. Chunk errors skip
+    /// the offending record, while a record with a misplaced MB flag is
+    /// still intact and kept; a truncated record ends decoding since
+    /// nothing after it can be located.
+    pub fn decode_lossy<T: AsRef<[u8]>>(data: T) -> (Self, Vec<DecodeError>) {
+        let data = data.as_ref();
+        let mut records = vec![];
+        let mut errors = vec![];
+        let mut chunked: Option<NdefRecord> = None;
+        let mut offset = 0;
+        for index in 0.. {
+            let start = offset;
+            let error = move |kind| DecodeError {
+                offset: start,
+                record: index,
+                kind,
+            };
+            let (record, len) = match NdefRecordRef::parse_at(data, offset, index, &DecodeOptions::default()) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    errors.push(e);
+                    break;
+                }
+            };
+            let flags = record.flags();
+            if flags.contains(RecordFlags::MB) != (index == 0) {
+                errors.push(error(DecodeErrorKind::MessageBegin {
+                    expected: index == 0,
+                    actual: index != 0,
+                }));
+            }
+            let record = record.to_owned();
+            match chunked.take() {
+                Some(mut head) => match head.append_chunk(record) {
+                    Ok(()) if head.flags().contains(RecordFlags::CF) => chunked = Some(head),
+                    Ok(()) => records.push(head),
+                    Err(kind) => errors.push(error(kind)),
+                },
+                None if record.tnf() == TNF::Unchanged => {
+                    errors.push(error(DecodeErrorKind::InvalidChunk("chunk without an initial chunk")))
+                }
+                None if flags.contains(RecordFlags::CF | RecordFlags::ME) => {
+                    errors.push(error(DecodeErrorKind::InvalidChunk("ME flag set on an initial chunk")))
+                }
+                None if flags.contains(RecordFlags::CF) => chunked = Some(record),
+                None => records.push(record),
+            }
+            offset += len;
+            if flags.contains(RecordFlags::ME) {
+                if offset < data.len() {
+                    errors.push(DecodeError {
+                        offset,
+                        record: index + 1,
+                        kind: DecodeErrorKind::TrailingBytes(data.len() - offset),
+                    });
+                }
+                break;
+            }
+            if offset >= data.len() {
+                errors.push(error(if chunked.is_some() {
+                    DecodeErrorKind::InvalidChunk("chunked record is not terminated")
+                } else {
+                    DecodeErrorKind::MissingMessageEnd
+                }));
+                break;
+            }
+        }
+        (Self { records }, errors)
+    }
+}
+
+/// What to do with bytes following the record with the ME flag, such as
+/// the unused memory of a tag read in full.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TrailingBytes {
+    Reject,
+    Ignore,
+}
+
+/// Checks applied by [`NdefMessage::decode_with`]. Structural errors such
+/// as truncated records or broken chunk sequences are always rejected.
+///
+/// Record lengths are checked against the input before anything is copied,
+/// so a forged length field can only claim as much memory as the input
+/// holds. The limits bound that further for untrusted input; they are
+/// unlimited by default.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DecodeOptions {
+    /// Require MB on the first record only and ME on the last. When off,
+    /// a message ends at the first ME record or at the end of the data.
+    pub strict_flags: bool,
+    /// Reject records with TNF 0x07.
+    pub reject_reserved_tnf: bool,
+    pub trailing_bytes: TrailingBytes,
+    /// Largest payload of a record, after chunks are reassembled.
+    pub max_payload_len: usize,
+    /// Most records on the wire, counting each chunk.
+    pub max_records: usize,
+    /// Largest encoded message, excluding ignored trailing bytes.
+    pub max_message_len: usize,
+    /// Read 4-byte payload lengths as little-endian, as written by
+    /// releases of this crate before the byte order was fixed.
+    pub little_endian_lengths: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl DecodeOptions {
+    const DEFAULT: Self = Self {
+        strict_flags: true,
+        reject_reserved_tnf: false,
+        trailing_bytes: TrailingBytes::Reject,
+        max_payload_len: usize::MAX,
+        max_records: usize::MAX,
+        max_message_len: usize::MAX,
+        little_endian_lengths: false,
+    };
+
+    /// Every check enabled.
+    pub fn strict() -> Self {
+        Self {
+            strict_flags: true,
+            reject_reserved_tnf: true,
+            trailing_bytes: TrailingBytes::Reject,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Tolerates the deviations seen on tags in the field: missing MB or
+    /// ME flags, reserved TNFs and garbage after the message.
+    pub fn lenient() -> Self {
+        Self {
+            strict_flags: false,
+            reject_reserved_tnf: false,
+            trailing_bytes: TrailingBytes::Ignore,
+            ..Self::DEFAULT
+        }
+    }
+}
+
+
+/// Message validated in place over a byte slice. Records are parsed lazily
+/// by [`NdefMessageRef::records`] and borrow from the slice.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NdefMessageRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> NdefMessageRef<'a> {
+    /// Checks that `data` holds exactly one message: complete records, MB
+    /// on the first record only and ME on the last.
+    pub fn parse(data: &'a [u8]) -> crate::Result<Self> {
+        let mut offset = 0;
+        let mut count = 0;
+        for index in 0.. {
+            let start = offset;
+            let error = move |kind| DecodeError {
+                offset: start,
+                record: index,
+                kind,
+            };
+            let (record, len) = NdefRecordRef::parse_at(data, offset, index, &DecodeOptions::default())?;
+            let begin = record.flags().contains(RecordFlags::MB);
+            if begin != (index == 0) {
+                return Err(error(DecodeErrorKind::MessageBegin {
+                    expected: index == 0,
+                    actual: begin,
+                })
+                .into());
+            }
+            offset += len;
+            count = index + 1;
+            if record.flags().contains(RecordFlags::ME) {
+                break;
+            }
+            if offset == data.len() {
+                return Err(error(DecodeErrorKind::MissingMessageEnd).into());
+            }
+        }
+        if offset != data.len() {
+            return Err(DecodeError {
+                offset,
+                record: count,
+                kind: DecodeErrorKind::TrailingBytes(data.len() - offset),
+            }
+            .into());
+        }
+        Ok(Self { data })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Records as they appear on the wire; chunks are not reassembled.
+    pub fn records(&self) -> impl Iterator<Item = NdefRecordRef<'a>> {
+        let mut rest = self.data;
+        std::iter::from_fn(move || {
+            let (record, len) = NdefRecordRef::parse(rest).ok()?;
+            rest = &rest[len..];
+            Some(record)
+        })
+    }
+
+    /// Decodes into an owned [`NdefMessage`], reassembling chunked records.
+    pub fn to_owned(&self) -> crate::Result<NdefMessage> {
+        NdefMessage::decode(self.data)
+    }
 }
 
+/// Builds a message record by record. The typed adders do not fail on
+/// their own: the first error is kept and returned by
+/// [`build`](Self::build), so calls chain without `?`.
+#[derive(Default, Debug)]
+pub struct NdefMessageBuilder {
+    records: Vec<NdefRecord>,
+    error: Option<NdefError>,
+}
+
+impl NdefMessageBuilder {
+    pub fn record(mut self, record: NdefRecord) -> Self {
+        self.records.push(record);
+        self
+    }
+
+    /// Appends every record of `records`, moving them into the message.
+    pub fn records<I: IntoIterator<Item = NdefRecord>>(mut self, records: I) -> Self {
+        self.records.extend(records);
+        self
+    }
+
+    /// Appends a URI record, abbreviated with the longest matching prefix.
+    pub fn uri(self, uri: &str) -> Self {
+        let record = NdefRecord::builder().well_known(&UriPayload::from_string(uri)).build();
+        self.try_record(record)
+    }
+
+    /// Appends a UTF-8 Text record in `language`, e.g. `en` or `de-CH`.
+    pub fn text(self, language: &str, text: &str) -> Self {
+        let record = LanguageTag::new(language)
+            .and_then(|language| NdefRecord::builder().well_known(&LocalizedTextPayload::new(language, text)).build());
+        self.try_record(record)
+    }
+
+    /// Appends an Android Application Record, which makes Android open or
+    /// install `package` when the tag is read.
+    pub fn aar(self, package: &str) -> Self {
+        let record = ExternalPayload::new("android.com", "pkg", package.as_bytes().to_vec())
+            .and_then(|payload| NdefRecord::builder().external(&payload).build());
+        self.try_record(record)
+    }
+
+    /// Fails with the error of the first adder that failed.
+    pub fn build(self) -> crate::Result<NdefMessage> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(NdefMessage::from_vec(self.records)),
+        }
+    }
+
+    /// Appends `record`, or keeps its error if it is the first one.
+    fn try_record(mut self, record: crate::Result<NdefRecord>) -> Self {
+        match record {
+            Ok(record) => self.records.push(record),
+            Err(error) => {
+                self.error.get_or_insert(error);
+            }
+        }
+        self
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -82,6 +718,8 @@ mod tests {
     use crate::record::NdefRecord;
     use crate::payload::*;
     use crate::*;
+    use crate::audit::Field;
+    use crate::error::{DecodeError, DecodeErrorKind, EncodeError, NdefError};
 
     
     #[test]
@@ -108,7 +746,7 @@ mod tests {
 
         assert_eq!(2, message.records().len());
 
-        let record = message.records().get(0).unwrap();
+        let record = message.records().first().unwrap();
         assert_eq!(TNF::WellKnown, record.tnf());
         assert_eq!(RTD_URI.as_bytes(), record.record_type());
         let payload = UriPayload::try_from(record).unwrap();
@@ -141,7 +779,7 @@ mod tests {
 
         let message = NdefMessage::decode(hex::decode(expect).unwrap()).unwrap();
         assert_eq!(1, message.records().len());
-        let record = message.records().get(0).unwrap();
+        let record = message.records().first().unwrap();
         assert_eq!(TNF::WellKnown , record.tnf());
         assert_eq!(RTD_URI.as_bytes(), record.record_type());
         let payload = UriPayload::try_from(record).unwrap();
@@ -153,13 +791,442 @@ mod tests {
     #[test]
     fn test_not_sr() {
         let record = NdefRecord::builder()
-            .tnf(TNF::External)
+            .tnf(TNF::WellKnown)
             .payload(&SmartPosterPayload::from_static(&[0xabu8; 300]))
             .build()
             .unwrap();
         let message = NdefMessage::from(record);
         let buffer = message.to_buffer().unwrap();
-        let expect = "c4022c0100005370abababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababab";
+        let expect = "c1020000012c5370abababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababababab";
         assert_eq!(expect, hex::encode(buffer));
     }
+
+    #[test]
+    fn test_dedup() {
+        let uri = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        let text = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&TextPayload::from_static("hello"))
+            .build()
+            .unwrap();
+
+        let mut message = NdefMessage::from(&[uri.clone(), text.clone(), uri.clone()]);
+        message.dedup();
+        assert_eq!(2, message.records().len());
+        assert_eq!(&uri, &message.records()[0]);
+        assert_eq!(&text, &message.records()[1]);
+
+        let decoded = NdefMessage::decode(message.to_buffer().unwrap()).unwrap();
+        assert_ne!(decoded.records()[0].flags(), uri.flags());
+        assert_eq!(decoded.records()[0], uri);
+    }
+
+    #[test]
+    fn test_builder() {
+        let uri = |value: &'static str| {
+            NdefRecord::builder()
+                .tnf(TNF::WellKnown)
+                .payload(&UriPayload::from_static(value))
+                .build()
+                .unwrap()
+        };
+        let message = NdefMessage::builder()
+            .record(uri("https://a.example"))
+            .records(vec![uri("https://b.example"), uri("https://c.example")])
+            .build()
+            .unwrap();
+        assert_eq!(3, message.records().len());
+        let records = message.into_records();
+        let message = NdefMessage::from_vec(records.clone());
+        assert_eq!(records, message.records());
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        // MIME "a/b" split into "He", "ll", "o", with an ID on the initial chunk.
+        let data = hex::decode("ba030201612f626948653600026c6c5600016f").unwrap();
+        let message = NdefMessage::decode(&data).unwrap();
+        assert_eq!(1, message.records().len());
+        let record = &message.records()[0];
+        assert_eq!(TNF::MimeMedia, record.tnf());
+        assert_eq!(b"a/b", record.record_type());
+        assert_eq!(Some(&b"i"[..]), record.id());
+        assert_eq!(b"Hello", record.payload());
+        assert!(!record.flags().contains(RecordFlags::CF));
+        assert!(record.flags().contains(RecordFlags::ME | RecordFlags::SR));
+
+        // Terminating chunk missing.
+        assert!(NdefMessage::decode(&data[..15]).is_err());
+        // Chunk without an initial chunk.
+        assert!(NdefMessage::decode(hex::decode("d600016f").unwrap()).is_err());
+        // Middle chunk carrying a type.
+        assert!(NdefMessage::decode(hex::decode("b2030161 2f6248 56010178 6f".replace(' ', "")).unwrap()).is_err());
+        // ME on the initial chunk.
+        assert!(NdefMessage::decode(hex::decode("f2030161 2f6248".replace(' ', "")).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let message = NdefMessage::from(&[
+            NdefRecord::builder()
+                .tnf(TNF::WellKnown)
+                .payload(&UriPayload::from_static("https://example.com"))
+                .build()
+                .unwrap(),
+            NdefRecord::builder().tnf(TNF::Unknown).unknown(vec![0u8; 300]).build().unwrap(),
+        ]);
+        let mut output = std::io::BufWriter::new(vec![]);
+        message.write_to(&mut output).unwrap();
+        assert_eq!(message.to_buffer().unwrap(), output.into_inner().unwrap());
+
+        let mut full = [0u8; 8];
+        assert!(message.write_to(&mut &mut full[..]).is_err());
+    }
+
+    #[test]
+    fn test_message_ref() {
+        let message = NdefMessage::from(&[
+            NdefRecord::builder()
+                .tnf(TNF::WellKnown)
+                .payload(&UriPayload::from_static("https://example.com"))
+                .build()
+                .unwrap(),
+            NdefRecord::builder().tnf(TNF::Unknown).unknown(vec![1, 2, 3]).build().unwrap(),
+        ]);
+        let data = message.to_buffer().unwrap();
+        let parsed = NdefMessageRef::parse(&data).unwrap();
+        let records: Vec<_> = parsed.records().collect();
+        assert_eq!(2, records.len());
+        assert_eq!(&[1, 2, 3], records[1].payload());
+        assert_eq!(message.records(), parsed.to_owned().unwrap().records());
+
+        assert!(NdefMessageRef::parse(&data[..data.len() - 1]).is_err());
+        // Trailing bytes are reported at the index the next record would have.
+        let trailing = [&data[..], &[0xd0, 0, 0]].concat();
+        for result in [NdefMessageRef::parse(&trailing).map(|_| ()), NdefMessage::decode(&trailing).map(|_| ())] {
+            match result {
+                Err(NdefError::Decode(error)) => assert_eq!((data.len(), 2), (error.offset, error.record)),
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(2, NdefMessage::decode_lossy(&trailing).1[0].record);
+        let first = records[1].header().encoded_len() + 3;
+        assert!(NdefMessageRef::parse(&data[data.len() - first..]).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let message = NdefMessage::decode(hex::decode("91010455036162635101015402").unwrap()).unwrap();
+        assert_eq!(
+            "0: WellKnown type=\"U\" flags=MB|SR payload[4]: 03 61 62 63 |.abc|\n1: WellKnown type=\"T\" flags=ME|SR payload[1]: 02 |.|",
+            message.to_string()
+        );
+    }
+
+    #[test]
+    fn test_collection() {
+        let record = |byte: u8| NdefRecord::builder().unknown(vec![byte]).build().unwrap();
+        let mut message: NdefMessage = (0..3).map(record).collect();
+        message.extend([record(3)]);
+        assert_eq!(4, message.len());
+        assert!(!message.is_empty());
+        assert_eq!(&[2], message[2].payload());
+        assert_eq!(None, message.get(4));
+        assert_eq!(4, (&message).into_iter().count());
+        let payloads: Vec<u8> = message.into_iter().map(|r| r.payload()[0]).collect();
+        assert_eq!(vec![0, 1, 2, 3], payloads);
+        assert!(NdefMessage::default().is_empty());
+    }
+
+    #[test]
+    fn test_decode_options() {
+        // Short Unknown records: no MB, reserved TNF, ME followed by garbage.
+        let data = hex::decode("150001aa57000000ffff").unwrap();
+        assert!(NdefMessage::decode(&data).is_err());
+        assert!(NdefMessage::decode_with(&data, &DecodeOptions::strict()).is_err());
+        let message = NdefMessage::decode_with(&data, &DecodeOptions::lenient()).unwrap();
+        assert_eq!(2, message.len());
+        assert_eq!(TNF::Reserved, message[1].tnf());
+
+        let reserved = hex::decode("d70000").unwrap();
+        assert!(NdefMessage::decode(&reserved).is_ok());
+        assert!(NdefMessage::decode_with(&reserved, &DecodeOptions::strict()).is_err());
+
+        let options = DecodeOptions {
+            trailing_bytes: TrailingBytes::Ignore,
+            ..DecodeOptions::default()
+        };
+        let padded = hex::decode("d50001aa000000").unwrap();
+        assert!(NdefMessage::decode(&padded).is_err());
+        assert_eq!(1, NdefMessage::decode_with(&padded, &options).unwrap().len());
+
+        // Without strict flags a message may end without ME.
+        let open = hex::decode("950001aa").unwrap();
+        assert!(NdefMessage::decode(&open).is_err());
+        assert_eq!(1, NdefMessage::decode_with(&open, &DecodeOptions::lenient()).unwrap().len());
+    }
+
+    #[test]
+    fn test_decode_error_location() {
+        // Second record claims 4 payload bytes but only 2 follow.
+        let data = hex::decode("910001aa510004bbcc").unwrap();
+        match NdefMessage::decode(&data) {
+            Err(NdefError::Decode(DecodeError {
+                offset: 7,
+                record: 1,
+                kind: DecodeErrorKind::Truncated { field: Field::Payload, expected: 4, actual: 2 },
+            })) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let data = hex::decode("910001aad10001bb").unwrap();
+        let error = NdefMessage::decode(&data).unwrap_err();
+        assert_eq!("Decode error: MB flag expected false, found true at byte 4 (record 1)", error.to_string());
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        // Long record header claiming a 0xffffffff byte payload.
+        let forged = hex::decode("c100ffffffffaa").unwrap();
+        let options = DecodeOptions {
+            max_payload_len: 1024,
+            ..DecodeOptions::default()
+        };
+        let error = NdefMessage::decode_with(&forged, &options).unwrap_err();
+        assert!(matches!(
+            error,
+            NdefError::Decode(DecodeError {
+                kind: DecodeErrorKind::PayloadTooLarge { len: 0xffffffff, limit: 1024 },
+                ..
+            })
+        ));
+
+        let data = hex::decode("910001aa110001bb510001cc").unwrap();
+        assert_eq!(3, NdefMessage::decode_with(&data, &options).unwrap().len());
+        let options = DecodeOptions {
+            max_records: 2,
+            ..DecodeOptions::default()
+        };
+        assert!(NdefMessage::decode_with(&data, &options).is_err());
+        let options = DecodeOptions {
+            max_message_len: 11,
+            ..DecodeOptions::default()
+        };
+        assert!(NdefMessage::decode_with(&data, &options).is_err());
+
+        // The limit applies to the reassembled payload of a chunked record.
+        let chunked = hex::decode("b50002aabb360002ccdd560002eeff").unwrap();
+        let options = DecodeOptions {
+            max_payload_len: 4,
+            ..DecodeOptions::default()
+        };
+        assert!(NdefMessage::decode_with(&chunked, &options).is_err());
+        assert_eq!(1, NdefMessage::decode(&chunked).unwrap().len());
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        let message = NdefMessage::from(&[
+            NdefRecord::builder().tnf(TNF::WellKnown).payload(&UriPayload::from_static("https://example.com")).build().unwrap(),
+            NdefRecord::builder().tnf(TNF::Unknown).id(b"id".to_vec()).unknown(vec![0; 300]).build().unwrap(),
+        ]);
+        assert_eq!(message.to_buffer().unwrap().len(), message.encoded_len());
+        for record in message.records() {
+            assert_eq!(record.to_buffer(RecordFlags::MB).unwrap().len(), record.encoded_len(RecordFlags::MB));
+        }
+    }
+
+    #[test]
+    fn test_typed_builder() {
+        let message = NdefMessage::builder()
+            .uri("https://example.com")
+            .text("en", "hi")
+            .aar("com.example")
+            .build()
+            .unwrap();
+        assert_eq!(
+            "9101 0c55 0465 7861 6d70 6c65 2e63 6f6d 1101 0554 0265 6e68 6954 0f0b 616e 6472 6f69 642e 636f 6d3a 706b 6763 6f6d 2e65 7861 6d70 6c65"
+                .replace(' ', ""),
+            hex::encode(message.to_buffer().unwrap())
+        );
+        // The first failing adder fails the whole chain.
+        let failed = NdefMessage::builder()
+            .uri("https://example.com")
+            .text("not a tag", "hi")
+            .aar(&"x".repeat(300))
+            .build();
+        assert!(matches!(failed, Err(NdefError::InvalidLanguage)));
+    }
+
+    #[test]
+    fn test_message_eq_hash() {
+        let message = NdefMessage::builder().uri("https://example.com").text("en", "hi").build().unwrap();
+        let decoded = NdefMessage::decode(message.to_buffer().unwrap()).unwrap();
+        assert_eq!(message, decoded);
+
+        let mut seen = std::collections::HashMap::new();
+        seen.insert(message, "first");
+        assert_eq!(Some(&"first"), seen.get(&decoded));
+        let reversed: NdefMessage = decoded.into_records().into_iter().rev().collect();
+        assert!(!seen.contains_key(&reversed));
+    }
+
+    #[test]
+    fn test_eq_semantic() {
+        // IL set with a zero-length id.
+        let decoded = NdefMessage::decode(hex::decode("dd000100aa").unwrap()).unwrap();
+        let built = NdefMessage::from(NdefRecord::builder().tnf(TNF::Unknown).unknown(vec![0xaa]).build().unwrap());
+        assert_ne!(built, decoded);
+        assert!(built.eq_semantic(&decoded));
+        let other = NdefMessage::from(NdefRecord::builder().tnf(TNF::Unknown).unknown(vec![0xbb]).build().unwrap());
+        assert!(!built.eq_semantic(&other));
+    }
+
+    #[test]
+    fn test_search_helpers() {
+        let message = NdefMessage::builder()
+            .aar("com.example")
+            .text("en", "hi")
+            .uri("https://example.com")
+            .uri("tel:123")
+            .build()
+            .unwrap();
+        let uri = message.find_first::<UriPayload>().unwrap();
+        assert_eq!("https://example.com", uri.full_uri());
+        assert_eq!(2, message.records_of_type(TNF::WellKnown, b"U").count());
+        assert_eq!(1, message.records_of_type(TNF::External, b"android.com:pkg").count());
+        assert_eq!(vec!["https://example.com", "tel:123"], message.uris().map(|u| u.full_uri()).collect::<Vec<_>>());
+        let texts = message.texts().collect::<Vec<_>>();
+        assert_eq!(("en", "hi"), (texts[0].language().as_str(), texts[0].text()));
+        assert!(message.find_first::<GeoUriPayload>().is_none());
+    }
+
+    #[test]
+    fn test_decode_lossy() {
+        // Two intact records, then one cut short by an interrupted write.
+        let data = hex::decode("950001aa150001bb550004cc").unwrap();
+        let (message, errors) = NdefMessage::decode_lossy(&data);
+        assert_eq!(vec![&[0xaa][..], &[0xbb][..]], message.iter().map(|r| r.payload()).collect::<Vec<_>>());
+        assert_eq!(1, errors.len());
+        assert_eq!((11, 2), (errors[0].offset, errors[0].record));
+
+        let (message, errors) = NdefMessage::decode_lossy(hex::decode("d50001aa").unwrap());
+        assert_eq!(1, message.len());
+        assert!(errors.is_empty());
+
+        // No MB flag on the first record, then a chunk with no initial chunk.
+        let (message, errors) = NdefMessage::decode_lossy(hex::decode("150001aa560001bb").unwrap());
+        assert_eq!(vec![&[0xaa][..]], message.iter().map(|r| r.payload()).collect::<Vec<_>>());
+        assert!(matches!(errors[0].kind, DecodeErrorKind::MessageBegin { expected: true, .. }));
+        assert!(matches!(errors[1].kind, DecodeErrorKind::InvalidChunk(_)));
+        assert_eq!((2, 1), (errors.len(), errors[1].record));
+    }
+
+    #[test]
+    fn test_to_buffer_into() {
+        let message = NdefMessage::builder().uri("https://example.com").text("en", "hi").build().unwrap();
+        let expected = message.to_buffer().unwrap();
+        let mut buffer = [0u8; 64];
+        let len = message.to_buffer_into(&mut buffer).unwrap();
+        assert_eq!(expected, &buffer[..len]);
+        assert!(matches!(
+            message.to_buffer_into(&mut buffer[..len - 1]),
+            Err(NdefError::BufferTooSmall { needed, available }) if needed == len && available == len - 1
+        ));
+
+        let len = message[0].to_buffer_into(&mut buffer, RecordFlags::MB).unwrap();
+        assert_eq!(message[0].to_buffer(RecordFlags::MB).unwrap(), &buffer[..len]);
+    }
+
+    #[test]
+    fn test_long_record_interop() {
+        // 300 byte URI record as written by Android: big-endian length.
+        let mut dump = hex::decode("c1010000012c5500").unwrap();
+        dump.extend(std::iter::repeat_n(b'a', 299));
+        let message = NdefMessage::decode(&dump).unwrap();
+        assert_eq!(300, message[0].payload().len());
+        assert_eq!(dump, message.to_buffer().unwrap());
+
+        // Output of earlier releases, with the length little-endian.
+        let mut legacy = hex::decode("c1012c0100005500").unwrap();
+        legacy.extend(std::iter::repeat_n(b'a', 299));
+        assert!(NdefMessage::decode(&legacy).is_err());
+        let options = DecodeOptions {
+            little_endian_lengths: true,
+            ..DecodeOptions::default()
+        };
+        assert_eq!(message, NdefMessage::decode_with(&legacy, &options).unwrap());
+    }
+
+    #[test]
+    fn test_encode_validation() {
+        let reserved = NdefRecord::builder().tnf(TNF::Reserved).build().unwrap();
+        assert!(NdefMessage::from(reserved).to_buffer().is_err());
+
+        // A chunk sequence survives a decode and encode round trip only
+        // when its records stay in order.
+        let data = hex::decode("b50002aabb360002ccdd560002eeff").unwrap();
+        let mut chunks = vec![];
+        let mut offset = 0;
+        while offset < data.len() {
+            let (record, len) = NdefRecord::decode(&data[offset..]).unwrap();
+            chunks.push(record);
+            offset += len;
+        }
+        assert_eq!(data, NdefMessage::from(&chunks).to_buffer().unwrap());
+        let unterminated = NdefMessage::from(&chunks[..2]);
+        assert!(unterminated.to_buffer().is_err());
+        let orphan = NdefMessage::from(&chunks[1..]);
+        assert!(orphan.to_buffer().is_err());
+        assert!(matches!(
+            orphan.to_canonical_buffer(),
+            Err(NdefError::Encode(EncodeError::UnexpectedChunk))
+        ));
+
+        let mut typed = NdefRecord::builder().unknown(vec![1]).build().unwrap();
+        typed.set_type("x");
+        let message = NdefMessage::from(typed);
+        assert!(matches!(
+            message.to_buffer(),
+            Err(NdefError::Encode(EncodeError::UnexpectedType(TNF::Unknown)))
+        ));
+        assert!(matches!(
+            message.to_buffer_into(&mut [0; 16]),
+            Err(NdefError::Encode(EncodeError::UnexpectedType(TNF::Unknown)))
+        ));
+    }
+
+    #[test]
+    fn test_empty_message() {
+        assert_eq!("d00000", hex::encode(NdefMessage::empty().to_buffer().unwrap()));
+        assert_eq!("d00000", hex::encode(NdefMessage::default().to_buffer().unwrap()));
+        assert_eq!(3, NdefMessage::default().encoded_len());
+
+        let decoded = NdefMessage::decode(hex::decode("d00000").unwrap()).unwrap();
+        assert!(decoded.is_blank());
+        assert_eq!(NdefMessage::empty(), decoded);
+        assert!(!NdefMessage::builder().uri("tel:1").build().unwrap().is_blank());
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        // Chunked, with a zero-length id and a long length field for a
+        // short payload.
+        let mut records = vec![];
+        let data = hex::decode("ad000000000200aabb360001cc560001dd").unwrap();
+        let mut offset = 0;
+        while offset < data.len() {
+            let (record, len) = NdefRecord::decode(&data[offset..]).unwrap();
+            records.push(record);
+            offset += len;
+        }
+        let message = NdefMessage::from_vec(records);
+        let canonical = message.canonicalize().unwrap();
+        assert_eq!(1, canonical.len());
+        assert_eq!("d50004aabbccdd", hex::encode(message.to_canonical_buffer().unwrap()));
+    }
 }
@@ -0,0 +1,277 @@
+//! NTAG21x configuration pages: the ASCII mirror that patches the UID or
+//! the NFC counter into a URI as the tag is read, and password protection.
+//! NTAG210/212 mirror the UID only, and MIFARE Ultralight EV1 shares the
+//! password pages but has neither mirror nor NFC counter.
+
+use crate::{chip::ChipModel, error::NdefError, payload::UriPayload, tag::NFT2Tag};
+use bitflags::bitflags;
+
+/// Byte address of page 3, where [`NFT2Tag::to_bytes`] images start.
+const IMAGE_OFFSET: usize = 12;
+/// STRG_MOD_EN, set by default: keeps strong load modulation enabled.
+const STRG_MOD_EN: u8 = 0b0000_0100;
+
+/// What the tag mirrors into its memory as ASCII hex.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mirror {
+    /// The 7-byte UID, 14 characters.
+    Uid,
+    /// The 24-bit NFC counter, 6 characters.
+    Counter,
+    /// UID, an `x` separator, then the counter: 21 characters.
+    UidAndCounter,
+}
+
+impl Mirror {
+    /// Placeholder to put where the mirror goes; the tag overwrites it
+    /// when read, so its content only matters for locating it.
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            Mirror::Uid => "00000000000000",
+            Mirror::Counter => "000000",
+            Mirror::UidAndCounter => "00000000000000x000000",
+        }
+    }
+
+    /// MIRROR_CONF bits of the MIRROR byte.
+    fn conf(&self) -> u8 {
+        match self {
+            Mirror::Uid => 0b01,
+            Mirror::Counter => 0b10,
+            Mirror::UidAndCounter => 0b11,
+        }
+    }
+
+    /// URI payload with the placeholder between `prefix` and `suffix`, e.g.
+    /// `https://example.com/?id=` and an empty suffix.
+    pub fn uri(&self, prefix: &str, suffix: &str) -> UriPayload {
+        UriPayload::from_string(format!("{prefix}{}{suffix}", self.placeholder()))
+    }
+}
+
+/// Mirror position as the MIRROR and MIRROR_PAGE bytes of CFG0 expect it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MirrorConfig {
+    pub mirror: Mirror,
+    /// Byte address of the placeholder in tag memory.
+    pub address: usize,
+}
+
+impl MirrorConfig {
+    /// Finds the first occurrence of the `mirror` placeholder in the image
+    /// of `tag`.
+    pub fn locate(tag: &NFT2Tag, mirror: Mirror) -> crate::Result<Self> {
+        let image = tag.to_bytes()?;
+        let placeholder = mirror.placeholder().as_bytes();
+        let index = image
+            .windows(placeholder.len())
+            .position(|window| window == placeholder)
+            .ok_or(NdefError::InvalidTagData)?;
+        let address = IMAGE_OFFSET + index;
+        if address / 4 > 0xff {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        Ok(Self { mirror, address })
+    }
+
+    /// MIRROR byte of CFG0: MIRROR_CONF, MIRROR_BYTE and STRG_MOD_EN.
+    pub fn mirror_byte(&self) -> u8 {
+        (self.mirror.conf() << 6) | (((self.address % 4) as u8) << 4) | STRG_MOD_EN
+    }
+
+    /// MIRROR_PAGE byte of CFG0: the page holding the first placeholder
+    /// character.
+    pub fn mirror_page(&self) -> u8 {
+        (self.address / 4) as u8
+    }
+}
+
+bitflags! {
+    /// ACCESS byte of CFG1, without the AUTHLIM bits.
+    #[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct NtagAccess: u8 {
+        /// Password protects reads as well as writes from AUTH0 on.
+        const PROT = 0b1000_0000;
+        /// Locks the configuration pages permanently.
+        const CFGLCK = 0b0100_0000;
+        const NFC_CNT_EN = 0b0001_0000;
+        /// READ_CNT needs the password.
+        const NFC_CNT_PWD_PROT = 0b0000_1000;
+    }
+}
+
+/// AUTH0 value that disables password protection.
+const AUTH0_DISABLED: u8 = 0xff;
+
+/// Values for the CFG0, CFG1, PWD and PACK pages of an NTAG21x, written
+/// after the tag image.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NtagConfig {
+    pub mirror: Option<MirrorConfig>,
+    /// First page needing the password; 0xFF disables protection.
+    pub auth0: u8,
+    pub access: NtagAccess,
+    /// Failed password attempts allowed, `2^auth_limit`; 0 for unlimited.
+    pub auth_limit: u8,
+    pub password: [u8; 4],
+    /// Password acknowledge returned on successful authentication.
+    pub pack: [u8; 2],
+}
+
+impl Default for NtagConfig {
+    /// The factory configuration: no mirror, no password protection.
+    fn default() -> Self {
+        Self {
+            mirror: None,
+            auth0: AUTH0_DISABLED,
+            access: NtagAccess::empty(),
+            auth_limit: 0,
+            password: [0xff; 4],
+            pack: [0x00; 2],
+        }
+    }
+}
+
+impl NtagConfig {
+    /// Protects pages from `auth0` on with `password`: writes only, or reads
+    /// too when `protect_reads` is set.
+    pub fn password(mut self, password: [u8; 4], pack: [u8; 2], auth0: u8, protect_reads: bool) -> Self {
+        self.password = password;
+        self.pack = pack;
+        self.auth0 = auth0;
+        self.access.set(NtagAccess::PROT, protect_reads);
+        self
+    }
+
+    pub fn mirror(mut self, mirror: MirrorConfig) -> Self {
+        self.mirror = Some(mirror);
+        self
+    }
+
+    pub fn access(mut self, access: NtagAccess) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Failed attempts before the password locks, as the exponent (0 to 7).
+    pub fn auth_limit(mut self, exp: u8) -> Self {
+        self.auth_limit = exp;
+        self
+    }
+
+    /// The four configuration pages of `chip`, numbered from CFG0. Fails
+    /// with [`NdefError::InvalidTagType`] for a mirror or counter setting
+    /// the chip lacks.
+    pub fn pages(&self, chip: ChipModel) -> crate::Result<Vec<(u16, [u8; 4])>> {
+        let cfg0 = chip.config_page().ok_or(NdefError::InvalidTagType)?;
+        if self.auth_limit > 0b111 {
+            return Err(NdefError::InvalidTagData);
+        }
+        let has_counter = matches!(chip, ChipModel::Ntag213 | ChipModel::Ntag215 | ChipModel::Ntag216);
+        let has_uid_mirror = has_counter || matches!(chip, ChipModel::Ntag210 | ChipModel::Ntag212);
+        let mirror_supported = match self.mirror.map(|mirror| mirror.mirror) {
+            None => true,
+            Some(Mirror::Uid) => has_uid_mirror,
+            Some(Mirror::Counter | Mirror::UidAndCounter) => has_counter,
+        };
+        let counter_bits = NtagAccess::NFC_CNT_EN | NtagAccess::NFC_CNT_PWD_PROT;
+        if !mirror_supported || (self.access.intersects(counter_bits) && !has_counter) {
+            return Err(NdefError::InvalidTagType);
+        }
+        let (mirror, mirror_page) = match &self.mirror {
+            Some(mirror) => (mirror.mirror_byte(), mirror.mirror_page()),
+            None => (STRG_MOD_EN, 0x00),
+        };
+        let access = self.access.bits() | self.auth_limit;
+        Ok(vec![
+            (cfg0, [mirror, 0x00, mirror_page, self.auth0]),
+            (cfg0 + 1, [access, 0x00, 0x00, 0x00]),
+            (cfg0 + 2, self.password),
+            (cfg0 + 3, [self.pack[0], self.pack[1], 0x00, 0x00]),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tag::TlvValue, *};
+
+    #[test]
+    fn test_mirror() {
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&Mirror::UidAndCounter.uri("https://example.com/?m=", ""))
+            .build()
+            .unwrap();
+        let message = NdefMessage::from(record);
+        let tag = NFT2Tag::ntag213()
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let config = MirrorConfig::locate(&tag, Mirror::UidAndCounter).unwrap();
+        // Page 4 holds the Lock Control TLV; the URI record starts at 23.
+        assert_eq!(23 + 4 + 1 + 15, config.address);
+        assert_eq!(0x0a, config.mirror_page());
+        assert_eq!(0b1111_0100, config.mirror_byte());
+        assert!(MirrorConfig::locate(&tag, Mirror::Uid).is_ok());
+
+        let empty = NFT2Tag::ntag213().build().unwrap();
+        assert!(MirrorConfig::locate(&empty, Mirror::Counter).is_err());
+
+        let pages = NtagConfig::default().mirror(config).pages(ChipModel::Ntag213).unwrap();
+        assert_eq!((0x29, [0xf4, 0x00, 0x0a, 0xff]), pages[0]);
+    }
+
+    #[test]
+    fn test_password() {
+        let config = NtagConfig::default()
+            .password([0x12, 0x34, 0x56, 0x78], [0xab, 0xcd], 0x04, false)
+            .auth_limit(3);
+        let pages = config.pages(ChipModel::Ntag215).unwrap();
+        assert_eq!(
+            vec![
+                (0x83, [0x04, 0x00, 0x00, 0x04]),
+                (0x84, [0x03, 0x00, 0x00, 0x00]),
+                (0x85, [0x12, 0x34, 0x56, 0x78]),
+                (0x86, [0xab, 0xcd, 0x00, 0x00]),
+            ],
+            pages
+        );
+        let config = config.password([0; 4], [0; 2], 0x10, true);
+        assert_eq!(0x83, config.pages(ChipModel::Ntag213).unwrap()[1].1[0]);
+        assert!(config.pages(ChipModel::St25tv02k).is_err());
+        assert!(NtagConfig::default().auth_limit(8).pages(ChipModel::Ntag213).is_err());
+    }
+
+    #[test]
+    fn test_chip_features() {
+        let uid = MirrorConfig {
+            mirror: Mirror::Uid,
+            address: 0x20,
+        };
+        let counter = MirrorConfig {
+            mirror: Mirror::Counter,
+            address: 0x20,
+        };
+        let password = NtagConfig::default().password([0x12, 0x34, 0x56, 0x78], [0xab, 0xcd], 0x04, true);
+        let pages = password.pages(ChipModel::UltralightEv1_48).unwrap();
+        assert_eq!((0x10, [STRG_MOD_EN, 0x00, 0x00, 0x04]), pages[0]);
+        assert_eq!((0x11, [0x80, 0x00, 0x00, 0x00]), pages[1]);
+
+        let unsupported = [
+            NtagConfig::default().mirror(uid),
+            NtagConfig::default().mirror(counter),
+            NtagConfig::default().access(NtagAccess::NFC_CNT_EN),
+            password.clone().access(NtagAccess::NFC_CNT_PWD_PROT),
+        ];
+        for config in &unsupported {
+            assert!(matches!(config.pages(ChipModel::UltralightEv1_128), Err(NdefError::InvalidTagType)));
+        }
+        assert!(NtagConfig::default().mirror(uid).pages(ChipModel::Ntag212).is_ok());
+        assert!(NtagConfig::default().mirror(counter).pages(ChipModel::Ntag212).is_err());
+        assert!(NtagConfig::default().access(NtagAccess::NFC_CNT_EN).pages(ChipModel::Ntag210).is_err());
+        assert!(NtagConfig::default().mirror(counter).pages(ChipModel::Ntag216).is_ok());
+    }
+}
@@ -0,0 +1,14 @@
+//! Commonly used types in one import: `use ndef_rs::prelude::*;`.
+
+pub use crate::error::NdefError;
+pub use crate::message::{NdefMessage, NdefMessageBuilder};
+#[cfg(feature = "mime")]
+pub use crate::payload::MimePayload;
+pub use crate::payload::{
+    AbsoluteUriPayload, ExternalPayload, GeoUriPayload, RecordPayload, SmartPosterPayload, TextPayload,
+    TypedPayload, UriPayload,
+};
+pub use crate::record::{NdefRecord, NdefRecordBuilder};
+pub use crate::text::{LanguageTag, LocalizedTextPayload};
+pub use crate::{RecordFlags, Result, RTD, TNF};
+pub use crate::{RTD_ACTION, RTD_SMART_POSTER, RTD_TEXT, RTD_URI};
@@ -1,7 +1,9 @@
-use crate::{payload::*, error::NdefError};
+use crate::{payload::*, audit::Field, error::{DecodeError, DecodeErrorKind, EncodeError, NdefError}};
 use crate::*;
 use anyhow::anyhow;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io::{prelude::*, Cursor};
 
 #[derive(Debug, Clone)]
@@ -17,11 +19,84 @@ pub struct NdefRecord {
     payload: Vec<u8>,
 }
 
+/// Records are compared by TNF, type, id and payload only. Header flags
+/// depend on the record's position in a message and are ignored.
+impl PartialEq for NdefRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.tnf == other.tnf
+            && self.record_type == other.record_type
+            && self.id == other.id
+            && self.payload == other.payload
+    }
+}
+
+impl Eq for NdefRecord {}
+
+impl Hash for NdefRecord {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tnf.hash(state);
+        self.record_type.hash(state);
+        self.id.hash(state);
+        self.payload.hash(state);
+    }
+}
+
+/// Bytes of payload shown by the `Display` implementation.
+const PAYLOAD_PREVIEW_LEN: usize = 16;
+
+/// One line summary: TNF, type, id, flags and a hex/ASCII preview of the
+/// start of the payload, e.g.
+/// `WellKnown type="U" flags=SR payload[12]: 04 65 78 61 ... |.example.com|`.
+impl fmt::Display for NdefRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.tnf)?;
+        if !self.record_type.is_empty() {
+            write!(f, " type={:?}", String::from_utf8_lossy(&self.record_type))?;
+        }
+        if let Some(id) = self.id.as_ref() {
+            write!(f, " id={:?}", String::from_utf8_lossy(id))?;
+        }
+        let flags: Vec<_> = (self.flags & !RecordFlags::TNF).iter_names().map(|(name, _)| name).collect();
+        if !flags.is_empty() {
+            write!(f, " flags={}", flags.join("|"))?;
+        }
+        write!(f, " payload[{}]", self.payload.len())?;
+        if self.payload.is_empty() {
+            return Ok(());
+        }
+        let preview = &self.payload[..self.payload.len().min(PAYLOAD_PREVIEW_LEN)];
+        f.write_str(":")?;
+        for byte in preview {
+            write!(f, " {:02x}", byte)?;
+        }
+        let ascii: String = preview
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        write!(f, " |{}|", ascii)?;
+        if self.payload.len() > preview.len() {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
 #[allow(dead_code)]
 impl NdefRecord {
     pub fn builder() -> NdefRecordBuilder {
         NdefRecordBuilder::new()
     }
+
+    /// The TNF Empty record, encoded as `D0 00 00` on its own.
+    pub fn empty() -> Self {
+        Self {
+            flags: RecordFlags::SR,
+            tnf: TNF::Empty,
+            record_type: vec![],
+            id: None,
+            payload: vec![],
+        }
+    }
     pub fn flags(&self) -> RecordFlags {
         self.flags
     }
@@ -41,14 +116,34 @@ impl NdefRecord {
             .copied()
     }
 
+    /// Decodes the payload into the type matching the record's TNF and type.
+    pub fn typed(&self) -> Result<TypedPayload> {
+        TypedPayload::try_from(self)
+    }
+
     pub fn id(&self) -> Option<&[u8]> {
         self.id.as_deref()
     }
 
+    /// Compares TNF, type, id and payload, treating an IL flag with an
+    /// empty id the same as no id. Stacks differ in how they set the
+    /// header bits of the same logical record.
+    pub fn eq_semantic(&self, other: &Self) -> bool {
+        self.tnf == other.tnf
+            && self.record_type == other.record_type
+            && self.id().unwrap_or_default() == other.id().unwrap_or_default()
+            && self.payload == other.payload
+    }
+
     pub fn payload(&self) -> &[u8] {
         &self.payload
     }
 
+    /// Consumes the record, returning its TNF, type, id and payload buffers.
+    pub fn into_parts(self) -> (TNF, Vec<u8>, Option<Vec<u8>>, Vec<u8>) {
+        (self.tnf, self.record_type, self.id, self.payload)
+    }
+
     pub fn clear_begin(&mut self) {
         self.flags.remove(RecordFlags::MB);
     }
@@ -57,10 +152,86 @@ impl NdefRecord {
         self.flags.remove(RecordFlags::ME);
     }
 
-    pub fn to_buffer(&self, flag: RecordFlags) -> Result<Vec<u8>> {
-        let buffer: Vec<u8> = vec![];
-        let mut output = Cursor::new(buffer);
-        let mut rf = self.flags;
+    /// Replaces the payload, switching between the SR and 4-byte length
+    /// formats to fit it.
+    pub fn set_payload<T: Into<Vec<u8>>>(&mut self, payload: T) {
+        self.payload = payload.into();
+        self.flags.set(RecordFlags::SR, self.payload.len() < 256);
+    }
+
+    /// Replaces the id, updating the IL flag. An empty id removes it.
+    pub fn set_id<T: Into<Vec<u8>>>(&mut self, id: T) {
+        let id = id.into();
+        self.flags.set(RecordFlags::IL, !id.is_empty());
+        self.id = (!id.is_empty()).then_some(id);
+    }
+
+    /// Replaces the type field. Lengths and TNF rules are checked when the
+    /// record is encoded.
+    pub fn set_type<T: Into<Vec<u8>>>(&mut self, record_type: T) {
+        self.record_type = record_type.into();
+    }
+
+    /// Copy with header flags derived from the fields alone: SR for
+    /// payloads under 256 bytes, IL for a non-empty id, and MB, ME and CF
+    /// cleared. An empty id is dropped.
+    pub(crate) fn normalized(&self) -> NdefRecord {
+        let mut record = NdefRecord {
+            flags: RecordFlags::empty(),
+            tnf: self.tnf,
+            record_type: self.record_type.clone(),
+            id: None,
+            payload: vec![],
+        };
+        record.set_payload(self.payload.clone());
+        record.set_id(self.id.clone().unwrap_or_default());
+        record
+    }
+
+    /// Record of `tnf` from generated fields, adjusted so the builder
+    /// accepts it: Empty drops every field, Unknown drops the type and
+    /// AbsoluteUri gets a `/` type when it has none. Shared by the
+    /// `Arbitrary` and proptest generators.
+    #[cfg(any(feature = "arbitrary", feature = "proptest"))]
+    pub(crate) fn sanitized(tnf: TNF, mut record_type: Vec<u8>, id: Vec<u8>, payload: Vec<u8>) -> Result<NdefRecord> {
+        if tnf == TNF::Empty {
+            return NdefRecord::builder().tnf(tnf).build();
+        }
+        match tnf {
+            TNF::Unknown => record_type.clear(),
+            TNF::AbsoluteUri if record_type.is_empty() => record_type.push(b'/'),
+            _ => {}
+        }
+        NdefRecord::builder().tnf(tnf).payload_raw(record_type, payload).id(id).build()
+    }
+
+    /// Appends a middle or terminating chunk (TNF Unchanged, no type, no
+    /// id) to a record whose CF flag is set. After the terminating chunk CF
+    /// is cleared and the record takes the chunk's ME flag.
+    pub(crate) fn append_chunk(&mut self, chunk: NdefRecord) -> std::result::Result<(), DecodeErrorKind> {
+        if !self.flags.contains(RecordFlags::CF) {
+            return Err(DecodeErrorKind::InvalidChunk("record is not chunked"));
+        }
+        if chunk.tnf != TNF::Unchanged || !chunk.record_type.is_empty() || chunk.id.is_some() {
+            return Err(DecodeErrorKind::InvalidChunk("invalid middle or terminating chunk"));
+        }
+        if chunk.flags.contains(RecordFlags::CF | RecordFlags::ME) {
+            return Err(DecodeErrorKind::InvalidChunk("ME flag set on a chunk that is not the last"));
+        }
+        if self.payload.len() + chunk.payload.len() > u32::MAX as usize {
+            return Err(DecodeErrorKind::InvalidChunk("reassembled payload exceeds 4 GiB"));
+        }
+        self.payload.extend_from_slice(&chunk.payload);
+        self.flags.set(RecordFlags::SR, self.payload.len() < 256);
+        if !chunk.flags.contains(RecordFlags::CF) {
+            self.flags.remove(RecordFlags::CF);
+            self.flags.set(RecordFlags::ME, chunk.flags.contains(RecordFlags::ME));
+        }
+        Ok(())
+    }
+
+    fn header(&self, flag: RecordFlags) -> RecordHeader {
+        let mut rf = self.flags & !RecordFlags::TNF;
         if flag & RecordFlags::MB == RecordFlags::MB {
             rf |= RecordFlags::MB;
         } else {
@@ -73,48 +244,206 @@ impl NdefRecord {
             rf &= !RecordFlags::ME;
         }
 
-        let flag = rf.bits() | ((self.tnf as u8) & 0x07);
+        RecordHeader {
+            flags: rf,
+            tnf: self.tnf,
+            type_len: self.record_type.len() as u8,
+            payload_len: self.payload.len() as u32,
+            id_len: self.id.as_ref().map_or(0, |id| (id.len() & 0xff) as u8),
+        }
+    }
+
+    /// Exact size of [`NdefRecord::to_buffer`] output, computed without
+    /// serializing.
+    pub fn encoded_len(&self, flag: RecordFlags) -> usize {
+        let header = self.header(flag);
+        header.encoded_len() + header.body_len()
+    }
 
-        output
-            .write_u8(flag)
-            .map_err(|_| anyhow!("Failed to write flags"))?;
+    pub fn to_buffer(&self, flag: RecordFlags) -> Result<Vec<u8>> {
+        let mut output = Cursor::new(Vec::with_capacity(self.encoded_len(flag)));
+        self.write_to(&mut output, flag)?;
+        Ok(output.into_inner())
+    }
 
-        output
-            .write_u8(self.record_type.len() as u8)
-            .map_err(|_| anyhow!("Failed to write record type length"))?;
-        if self.flags & RecordFlags::SR == RecordFlags::SR {
-            output
-                .write_u8(self.payload.len() as u8)
-                .map_err(|_| anyhow!("Failed to write ID length"))?;
-        } else {
-            output
-                .write_u32::<LittleEndian>(self.payload.len() as u32)
-                .map_err(|_| anyhow!("Failed to write payload length"))?;
+    /// Encodes the record into the start of `buffer`, returning the number
+    /// of bytes written. Nothing is written if `buffer` is too small.
+    pub fn to_buffer_into(&self, buffer: &mut [u8], flag: RecordFlags) -> Result<usize> {
+        let needed = self.encoded_len(flag);
+        if buffer.len() < needed {
+            return Err(NdefError::BufferTooSmall {
+                needed,
+                available: buffer.len(),
+            });
         }
-        if let Some(id) = self.id.as_ref() {
-            output
-                .write_u8((id.len() & 0xff) as u8)
-                .map_err(|_| anyhow!("Failed to write TNF"))?;
+        self.write_to(&mut &mut buffer[..], flag)?;
+        Ok(needed)
+    }
+
+    /// Rejects records whose fields or header flags cannot be serialized
+    /// as a valid record. Chunk sequences are checked by the message.
+    pub(crate) fn check_encodable(&self) -> std::result::Result<(), EncodeError> {
+        match self.tnf {
+            TNF::Reserved => return Err(EncodeError::ReservedTnf),
+            TNF::Empty if !self.record_type.is_empty() || self.id.is_some() || !self.payload.is_empty() => {
+                return Err(EncodeError::NonEmptyEmptyRecord)
+            }
+            TNF::Unknown | TNF::Unchanged if !self.record_type.is_empty() => {
+                return Err(EncodeError::UnexpectedType(self.tnf))
+            }
+            TNF::AbsoluteUri if self.record_type.is_empty() => {
+                return Err(EncodeError::MissingType(self.tnf))
+            }
+            _ => {}
+        }
+        if self.record_type.len() > 0xff {
+            return Err(EncodeError::TypeTooLong(self.record_type.len()));
+        }
+        let id_len = self.id.as_ref().map_or(0, Vec::len);
+        if id_len > 0xff {
+            return Err(EncodeError::IdTooLong(id_len));
+        }
+        if self.payload.len() > u32::MAX as usize {
+            return Err(EncodeError::PayloadTooLong(self.payload.len()));
+        }
+        if self.flags.contains(RecordFlags::SR) && self.payload.len() > 0xff {
+            return Err(EncodeError::ShortRecordTooLong(self.payload.len()));
         }
-        output
+        if self.flags.contains(RecordFlags::IL) != self.id.is_some() {
+            return Err(EncodeError::IdFlagMismatch);
+        }
+        Ok(())
+    }
+
+    /// Serializes the record straight into `writer`; `flag` supplies the
+    /// MB and ME bits as for [`NdefRecord::to_buffer`].
+    pub fn write_to<W: Write>(&self, writer: &mut W, flag: RecordFlags) -> Result<()> {
+        self.check_encodable()?;
+        self.header(flag).write(writer)?;
+        writer
             .write_all(&self.record_type)
             .map_err(|_| anyhow!("Failed to write record type"))?;
         if let Some(id) = self.id.as_ref() {
-            output
+            writer
                 .write_all(id)
                 .map_err(|_| anyhow!("Failed to write ID"))?;
         }
-        output
+        writer
             .write_all(&self.payload)
             .map_err(|_| anyhow!("Failed to write payload"))?;
-        Ok(output.into_inner())
+        Ok(())
+    }
+
+    /// Decodes the record at the start of `data`, returning it with the
+    /// number of bytes consumed, for records embedded in other framing.
+    /// MB/ME flags are kept as found and chunks are not reassembled.
+    pub fn decode(data: &[u8]) -> Result<(Self, usize)> {
+        let (record, len) = NdefRecordRef::parse(data)?;
+        Ok((record.to_owned(), len))
+    }
+}
+
+/// Decodes a buffer holding exactly one record.
+impl TryFrom<&[u8]> for NdefRecord {
+    type Error = NdefError;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        let (record, len) = NdefRecord::decode(data)?;
+        if len < data.len() {
+            return Err(DecodeError {
+                offset: len,
+                record: 1,
+                kind: DecodeErrorKind::TrailingBytes(data.len() - len),
+            }
+            .into());
+        }
+        Ok(record)
     }
+}
 
-    pub(crate) fn decode(reader: &mut dyn Read) -> Result<Self> {
+impl TryFrom<Vec<u8>> for NdefRecord {
+    type Error = NdefError;
+
+    fn try_from(data: Vec<u8>) -> Result<Self> {
+        Self::try_from(data.as_slice())
+    }
+}
+
+/// Fixed part of a record preceding the type, id and payload fields.
+///
+/// `flags` holds the MB/ME/CF/SR/IL bits; the TNF is kept separately. The
+/// payload length is written as one byte when `SR` is set and as four
+/// big-endian bytes otherwise, and the id length byte is only present when
+/// `IL` is set.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecordHeader {
+    pub flags: RecordFlags,
+    pub tnf: TNF,
+    pub type_len: u8,
+    pub payload_len: u32,
+    pub id_len: u8,
+}
+
+impl RecordHeader {
+    pub fn is_short(&self) -> bool {
+        self.flags.contains(RecordFlags::SR)
+    }
+
+    pub fn has_id(&self) -> bool {
+        self.flags.contains(RecordFlags::IL)
+    }
+
+    /// Size of the encoded header in bytes.
+    pub fn encoded_len(&self) -> usize {
+        let payload_len = if self.is_short() { 1 } else { 4 };
+        let id_len = if self.has_id() { 1 } else { 0 };
+        2 + payload_len + id_len
+    }
+
+    /// Size of the type, id and payload fields following the header.
+    pub fn body_len(&self) -> usize {
+        self.type_len as usize + self.id_len as usize + self.payload_len as usize
+    }
+
+    pub fn write(&self, writer: &mut dyn Write) -> Result<()> {
+        if self.is_short() && self.payload_len > 0xff {
+            return Err(NdefError::InvalidFlags);
+        }
+        let flag = (self.flags & !RecordFlags::TNF).bits() | ((self.tnf as u8) & 0x07);
+        writer
+            .write_u8(flag)
+            .map_err(|_| anyhow!("Failed to write flags"))?;
+        writer
+            .write_u8(self.type_len)
+            .map_err(|_| anyhow!("Failed to write record type length"))?;
+        if self.is_short() {
+            writer
+                .write_u8(self.payload_len as u8)
+                .map_err(|_| anyhow!("Failed to write payload length"))?;
+        } else {
+            writer
+                .write_u32::<BigEndian>(self.payload_len)
+                .map_err(|_| anyhow!("Failed to write payload length"))?;
+        }
+        if self.has_id() {
+            writer
+                .write_u8(self.id_len)
+                .map_err(|_| anyhow!("Failed to write ID length"))?;
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(self.encoded_len());
+        self.write(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    pub fn decode(reader: &mut dyn Read) -> Result<Self> {
         let flags = reader.read_u8().map_err(|e| anyhow!("read error, {}", e))?;
-        let tnf = TNF::from_repr(flags & 0x0f)
+        let tnf = TNF::from_repr(flags & 0x07)
             .ok_or_else(|| NdefError::InvalidTnf)?;
-        let flags = RecordFlags::from_bits_retain(flags);
+        let flags = RecordFlags::from_bits_retain(flags) & !RecordFlags::TNF;
 
         let type_len = reader
             .read_u8()
@@ -125,7 +454,7 @@ impl NdefRecord {
                 .map_err(|_| NdefError::InvalidPayload)? as u32
         } else {
             reader
-                .read_u32::<LittleEndian>()
+                .read_u32::<BigEndian>()
                 .map_err(|_| NdefError::InvalidPayload)?
         };
 
@@ -136,55 +465,185 @@ impl NdefRecord {
         } else {
             0
         };
+        Ok(Self {
+            flags,
+            tnf,
+            type_len,
+            payload_len,
+            id_len,
+        })
+    }
 
-        let mut record_type = vec![0u8; type_len as usize];
-        reader
-            .read_exact(&mut record_type)
-            .map_err(|_| NdefError::InvalidRecordType)?;
+    /// Parses a header from the start of `data`, returning it together with
+    /// the number of bytes it occupied.
+    pub fn parse(data: &[u8]) -> Result<(Self, usize)> {
+        let mut reader = Cursor::new(data);
+        let header = Self::decode(&mut reader)?;
+        Ok((header, reader.position() as usize))
+    }
+}
 
-        let id = if id_len > 0 {
-            let mut id = vec![0u8; id_len as usize];
-            reader
-                .read_exact(&mut id)
-                .map_err(|_| NdefError::InvalidId)?;
-            Some(id)
+/// Record parsed in place: type, id and payload borrow from the input
+/// buffer, so parsing allocates nothing.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NdefRecordRef<'a> {
+    header: RecordHeader,
+    record_type: &'a [u8],
+    id: Option<&'a [u8]>,
+    payload: &'a [u8],
+}
+
+impl<'a> NdefRecordRef<'a> {
+    /// Parses the record at the start of `data`, returning it together with
+    /// the number of bytes it occupied.
+    pub fn parse(data: &'a [u8]) -> Result<(Self, usize)> {
+        Ok(Self::parse_at(data, 0, 0, &DecodeOptions::default())?)
+    }
+
+    /// Parses the record starting at `offset`, returning it with its encoded
+    /// length. Errors report `offset` and the wire record `index`. A payload
+    /// length above the `options` limit is rejected before the payload is
+    /// read.
+    pub(crate) fn parse_at(
+        data: &'a [u8],
+        offset: usize,
+        index: usize,
+        options: &DecodeOptions,
+    ) -> std::result::Result<(Self, usize), DecodeError> {
+        let mut position = offset;
+        let mut take = |field: Field, len: usize| {
+            let available = data.len().saturating_sub(position);
+            if available < len {
+                return Err(DecodeError {
+                    offset: position,
+                    record: index,
+                    kind: DecodeErrorKind::Truncated {
+                        field,
+                        expected: len,
+                        actual: available,
+                    },
+                });
+            }
+            position += len;
+            Ok(&data[position - len..position])
+        };
+        let byte = take(Field::Flags, 1)?[0];
+        let flags = RecordFlags::from_bits_retain(byte) & !RecordFlags::TNF;
+        let tnf = TNF::from_repr(byte & 0x07).unwrap();
+        let type_len = take(Field::TypeLength, 1)?[0];
+        let payload_len = if flags.contains(RecordFlags::SR) {
+            take(Field::PayloadLength, 1)?[0] as u32
         } else {
-            None
+            let bytes = take(Field::PayloadLength, 4)?;
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            if options.little_endian_lengths {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            }
         };
-
-        let mut payload = vec![0u8; payload_len as usize];
-        reader
-            .read_exact(&mut payload)
-            .map_err(|_| NdefError::InvalidPayload)?;
-        Ok(Self {
-            flags,
-            tnf,
-            id,
+        if payload_len as usize > options.max_payload_len {
+            return Err(DecodeError {
+                offset,
+                record: index,
+                kind: DecodeErrorKind::PayloadTooLarge {
+                    len: payload_len as usize,
+                    limit: options.max_payload_len,
+                },
+            });
+        }
+        let id_len = if flags.contains(RecordFlags::IL) {
+            take(Field::IdLength, 1)?[0]
+        } else {
+            0
+        };
+        let record_type = take(Field::Type, type_len as usize)?;
+        let id = take(Field::Id, id_len as usize)?;
+        let payload = take(Field::Payload, payload_len as usize)?;
+        let record = Self {
+            header: RecordHeader {
+                flags,
+                tnf,
+                type_len,
+                payload_len,
+                id_len,
+            },
             record_type,
+            id: flags.contains(RecordFlags::IL).then_some(id),
             payload,
-        })
+        };
+        Ok((record, position - offset))
+    }
+
+    pub fn header(&self) -> &RecordHeader {
+        &self.header
+    }
+
+    pub fn flags(&self) -> RecordFlags {
+        self.header.flags
+    }
+
+    pub fn tnf(&self) -> TNF {
+        self.header.tnf
+    }
+
+    pub fn record_type(&self) -> &'a [u8] {
+        self.record_type
+    }
+
+    pub fn id(&self) -> Option<&'a [u8]> {
+        self.id
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// Copies the fields into an owned [`NdefRecord`].
+    pub fn to_owned(&self) -> NdefRecord {
+        NdefRecord {
+            flags: self.header.flags,
+            tnf: self.header.tnf,
+            record_type: self.record_type.to_vec(),
+            id: self.id.map(<[u8]>::to_vec),
+            payload: self.payload.to_vec(),
+        }
     }
 }
 
 pub struct NdefRecordBuilder {
     flags: RecordFlags,
-    tnf: TNF,
+    tnf: Option<TNF>,
+    /// TNF implied by the [`RecordPayload`] the fields came from.
+    payload_tnf: Option<TNF>,
     record_type: Vec<u8>,
     id: Option<Vec<u8>>,
     payload: Vec<u8>,
+    short_record: Option<bool>,
 }
 
 impl NdefRecordBuilder {
     fn new() -> Self {
         Self {
             flags: RecordFlags::empty(),
-            tnf: TNF::Empty,
+            tnf: None,
+            payload_tnf: None,
             record_type: vec![],
             id: None,
             payload: vec![],
+            short_record: None,
         }
     }
 
+    /// Forces the SR (1-byte payload length) format when `true` or the
+    /// 4-byte format when `false`, instead of picking SR for payloads
+    /// under 256 bytes. [`build`](Self::build) fails if SR is forced on a
+    /// larger payload.
+    pub fn short_record(mut self, short: bool) -> Self {
+        self.short_record = Some(short);
+        self
+    }
+
     pub fn id(mut self, id: Vec<u8>) -> Self {
         if id.is_empty() {
             return self;
@@ -194,35 +653,97 @@ impl NdefRecordBuilder {
         self
     }
 
+    /// Sets the TNF. Unless set, it is taken from the payload, or is Empty.
     pub fn tnf(mut self, tnf: TNF) -> Self {
-        self.tnf = tnf;
+        self.tnf = Some(tnf);
         self
     }
 
+    /// Sets the type and payload fields from `payload`, whose TNF the
+    /// record takes. [`build`](Self::build) fails if an explicit TNF
+    /// contradicts it.
     pub fn payload<P>(mut self, payload: &P) -> Self
     where
         P: RecordPayload,
     {
+        self.payload_tnf = Some(payload.tnf());
         self.record_type = payload.record_type().to_vec();
         self.payload = payload.payload().to_vec();
-        if self.payload.len() < 256 {
-            self.flags |= RecordFlags::SR;
-        } else {
-            self.flags &= !RecordFlags::SR;
-        }
         self
     }
 
-    pub fn build(self) -> Result<NdefRecord> {
-        if self.tnf == TNF::Empty
+    /// NFC Forum well-known record, e.g. from a [`UriPayload`] or
+    /// [`TextPayload`].
+    pub fn well_known<P: RecordPayload>(self, payload: &P) -> Self {
+        self.tnf(TNF::WellKnown).payload(payload)
+    }
+
+    /// External type record, e.g. from an [`ExternalPayload`].
+    pub fn external<P: RecordPayload>(self, payload: &P) -> Self {
+        self.tnf(TNF::External).payload(payload)
+    }
+
+    /// Media-type record, e.g. from a [`MimePayload`] or [`IconPayload`].
+    pub fn mime<P: RecordPayload>(self, payload: &P) -> Self {
+        self.tnf(TNF::MimeMedia).payload(payload)
+    }
+
+    /// Sets the type and payload fields directly, for combinations no
+    /// [`RecordPayload`] models. The TNF must be set separately.
+    pub fn payload_raw<T: Into<Vec<u8>>, P: Into<Vec<u8>>>(mut self, record_type: T, payload: P) -> Self {
+        self.payload_tnf = None;
+        self.record_type = record_type.into();
+        self.payload = payload.into();
+        self
+    }
+
+    /// Record whose payload is a whole encoded NDEF message, as used by
+    /// Smart Poster and handover records.
+    pub fn nested_message<T: Into<Vec<u8>>>(mut self, record_type: T, message: &NdefMessage) -> Result<Self> {
+        self.payload_tnf = None;
+        self.record_type = record_type.into();
+        self.payload = message.to_buffer()?;
+        Ok(self)
+    }
+
+    /// Absolute-URI record: the URI goes into the type field and the
+    /// payload is left empty.
+    pub fn absolute_uri<T: Into<String>>(mut self, uri: T) -> Self {
+        self.tnf = Some(TNF::AbsoluteUri);
+        self.payload_tnf = None;
+        self.record_type = uri.into().into_bytes();
+        self.payload = vec![];
+        self
+    }
+
+    /// Record of unknown type: opaque payload bytes with an empty type field.
+    pub fn unknown<T: Into<Vec<u8>>>(mut self, payload: T) -> Self {
+        self.tnf = Some(TNF::Unknown);
+        self.payload_tnf = None;
+        self.record_type = vec![];
+        self.payload = payload.into();
+        self
+    }
+
+    pub fn build(mut self) -> Result<NdefRecord> {
+        let tnf = match (self.tnf, self.payload_tnf) {
+            (Some(tnf), Some(payload_tnf)) if tnf != payload_tnf => return Err(NdefError::InvalidTnf),
+            (tnf, payload_tnf) => tnf.or(payload_tnf).unwrap_or(TNF::Empty),
+        };
+        let short = self.short_record.unwrap_or(self.payload.len() < 256);
+        if short && self.payload.len() > 0xff {
+            return Err(NdefError::InvalidFlags);
+        }
+        self.flags.set(RecordFlags::SR, short);
+        if tnf == TNF::Empty
             && (!self.payload.is_empty() || !self.record_type.is_empty() || self.id.is_some())
         {
             return Err(anyhow!("Invalid empty record").into());
         }
-        if self.tnf == TNF::Empty {
+        if tnf == TNF::Empty {
             Ok(NdefRecord {
                 flags: self.flags,
-                tnf: self.tnf,
+                tnf,
                 record_type: vec![],
                 id: None,
                 payload: vec![],
@@ -231,6 +752,12 @@ impl NdefRecordBuilder {
             if self.record_type.len() > 0xff {
                 return Err(anyhow!("record type too long").into());
             }
+            if tnf == TNF::AbsoluteUri && self.record_type.is_empty() {
+                return Err(NdefError::InvalidRecordType);
+            }
+            if matches!(tnf, TNF::Unknown | TNF::Unchanged) && !self.record_type.is_empty() {
+                return Err(NdefError::InvalidRecordType);
+            }
             if let Some(id) = self.id.as_ref() {
                 if id.len() > 0xff {
                     return Err(anyhow!("record id too long").into());
@@ -239,7 +766,7 @@ impl NdefRecordBuilder {
 
             Ok(NdefRecord {
                 flags: self.flags,
-                tnf: self.tnf,
+                tnf,
                 record_type: self.record_type,
                 id: self.id,
                 payload: self.payload,
@@ -247,3 +774,236 @@ impl NdefRecordBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_payload_is_short() {
+        let record = NdefRecord::builder().tnf(TNF::Empty).build().unwrap();
+        assert_eq!("d00000", hex::encode(NdefMessage::from(record).to_buffer().unwrap()));
+        assert_eq!("d00000", hex::encode(NdefRecord::empty().to_buffer(RecordFlags::MB | RecordFlags::ME).unwrap()));
+
+        let record = NdefRecord::builder()
+            .tnf(TNF::AbsoluteUri)
+            .payload(&AbsoluteUriPayload::from_static("https://example.com/schema"))
+            .build()
+            .unwrap();
+        assert!(record.flags().contains(RecordFlags::SR));
+        assert!(NdefRecord::builder().tnf(TNF::AbsoluteUri).build().is_err());
+    }
+
+    #[test]
+    fn test_short_record_override() {
+        let record = NdefRecord::builder().tnf(TNF::Unknown).unknown(vec![1, 2]).short_record(false).build().unwrap();
+        let data = NdefMessage::from(record).to_buffer().unwrap();
+        assert_eq!((0xc5, 8), (data[0], data.len()));
+        assert!(!NdefMessage::decode(&data).unwrap()[0].flags().contains(RecordFlags::SR));
+
+        assert!(NdefRecord::builder().tnf(TNF::Unknown).unknown(vec![0; 256]).short_record(true).build().is_err());
+    }
+
+    #[test]
+    fn test_payload_raw() {
+        let record = NdefRecord::builder()
+            .tnf(TNF::MimeMedia)
+            .payload_raw("application/x-test", vec![1, 2, 3])
+            .build()
+            .unwrap();
+        assert_eq!(b"application/x-test", record.record_type());
+        assert_eq!(&[1, 2, 3], record.payload());
+    }
+
+    #[test]
+    fn test_tnf_shorthands() {
+        let uri = UriPayload::from_static("https://example.com");
+        let record = NdefRecord::builder().well_known(&uri).build().unwrap();
+        assert_eq!(TNF::WellKnown, record.tnf());
+        assert_eq!(uri, UriPayload::try_from(&record).unwrap());
+
+        let external = ExternalPayload::from_raw(b"example.com:t".to_vec(), vec![1]);
+        assert_eq!(TNF::External, NdefRecord::builder().external(&external).build().unwrap().tnf());
+        let icon = IconPayload::png(b"\x89PNG\r\n\x1a\n".to_vec()).unwrap();
+        assert_eq!(TNF::MimeMedia, NdefRecord::builder().mime(&icon).build().unwrap().tnf());
+
+        // The TNF follows the payload, and contradicting it is rejected.
+        assert_eq!(TNF::WellKnown, NdefRecord::builder().payload(&uri).build().unwrap().tnf());
+        assert!(matches!(NdefRecord::builder().external(&uri).build(), Err(NdefError::InvalidTnf)));
+        assert!(NdefRecord::builder().well_known(&external).build().is_err());
+        assert!(NdefRecord::builder().payload(&icon).tnf(TNF::WellKnown).build().is_err());
+    }
+
+    #[test]
+    fn test_nested_message() {
+        let uri = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        let inner = NdefMessage::from(uri);
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .nested_message(RTD_SMART_POSTER.as_bytes(), &inner)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(RTD_SMART_POSTER.as_bytes(), record.record_type());
+        assert_eq!(inner.to_buffer().unwrap(), record.payload());
+        let poster = SmartPosterPayload::try_from(&record).unwrap();
+        assert_eq!(1, poster.message().unwrap().records().len());
+    }
+
+    #[test]
+    fn test_record_ref() {
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .id(b"u1".to_vec())
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        let mut data = record.to_buffer(RecordFlags::MB | RecordFlags::ME).unwrap();
+        data.push(0xaa);
+        let (parsed, len) = NdefRecordRef::parse(&data).unwrap();
+        assert_eq!(data.len() - 1, len);
+        assert_eq!(b"U", parsed.record_type());
+        assert_eq!(Some(&b"u1"[..]), parsed.id());
+        assert_eq!(record.payload(), parsed.payload());
+        assert_eq!(record, parsed.to_owned());
+        assert!(NdefRecordRef::parse(&data[..len - 1]).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .id(b"a".to_vec())
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            "WellKnown type=\"U\" id=\"a\" flags=SR|IL payload[12]: 04 65 78 61 6d 70 6c 65 2e 63 6f 6d |.example.com|",
+            record.to_string()
+        );
+        let record = NdefRecord::builder().unknown(vec![0u8; 20]).build().unwrap();
+        assert!(record.to_string().ends_with("|................|..."));
+        let record = NdefRecord::builder().tnf(TNF::Empty).build().unwrap();
+        assert_eq!("Empty flags=SR payload[0]", record.to_string());
+    }
+
+    #[test]
+    fn test_typed() {
+        let uri = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            TypedPayload::Uri(UriPayload::from_static("https://example.com")),
+            uri.typed().unwrap()
+        );
+        let external = NdefRecord::builder()
+            .tnf(TNF::External)
+            .payload(&ExternalPayload::from_static(b"example.com:a", b"1"))
+            .build()
+            .unwrap();
+        assert!(matches!(external.typed().unwrap(), TypedPayload::External(_)));
+        // Status byte 0x02, language "en", then the text.
+        let (text, _) = NdefRecord::decode(&hex::decode("d101075402656e68692121").unwrap()).unwrap();
+        match text.typed().unwrap() {
+            TypedPayload::Text(text) => {
+                assert_eq!(("en", "hi!!"), (text.language().as_str(), text.text()));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        let unknown = NdefRecord::builder().unknown(vec![1, 2]).build().unwrap();
+        assert_eq!(TypedPayload::Unknown(unknown.clone()), unknown.typed().unwrap());
+    }
+
+    #[test]
+    fn test_absolute_uri_and_unknown() {
+        let record = NdefRecord::builder()
+            .absolute_uri("https://example.com/type")
+            .build()
+            .unwrap();
+        assert_eq!(TNF::AbsoluteUri, record.tnf());
+        assert_eq!(b"https://example.com/type", record.record_type());
+        assert!(record.payload().is_empty());
+
+        let record = NdefRecord::builder().unknown(vec![0x01, 0x02]).build().unwrap();
+        assert_eq!(TNF::Unknown, record.tnf());
+        assert!(record.record_type().is_empty());
+        let bytes = NdefMessage::from(record.clone()).to_buffer().unwrap();
+        assert_eq!("d500020102", hex::encode(&bytes));
+        assert_eq!(record, NdefMessage::decode(bytes).unwrap().records()[0]);
+
+        let result = NdefRecord::builder()
+            .tnf(TNF::Unknown)
+            .payload(&ExternalPayload::from_static(b"a:b", b"x"))
+            .build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_record_header() {
+        let header = RecordHeader {
+            flags: RecordFlags::MB | RecordFlags::ME | RecordFlags::SR | RecordFlags::IL,
+            tnf: TNF::WellKnown,
+            type_len: 1,
+            payload_len: 5,
+            id_len: 2,
+        };
+        let bytes = header.encode().unwrap();
+        assert_eq!("d9010502", hex::encode(&bytes));
+        assert_eq!(4, header.encoded_len());
+        assert_eq!(8, header.body_len());
+        assert_eq!((header, 4), RecordHeader::parse(&bytes).unwrap());
+
+        let header = RecordHeader {
+            flags: RecordFlags::SR,
+            tnf: TNF::MimeMedia,
+            type_len: 1,
+            payload_len: 300,
+            id_len: 0,
+        };
+        assert!(header.encode().is_err());
+        assert!(RecordHeader::parse(&[0xd1, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_decode_consumed() {
+        let data = hex::decode("910001aa510001bbff").unwrap();
+        let (first, len) = NdefRecord::decode(&data).unwrap();
+        assert_eq!((&[0xaa][..], 4), (first.payload(), len));
+        let (second, len) = NdefRecord::decode(&data[4..]).unwrap();
+        assert_eq!((&[0xbb][..], 4), (second.payload(), len));
+        assert!(NdefRecord::decode(&data[8..]).is_err());
+    }
+
+    #[test]
+    fn test_try_from_bytes() {
+        let data = hex::decode("d50001aa").unwrap();
+        let record: NdefRecord = data.as_slice().try_into().unwrap();
+        assert_eq!(&[0xaa], record.payload());
+        let message: NdefMessage = data.clone().try_into().unwrap();
+        assert_eq!(record, message[0]);
+        assert!(NdefRecord::try_from(hex::decode("d50001aaff").unwrap()).is_err());
+        assert!(NdefMessage::try_from(&data[..3]).is_err());
+    }
+
+    #[test]
+    fn test_mutation() {
+        let (mut record, _) = NdefRecord::decode(&hex::decode("d50001aa").unwrap()).unwrap();
+        record.set_payload(vec![0xbb; 300]);
+        record.set_id(b"x".to_vec());
+        let data = NdefMessage::from(record.clone()).to_buffer().unwrap();
+        assert_eq!("cd000000012c0178", hex::encode(&data[..8]));
+        assert_eq!(record, NdefMessage::decode(&data).unwrap()[0]);
+
+        record.set_payload(vec![0xcc]);
+        record.set_id(vec![]);
+        record.set_type(b"t".to_vec());
+        record.tnf = TNF::External;
+        assert_eq!("d4010174cc", hex::encode(NdefMessage::from(record).to_buffer().unwrap()));
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{error::NdefError, payload::RecordPayload, record::NdefRecord, *};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Signature RTD version 2.0.
+pub const SIGNATURE_VERSION: u8 = 0x20;
+
+/// Certificates a certificate chain can hold: the count is a nibble.
+const MAX_CERTIFICATES: usize = 0x0f;
+/// Longest signature or certificate, stored with a 2-byte length.
+const MAX_FIELD_LEN: usize = 0xffff;
+
+/// Signature type 0x00: no signature present. Such a record only marks the
+/// start of a signed section.
+pub const SIGNATURE_NOT_PRESENT: u8 = 0x00;
+
+/// Signature ("Sig") record payload, Signature RTD 2.0.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SignaturePayload {
+    signature_type: u8,
+    hash_type: u8,
+    signature: Vec<u8>,
+    certificate_format: u8,
+    certificates: Vec<Vec<u8>>,
+}
+
+impl SignaturePayload {
+    /// Fails if `signature` exceeds 65535 bytes.
+    pub fn new<T: Into<Vec<u8>>>(signature_type: u8, hash_type: u8, signature: T) -> Result<Self> {
+        let signature = signature.into();
+        if signature.len() > MAX_FIELD_LEN {
+            return Err(NdefError::InvalidPayload);
+        }
+        Ok(Self {
+            signature_type: signature_type & 0x7f,
+            hash_type,
+            signature,
+            certificate_format: 0,
+            certificates: vec![],
+        })
+    }
+
+    /// Marker record that opens a signed section without signing anything.
+    pub fn marker() -> Self {
+        Self {
+            signature_type: SIGNATURE_NOT_PRESENT,
+            hash_type: 0,
+            signature: vec![],
+            certificate_format: 0,
+            certificates: vec![],
+        }
+    }
+
+    /// Fails if the chain already holds 15 certificates or `certificate`
+    /// exceeds 65535 bytes.
+    pub fn add_certificate<T: Into<Vec<u8>>>(mut self, format: u8, certificate: T) -> Result<Self> {
+        let certificate = certificate.into();
+        if certificate.len() > MAX_FIELD_LEN || self.certificates.len() == MAX_CERTIFICATES {
+            return Err(NdefError::InvalidPayload);
+        }
+        self.certificate_format = format & 0x07;
+        self.certificates.push(certificate);
+        Ok(self)
+    }
+
+    pub fn is_marker(&self) -> bool {
+        self.signature_type == SIGNATURE_NOT_PRESENT
+    }
+
+    pub fn signature_type(&self) -> u8 {
+        self.signature_type
+    }
+
+    pub fn hash_type(&self) -> u8 {
+        self.hash_type
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    pub fn certificate_format(&self) -> u8 {
+        self.certificate_format
+    }
+
+    pub fn certificates(&self) -> &[Vec<u8>] {
+        &self.certificates
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![SIGNATURE_VERSION, self.signature_type, self.hash_type];
+        buffer.extend_from_slice(&(self.signature.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(&self.signature);
+        buffer.push((self.certificate_format << 4) | self.certificates.len() as u8);
+        for certificate in self.certificates.iter() {
+            buffer.extend_from_slice(&(certificate.len() as u16).to_be_bytes());
+            buffer.extend_from_slice(certificate);
+        }
+        buffer
+    }
+
+    fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = data;
+        let mut take = |len: usize| -> Result<&[u8]> {
+            if reader.len() < len {
+                return Err(NdefError::InvalidPayload);
+            }
+            let (head, tail) = reader.split_at(len);
+            reader = tail;
+            Ok(head)
+        };
+        if take(1)?[0] != SIGNATURE_VERSION {
+            return Err(NdefError::InvalidVersion);
+        }
+        let header = take(2)?;
+        // Signature and certificate chain URIs are not supported.
+        if header[0] & 0x80 != 0 {
+            return Err(NdefError::InvalidPayload);
+        }
+        let (signature_type, hash_type) = (header[0], header[1]);
+        let len = take(2)?;
+        let signature = take(u16::from_be_bytes([len[0], len[1]]) as usize)?.to_vec();
+        let mut payload = Self::new(signature_type, hash_type, signature)?;
+        let chain = take(1)?[0];
+        if chain & 0x80 != 0 {
+            return Err(NdefError::InvalidPayload);
+        }
+        for _ in 0..(chain & 0x0f) {
+            let len = take(2)?;
+            let certificate = take(u16::from_be_bytes([len[0], len[1]]) as usize)?;
+            payload = payload.add_certificate((chain >> 4) & 0x07, certificate)?;
+        }
+        Ok(payload)
+    }
+}
+
+impl RecordPayload for SignaturePayload {
+    fn tnf(&self) -> TNF {
+        TNF::WellKnown
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(RTD_SIGNATURE.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.to_bytes())
+    }
+}
+
+impl TryFrom<&NdefRecord> for SignaturePayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        if record.tnf() != TNF::WellKnown {
+            return Err(NdefError::InvalidTnf);
+        }
+        if record.record_type() != RTD_SIGNATURE.as_bytes() {
+            return Err(NdefError::InvalidRecordType);
+        }
+        Self::decode(record.payload())
+    }
+}
+
+/// Data covered by a signature: the type, id and payload fields of each
+/// covered record, concatenated in message order.
+pub fn signed_data(records: &[NdefRecord]) -> Vec<u8> {
+    let mut data = vec![];
+    for record in records {
+        data.extend_from_slice(record.record_type());
+        data.extend_from_slice(record.id().unwrap_or_default());
+        data.extend_from_slice(record.payload());
+    }
+    data
+}
+
+fn is_signature(record: &NdefRecord) -> bool {
+    record.tnf() == TNF::WellKnown && record.record_type() == RTD_SIGNATURE.as_bytes()
+}
+
+/// Builds a message where each Signature record covers exactly the records
+/// given to the matching [`SignedMessageBuilder::signed`] call.
+///
+/// A signature covers every record since the previous Sig record, so
+/// unsigned records queued before a signed section are closed off with a
+/// marker Sig record.
+#[derive(Debug, Default)]
+pub struct SignedMessageBuilder {
+    records: Vec<NdefRecord>,
+    pending: Vec<NdefRecord>,
+}
+
+impl SignedMessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unsigned(mut self, record: NdefRecord) -> Self {
+        self.pending.push(record);
+        self
+    }
+
+    /// Adds `records` followed by the Signature record produced by `signer`,
+    /// which receives the data to sign (see [`signed_data`]).
+    pub fn signed<I, F>(mut self, records: I, signer: F) -> Result<Self>
+    where
+        I: IntoIterator<Item = NdefRecord>,
+        F: FnOnce(&[u8]) -> Result<SignaturePayload>,
+    {
+        let records: Vec<NdefRecord> = records.into_iter().collect();
+        if records.is_empty() {
+            return Err(NdefError::InvalidMessage);
+        }
+        if !self.pending.is_empty() {
+            self.records.append(&mut self.pending);
+            self.records.push(signature_record(&SignaturePayload::marker())?);
+        }
+        let signature = signer(&signed_data(&records))?;
+        self.records.extend(records);
+        self.records.push(signature_record(&signature)?);
+        Ok(self)
+    }
+
+    pub fn build(mut self) -> NdefMessage {
+        self.records.append(&mut self.pending);
+        NdefMessage::from_vec(self.records)
+    }
+}
+
+fn signature_record(signature: &SignaturePayload) -> Result<NdefRecord> {
+    NdefRecord::builder()
+        .tnf(TNF::WellKnown)
+        .payload(signature)
+        .build()
+}
+
+/// Records covered by one Signature record.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SignatureCoverage {
+    /// Index of the Signature record in the message.
+    pub signature_index: usize,
+    /// Indices of the records it covers.
+    pub covered: Range<usize>,
+}
+
+impl SignatureCoverage {
+    pub fn signed_data(&self, message: &NdefMessage) -> Vec<u8> {
+        signed_data(&message.records()[self.covered.clone()])
+    }
+
+    /// Covered records in message order as a canonical message (see
+    /// [`NdefMessage::canonicalize`]), for signers that hash whole records
+    /// including their headers.
+    pub fn canonical_data(&self, message: &NdefMessage) -> Result<Vec<u8>> {
+        NdefMessage::from(&message.records()[self.covered.clone()]).to_canonical_buffer()
+    }
+}
+
+/// Which records of a message are covered by a signature.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct CoverageReport {
+    pub signatures: Vec<SignatureCoverage>,
+    /// Indices of non-Sig records no signature covers, including those
+    /// before a Sig record that fails to parse.
+    pub uncovered: Vec<usize>,
+    /// Indices of Sig records that fail to parse.
+    pub invalid: Vec<usize>,
+}
+
+impl CoverageReport {
+    pub fn new(message: &NdefMessage) -> Self {
+        let mut report = Self::default();
+        let mut section_start = 0;
+        for (index, record) in message.records().iter().enumerate() {
+            if !is_signature(record) {
+                continue;
+            }
+            match SignaturePayload::try_from(record) {
+                Ok(signature) if !signature.is_marker() => report.signatures.push(SignatureCoverage {
+                    signature_index: index,
+                    covered: section_start..index,
+                }),
+                Ok(_) => report.uncovered.extend(section_start..index),
+                Err(_) => {
+                    report.invalid.push(index);
+                    report.uncovered.extend(section_start..index);
+                }
+            }
+            section_start = index + 1;
+        }
+        report.uncovered.extend(section_start..message.records().len());
+        report
+    }
+
+    pub fn is_fully_signed(&self) -> bool {
+        self.uncovered.is_empty() && self.invalid.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::*;
+
+    fn uri(value: &'static str) -> NdefRecord {
+        NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&UriPayload::from_static(value))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_signature_payload() {
+        let signature = SignaturePayload::new(0x0b, 0x02, vec![0xaa, 0xbb])
+            .unwrap()
+            .add_certificate(0x00, vec![0x30, 0x01])
+            .unwrap();
+        assert_eq!("200b020002aabb0100023001", hex::encode(signature.payload()));
+        let record = signature_record(&signature).unwrap();
+        assert_eq!(signature, SignaturePayload::try_from(&record).unwrap());
+
+        assert!(SignaturePayload::new(0x0b, 0x02, vec![0; 0x10000]).is_err());
+        assert!(signature.clone().add_certificate(0x00, vec![0; 0x10000]).is_err());
+        let full = (1..MAX_CERTIFICATES).fold(signature, |s, _| s.add_certificate(0x00, vec![0x30]).unwrap());
+        assert_eq!(0x0f, full.payload()[7] & 0x0f);
+        assert!(full.add_certificate(0x00, vec![0x30]).is_err());
+    }
+
+    #[test]
+    fn test_signed_message_coverage() {
+        let message = SignedMessageBuilder::new()
+            .unsigned(uri("https://a.example"))
+            .signed([uri("https://b.example"), uri("https://c.example")], |data| {
+                assert_eq!(signed_data(&[uri("https://b.example"), uri("https://c.example")]), data);
+                SignaturePayload::new(0x0b, 0x02, vec![0x01])
+            })
+            .unwrap()
+            .unsigned(uri("https://d.example"))
+            .build();
+        let message = NdefMessage::decode(message.to_buffer().unwrap()).unwrap();
+        assert_eq!(6, message.records().len());
+
+        let report = CoverageReport::new(&message);
+        assert_eq!(
+            vec![SignatureCoverage {
+                signature_index: 4,
+                covered: 2..4,
+            }],
+            report.signatures
+        );
+        assert_eq!(vec![0, 5], report.uncovered);
+        assert!(!report.is_fully_signed());
+        assert!(report.invalid.is_empty());
+        assert_eq!(
+            signed_data(&message.records()[2..4]),
+            report.signatures[0].signed_data(&message)
+        );
+        assert_eq!(
+            NdefMessage::from(&message.records()[2..4]).to_buffer().unwrap(),
+            report.signatures[0].canonical_data(&message).unwrap()
+        );
+
+        // A corrupt Sig record covers nothing.
+        let mut records = message.into_records();
+        records[4].set_payload(vec![0x20, 0x0b]);
+        let report = CoverageReport::new(&NdefMessage::from_vec(records));
+        assert!(report.signatures.is_empty());
+        assert_eq!(vec![4], report.invalid);
+        assert_eq!(vec![0, 2, 3, 5], report.uncovered);
+    }
+}
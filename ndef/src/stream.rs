@@ -0,0 +1,113 @@
+use crate::{error::NdefError, record::RecordHeader, *};
+use std::io::Read;
+
+/// Payload bytes handed to [`DecodeHandler::on_payload_chunk`] at a time.
+pub const PAYLOAD_CHUNK_SIZE: usize = 4096;
+
+/// Receives the records of a message as they are read by [`decode_with`].
+///
+/// Type and id are passed whole (at most 255 bytes each); the payload arrives
+/// in chunks of up to [`PAYLOAD_CHUNK_SIZE`] bytes, so memory use does not
+/// depend on payload size. Returning an error stops decoding.
+pub trait DecodeHandler {
+    fn on_record_start(&mut self, _header: &RecordHeader, _record_type: &[u8], _id: Option<&[u8]>) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_payload_chunk(&mut self, _chunk: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_record_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads one message from `reader`, stopping after the record with the ME
+/// flag, and reports it to `handler`. Returns the number of records read.
+pub fn decode_with(reader: &mut dyn Read, handler: &mut impl DecodeHandler) -> Result<usize> {
+    let mut buffer = vec![0u8; PAYLOAD_CHUNK_SIZE];
+    let mut count = 0;
+    loop {
+        let header = RecordHeader::decode(reader)?;
+        if header.flags.contains(RecordFlags::MB) != (count == 0) {
+            return Err(NdefError::InvalidFlags);
+        }
+        let mut record_type = vec![0u8; header.type_len as usize];
+        reader
+            .read_exact(&mut record_type)
+            .map_err(|_| NdefError::InvalidRecordType)?;
+        let id = if header.has_id() {
+            let mut id = vec![0u8; header.id_len as usize];
+            reader.read_exact(&mut id).map_err(|_| NdefError::InvalidId)?;
+            Some(id)
+        } else {
+            None
+        };
+        handler.on_record_start(&header, &record_type, id.as_deref())?;
+
+        let mut remaining = header.payload_len as usize;
+        while remaining > 0 {
+            let chunk = &mut buffer[..remaining.min(PAYLOAD_CHUNK_SIZE)];
+            reader.read_exact(chunk).map_err(|_| NdefError::InvalidPayload)?;
+            handler.on_payload_chunk(chunk)?;
+            remaining -= chunk.len();
+        }
+        handler.on_record_end()?;
+        count += 1;
+        if header.flags.contains(RecordFlags::ME) {
+            return Ok(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::*;
+
+    #[derive(Default)]
+    struct Collector {
+        types: Vec<Vec<u8>>,
+        chunks: Vec<usize>,
+        payload_len: usize,
+    }
+
+    impl DecodeHandler for Collector {
+        fn on_record_start(&mut self, _header: &RecordHeader, record_type: &[u8], _id: Option<&[u8]>) -> Result<()> {
+            self.types.push(record_type.to_vec());
+            Ok(())
+        }
+
+        fn on_payload_chunk(&mut self, chunk: &[u8]) -> Result<()> {
+            self.chunks.push(chunk.len());
+            self.payload_len += chunk.len();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_decode_with() {
+        let blob = NdefRecord::builder()
+            .tnf(TNF::External)
+            .payload_raw(b"example.com:fw".to_vec(), vec![0x5a; 10000])
+            .build()
+            .unwrap();
+        let uri = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&UriPayload::from_static("https://example.com"))
+            .build()
+            .unwrap();
+        let mut bytes = NdefMessage::from(&[blob, uri]).to_buffer().unwrap();
+        bytes.extend_from_slice(&[0xff; 4]);
+
+        let mut collector = Collector::default();
+        assert_eq!(2, decode_with(&mut bytes.as_slice(), &mut collector).unwrap());
+        assert_eq!(vec![b"example.com:fw".to_vec(), b"U".to_vec()], collector.types);
+        assert_eq!(vec![4096, 4096, 1808, 12], collector.chunks);
+        assert_eq!(10012, collector.payload_len);
+
+        let truncated = &bytes[..100];
+        assert!(decode_with(&mut &truncated[..], &mut Collector::default()).is_err());
+    }
+}
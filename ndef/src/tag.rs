@@ -1,169 +1,1798 @@
-use byteorder::{LittleEndian, WriteBytesExt};
-use std::io::{Cursor, Write};
-use anyhow::Result;
+use std::borrow::Cow;
+use std::ops::Range;
+use bitflags::bitflags;
 
+use crate::chip::ChipModel;
+use crate::error::{NdefError, TlvError};
 use crate::message::NdefMessage;
+use crate::tag_io::TagType;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TlvTag {
-    NULL = 0x00,
-    LockControl = 0x01,
-    MemoryControl = 0x02,
-    NDEFMessage = 0x03,
-    Proprietary = 0xFD,
-    Terminator = 0xFE,
+    NULL,
+    LockControl,
+    MemoryControl,
+    NDEFMessage,
+    Proprietary,
+    Terminator,
+    /// A reserved or vendor-specific tag value, carried through as is.
+    Other(u8),
 }
 
+impl TlvTag {
+    pub fn from_byte(value: u8) -> Self {
+        match value {
+            0x00 => TlvTag::NULL,
+            0x01 => TlvTag::LockControl,
+            0x02 => TlvTag::MemoryControl,
+            0x03 => TlvTag::NDEFMessage,
+            0xfd => TlvTag::Proprietary,
+            0xfe => TlvTag::Terminator,
+            other => TlvTag::Other(other),
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            TlvTag::NULL => 0x00,
+            TlvTag::LockControl => 0x01,
+            TlvTag::MemoryControl => 0x02,
+            TlvTag::NDEFMessage => 0x03,
+            TlvTag::Proprietary => 0xfd,
+            TlvTag::Terminator => 0xfe,
+            TlvTag::Other(tag) => *tag,
+        }
+    }
+}
+
+/// Position of a Lock Control or Memory Control area: `page_address` and
+/// `byte_offset` locate it in pages of `2^page_size_exp` bytes.
+fn control_area(value: &[u8], tag: TlvTag) -> std::result::Result<(u8, u8, u16, u8, u8), TlvError> {
+    if value.len() != 3 {
+        return Err(TlvError::InvalidLength {
+            tag: tag.to_byte(),
+            len: value.len(),
+            expected: 3,
+        });
+    }
+    let size = if value[1] == 0 { 256 } else { value[1] as u16 };
+    Ok((value[0] >> 4, value[0] & 0x0f, size, value[2] >> 4, value[2] & 0x0f))
+}
+
+/// Page address, byte offset and page size exponent locating `byte_address`
+/// with the smallest page size that keeps address and offset within a
+/// nibble each.
+fn control_position(byte_address: usize) -> std::result::Result<(u8, u8, u8), TlvError> {
+    (0..=15u8)
+        .find(|&exp| byte_address >> exp <= 0x0f && byte_address & ((1 << exp) - 1) <= 0x0f)
+        .map(|exp| ((byte_address >> exp) as u8, (byte_address & ((1 << exp) - 1)) as u8, exp))
+        .ok_or(TlvError::InvalidControlArea("address out of range"))
+}
+
+/// Lock Control TLV value: where the dynamic lock bits are.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LockControl {
+    pub page_address: u8,
+    pub byte_offset: u8,
+    /// Number of lock bits, 1 to 256.
+    pub size_bits: u16,
+    pub page_size_exp: u8,
+    /// Each lock bit locks `2^bytes_locked_per_bit_exp` bytes.
+    pub bytes_locked_per_bit_exp: u8,
+}
+
+impl LockControl {
+    /// Lock Control for `size_bits` lock bits (1 to 256) starting at
+    /// `byte_address` in tag memory, each locking `bytes_locked_per_bit`
+    /// bytes (a power of two). The page size is chosen so the address fits.
+    pub fn new(byte_address: usize, size_bits: u16, bytes_locked_per_bit: usize) -> std::result::Result<Self, TlvError> {
+        if !(1..=256).contains(&size_bits) {
+            return Err(TlvError::InvalidControlArea("lock bits out of range"));
+        }
+        if !bytes_locked_per_bit.is_power_of_two() || bytes_locked_per_bit > 1 << 15 {
+            return Err(TlvError::InvalidControlArea("bytes per lock bit not a power of two"));
+        }
+        let (page_address, byte_offset, page_size_exp) = control_position(byte_address)?;
+        Ok(Self {
+            page_address,
+            byte_offset,
+            size_bits,
+            page_size_exp,
+            bytes_locked_per_bit_exp: bytes_locked_per_bit.trailing_zeros() as u8,
+        })
+    }
+
+    pub fn from_bytes(value: &[u8]) -> std::result::Result<Self, TlvError> {
+        let (page_address, byte_offset, size_bits, bytes_locked_per_bit_exp, page_size_exp) =
+            control_area(value, TlvTag::LockControl)?;
+        Ok(Self {
+            page_address,
+            byte_offset,
+            size_bits,
+            page_size_exp,
+            bytes_locked_per_bit_exp,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [
+            (self.page_address << 4) | (self.byte_offset & 0x0f),
+            self.size_bits as u8,
+            (self.bytes_locked_per_bit_exp << 4) | (self.page_size_exp & 0x0f),
+        ]
+    }
+
+    /// Byte range of the lock bits, counted from the start of tag memory.
+    pub fn reserved_area(&self) -> Range<usize> {
+        let start = ((self.page_address as usize) << self.page_size_exp) + self.byte_offset as usize;
+        start..start + (self.size_bits as usize).div_ceil(8)
+    }
+}
+
+/// Memory Control TLV value: a reserved memory area.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MemoryControl {
+    pub page_address: u8,
+    pub byte_offset: u8,
+    /// Reserved bytes, 1 to 256.
+    pub size: u16,
+    pub page_size_exp: u8,
+}
+
+impl MemoryControl {
+    /// Memory Control reserving `size` bytes (1 to 256) from `byte_address`
+    /// in tag memory, addressed in pages of `bytes_per_page` bytes (a power
+    /// of two). Page address and byte offset must each fit a nibble.
+    pub fn new(byte_address: usize, size: u16, bytes_per_page: usize) -> std::result::Result<Self, TlvError> {
+        if !(1..=256).contains(&size) {
+            return Err(TlvError::InvalidControlArea("reserved size out of range"));
+        }
+        if !bytes_per_page.is_power_of_two() || bytes_per_page > 1 << 15 {
+            return Err(TlvError::InvalidControlArea("page size not a power of two"));
+        }
+        let (page_address, byte_offset) = (byte_address / bytes_per_page, byte_address % bytes_per_page);
+        if page_address > 0x0f || byte_offset > 0x0f {
+            return Err(TlvError::InvalidControlArea("address out of range"));
+        }
+        Ok(Self {
+            page_address: page_address as u8,
+            byte_offset: byte_offset as u8,
+            size,
+            page_size_exp: bytes_per_page.trailing_zeros() as u8,
+        })
+    }
+
+    /// Address of the first reserved byte in tag memory.
+    pub fn byte_address(&self) -> usize {
+        self.reserved_area().start
+    }
+
+    pub fn bytes_per_page(&self) -> usize {
+        1 << self.page_size_exp
+    }
+
+    pub fn from_bytes(value: &[u8]) -> std::result::Result<Self, TlvError> {
+        let (page_address, byte_offset, size, _, page_size_exp) = control_area(value, TlvTag::MemoryControl)?;
+        Ok(Self {
+            page_address,
+            byte_offset,
+            size,
+            page_size_exp,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; 3] {
+        [
+            (self.page_address << 4) | (self.byte_offset & 0x0f),
+            self.size as u8,
+            self.page_size_exp & 0x0f,
+        ]
+    }
+
+    /// Reserved byte range, counted from the start of tag memory.
+    pub fn reserved_area(&self) -> Range<usize> {
+        let start = ((self.page_address as usize) << self.page_size_exp) + self.byte_offset as usize;
+        start..start + self.size as usize
+    }
+}
+
+/// Largest value the 3-byte TLV length form can describe.
+const TLV_MAX_LENGTH: usize = 0xfffe;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TlvValue {
+    Null,
+    LockControl(LockControl),
+    MemoryControl(MemoryControl),
+    /// Encoded NDEF message; empty for an empty NDEF TLV.
+    Ndef(Vec<u8>),
+    Proprietary(Vec<u8>),
+    Terminator,
+    /// TLV with a tag value that has no variant of its own, kept so that
+    /// reserved and vendor-specific TLVs survive a read-modify-write. `tag`
+    /// should not be one of the tag values above, or the TLV reads back as
+    /// that kind.
+    Other { tag: u8, value: Vec<u8> },
+}
+
+impl TlvValue {
+    pub fn ndef_message(message: &NdefMessage) -> crate::Result<Self> {
+        let value = message.to_buffer()?;
+        if value.len() > TLV_MAX_LENGTH {
+            return Err(TlvError::TooLong(value.len()).into());
+        }
+        Ok(TlvValue::Ndef(value))
+    }
+
+    /// Lock Control TLV, see [`LockControl::new`].
+    pub fn lock_control(
+        byte_address: usize,
+        size_bits: u16,
+        bytes_locked_per_bit: usize,
+    ) -> std::result::Result<Self, TlvError> {
+        LockControl::new(byte_address, size_bits, bytes_locked_per_bit).map(TlvValue::LockControl)
+    }
+
+    /// Memory Control TLV, see [`MemoryControl::new`].
+    pub fn memory_control(byte_address: usize, size: u16, bytes_per_page: usize) -> std::result::Result<Self, TlvError> {
+        MemoryControl::new(byte_address, size, bytes_per_page).map(TlvValue::MemoryControl)
+    }
+
+    /// Builds a TLV from its tag byte and value field.
+    pub fn parse(tag: u8, value: &[u8]) -> std::result::Result<Self, TlvError> {
+        let tlv = match TlvTag::from_byte(tag) {
+            TlvTag::NULL | TlvTag::Terminator if !value.is_empty() => {
+                return Err(TlvError::InvalidLength {
+                    tag,
+                    len: value.len(),
+                    expected: 0,
+                })
+            }
+            TlvTag::NULL => TlvValue::Null,
+            TlvTag::Terminator => TlvValue::Terminator,
+            TlvTag::LockControl => TlvValue::LockControl(LockControl::from_bytes(value)?),
+            TlvTag::MemoryControl => TlvValue::MemoryControl(MemoryControl::from_bytes(value)?),
+            TlvTag::NDEFMessage => TlvValue::Ndef(value.to_vec()),
+            TlvTag::Proprietary => TlvValue::Proprietary(value.to_vec()),
+            TlvTag::Other(tag) => TlvValue::Other {
+                tag,
+                value: value.to_vec(),
+            },
+        };
+        Ok(tlv)
+    }
+
+    /// Parses the TLV at the start of `data`, returning it with the number
+    /// of bytes it occupies. Null and Terminator TLVs are a lone tag byte;
+    /// other lengths use one byte, or 0xFF and a big-endian `u16`.
+    pub fn decode(data: &[u8]) -> std::result::Result<(Self, usize), TlvError> {
+        let (tag, len, header_len) = Self::header(data)?;
+        let value = data
+            .get(header_len..header_len + len)
+            .ok_or(TlvError::Truncated { tag })?;
+        Ok((Self::parse(tag, value)?, header_len + len))
+    }
+
+    /// Tag byte, value length and header length of the TLV at the start of
+    /// `data`; a Null or Terminator TLV is a header without a value.
+    fn header(data: &[u8]) -> std::result::Result<(u8, usize, usize), TlvError> {
+        let (&tag, rest) = data.split_first().ok_or(TlvError::Empty)?;
+        if matches!(TlvTag::from_byte(tag), TlvTag::NULL | TlvTag::Terminator) {
+            return Ok((tag, 0, 1));
+        }
+        match rest {
+            [0xff, high, low, ..] => Ok((tag, u16::from_be_bytes([*high, *low]) as usize, 4)),
+            [0xff, ..] | [] => Err(TlvError::Truncated { tag }),
+            [len, ..] => Ok((tag, *len as usize, 2)),
+        }
+    }
+
+    pub fn tag(&self) -> TlvTag {
+        match self {
+            TlvValue::Null => TlvTag::NULL,
+            TlvValue::LockControl(_) => TlvTag::LockControl,
+            TlvValue::MemoryControl(_) => TlvTag::MemoryControl,
+            TlvValue::Ndef(_) => TlvTag::NDEFMessage,
+            TlvValue::Proprietary(_) => TlvTag::Proprietary,
+            TlvValue::Terminator => TlvTag::Terminator,
+            TlvValue::Other { tag, .. } => TlvTag::Other(*tag),
+        }
+    }
+
+    /// Byte range of tag memory declared by a Lock Control or Memory Control
+    /// TLV.
+    pub fn reserved_area(&self) -> Option<Range<usize>> {
+        match self {
+            TlvValue::LockControl(lock) => Some(lock.reserved_area()),
+            TlvValue::MemoryControl(memory) => Some(memory.reserved_area()),
+            _ => None,
+        }
+    }
+
+    /// Value field; `None` for the single-byte Null and Terminator TLVs.
+    fn value(&self) -> Option<Cow<'_, [u8]>> {
+        match self {
+            TlvValue::Null | TlvValue::Terminator => None,
+            TlvValue::LockControl(lock) => Some(Cow::Owned(lock.to_bytes().to_vec())),
+            TlvValue::MemoryControl(memory) => Some(Cow::Owned(memory.to_bytes().to_vec())),
+            TlvValue::Ndef(value) | TlvValue::Proprietary(value) | TlvValue::Other { value, .. } => {
+                Some(Cow::Borrowed(value))
+            }
+        }
+    }
+
+    /// Encodes the TLV: the tag byte, then for all but Null and Terminator
+    /// the length (one byte below 0xFF, else 0xFF and a big-endian `u16`)
+    /// and the value.
+    pub(crate) fn to_bytes(&self) -> crate::Result<Vec<u8>> {
+        let mut buffer = vec![self.tag().to_byte()];
+        if let Some(value) = self.value() {
+            if value.len() > TLV_MAX_LENGTH {
+                return Err(TlvError::TooLong(value.len()).into());
+            }
+            if value.len() < 0xff {
+                buffer.push(value.len() as u8);
+            } else {
+                buffer.push(0xff);
+                buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            }
+            buffer.extend_from_slice(&value);
+        }
+        Ok(buffer)
+    }
+}
+
+/// Iterator over the TLV blocks of a data area. It ends after the
+/// Terminator TLV, at the end of the data, or after yielding an error.
 #[derive(Debug, Clone)]
-pub struct TlvValue {
-    tag: TlvTag,
-    value: Option<Vec<u8>>,
+pub struct TlvIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> TlvIter<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Offset of the next TLV in the data.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Iterator for TlvIter<'_> {
+    type Item = std::result::Result<TlvValue, TlvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset >= self.data.len() {
+            return None;
+        }
+        match TlvValue::decode(&self.data[self.offset..]) {
+            Ok((tlv, len)) => {
+                self.offset += len;
+                self.done = tlv == TlvValue::Terminator;
+                Some(Ok(tlv))
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Reads TLVs from `area`, which starts at tag memory address `base`, up to
+/// the Terminator TLV. Bytes in `reserved`, and in the areas declared by Lock
+/// and Memory Control TLVs read along the way, are skipped.
+fn read_tlvs(area: &[u8], base: usize, mut reserved: Vec<Range<usize>>) -> std::result::Result<Vec<TlvValue>, TlvError> {
+    let mut tlvs = vec![];
+    let mut offset = 0;
+    loop {
+        let (head, _) = unreserved(area, base, &reserved, offset, 4);
+        if head.is_empty() {
+            break;
+        }
+        let (tag, len, header_len) = TlvValue::header(&head)?;
+        let (bytes, end) = unreserved(area, base, &reserved, offset, header_len + len);
+        if bytes.len() < header_len + len {
+            return Err(TlvError::Truncated { tag });
+        }
+        let tlv = TlvValue::parse(tag, &bytes[header_len..])?;
+        offset = end;
+        reserved.extend(tlv.reserved_area());
+        let done = tlv == TlvValue::Terminator;
+        tlvs.push(tlv);
+        if done {
+            break;
+        }
+    }
+    Ok(tlvs)
+}
+
+/// Up to `count` bytes of `area` from `offset` on, jumping over the
+/// `reserved` ranges, with the offset following the last byte taken.
+fn unreserved(area: &[u8], base: usize, reserved: &[Range<usize>], mut offset: usize, count: usize) -> (Vec<u8>, usize) {
+    let mut bytes = Vec::with_capacity(count.min(area.len()));
+    while bytes.len() < count && offset < area.len() {
+        match reserved.iter().find(|r| r.contains(&(base + offset))) {
+            Some(range) => offset = range.end - base,
+            None => {
+                bytes.push(area[offset]);
+                offset += 1;
+            }
+        }
+    }
+    (bytes, offset)
+}
+
+/// Lays the TLV stream out from tag memory address `base`, leaving
+/// `reserved` bytes zeroed and continuing the TLVs after them.
+fn lay_out_tlvs(tlvs: &[TlvValue], base: usize, reserved: &[Range<usize>]) -> crate::Result<Vec<u8>> {
+    let is_reserved = |address: usize| reserved.iter().any(|r| r.contains(&address));
+    let mut buffer = vec![];
+    let stream = tlvs.iter().map(|v| v.to_bytes()).collect::<crate::Result<Vec<_>>>()?;
+    for byte in stream.into_iter().flatten() {
+        while is_reserved(base + buffer.len()) {
+            buffer.push(0x00);
+        }
+        buffer.push(byte);
+    }
+    Ok(buffer)
+}
+
+/// Lays the TLVs out as [`lay_out_tlvs`] does, failing if they take more
+/// than `capacity` bytes. With `terminate` set, a Terminator TLV is
+/// appended when the TLVs do not end with one and room remains for it.
+fn fit_tlvs(
+    tlvs: &[TlvValue],
+    base: usize,
+    reserved: &[Range<usize>],
+    capacity: usize,
+    terminate: bool,
+) -> crate::Result<Vec<u8>> {
+    let buffer = lay_out_tlvs(tlvs, base, reserved)?;
+    if buffer.len() > capacity {
+        return Err(NdefError::CapacityExceeded {
+            needed: buffer.len(),
+            available: capacity,
+        });
+    }
+    if terminate && tlvs.last() != Some(&TlvValue::Terminator) {
+        let terminated = lay_out_tlvs(&[tlvs, &[TlvValue::Terminator]].concat(), base, reserved)?;
+        if terminated.len() <= capacity {
+            return Ok(terminated);
+        }
+    }
+    Ok(buffer)
+}
+
+/// Decodes the message of every non-empty NDEF TLV.
+fn ndef_messages(tlvs: &[TlvValue]) -> crate::Result<Vec<NdefMessage>> {
+    tlvs.iter()
+        .filter_map(|tlv| match tlv {
+            TlvValue::Ndef(value) if !value.is_empty() => Some(NdefMessage::decode(value)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// What a [`MemoryRegion`] holds.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RegionKind {
+    CapabilityContainer,
+    /// Bytes of the TLV at `index` in [`NFT2Tag::tlvs`].
+    Tlv { index: usize, tag: TlvTag },
+    /// Declared by a Lock Control or Memory Control TLV.
+    Reserved,
+    /// Unused data area after the last TLV.
+    Free,
+}
+
+/// A run of tag memory, as byte addresses counted from page 0.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MemoryRegion {
+    pub range: Range<usize>,
+    pub kind: RegionKind,
+}
+
+/// Lock byte values that make a Type 2 tag permanently read-only.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct T2tLockBytes {
+    /// Bytes 2 and 3 of page 2: lock pages 3 to 15 and freeze the block
+    /// lock bits.
+    pub static_lock: [u8; 2],
+    /// Address of the first dynamic lock byte and the values to write, for
+    /// data areas above 48 bytes.
+    pub dynamic_lock: Option<(usize, Vec<u8>)>,
+}
+
+pub struct NFT2Tag {
+    cc: [u8; 4],
+    tlvs: Vec<TlvValue>,
+    /// Data area size when it exceeds the 2040 bytes the CC can describe;
+    /// the CC size byte is then 0xFF.
+    extended_size: Option<u16>,
+    /// Append a Terminator TLV when laying out the data area.
+    terminate: bool,
+}
+
+impl NFT2Tag {
+    pub fn builder() -> TagBuilder {
+        TagBuilder::new()
+    }
+
+    /// Builder sized for the data area of a Type 2 `chip`.
+    pub fn builder_for(chip: ChipModel) -> crate::Result<TagBuilder> {
+        if chip.tag_type() != TagType::Type2 {
+            return Err(NdefError::InvalidTagType);
+        }
+        Ok(TagBuilder::new().size_in_bytes(chip.data_area_size() as u16))
+    }
+
+    /// NTAG213: 144 byte data area, dynamic lock bytes at page 0x28.
+    pub fn ntag213() -> TagBuilder {
+        Self::preset(ChipModel::Ntag213)
+    }
+
+    /// NTAG215: 496 byte data area as per the factory CC, dynamic lock bytes
+    /// at page 0x82.
+    pub fn ntag215() -> TagBuilder {
+        Self::preset(ChipModel::Ntag215)
+    }
+
+    /// NTAG216: 872 byte data area as per the factory CC, dynamic lock bytes
+    /// at page 0xE2.
+    pub fn ntag216() -> TagBuilder {
+        Self::preset(ChipModel::Ntag216)
+    }
+
+    /// MIFARE Ultralight EV1 MF0UL11: 48 byte data area, static locks only.
+    pub fn ultralight_ev1_48() -> TagBuilder {
+        Self::preset(ChipModel::UltralightEv1_48)
+    }
+
+    /// MIFARE Ultralight EV1 MF0UL21: 128 byte data area, dynamic lock bytes
+    /// at page 0x24.
+    pub fn ultralight_ev1_128() -> TagBuilder {
+        Self::preset(ChipModel::UltralightEv1_128)
+    }
+
+    /// Builder with the factory CC of `chip` (write access granted) and a
+    /// Lock Control TLV for its dynamic lock bytes, to be followed by the
+    /// NDEF TLV.
+    fn preset(chip: ChipModel) -> TagBuilder {
+        let builder = TagBuilder::new().size_in_bytes(chip.data_area_size() as u16).access(0x0, 0x0);
+        match chip.dynamic_lock() {
+            Some(lock) => builder.add_tlv(TlvValue::LockControl(lock)),
+            None => builder,
+        }
+    }
+
+    /// Parses an image in the [`NFT2Tag::to_bytes`] layout: the CC page
+    /// followed by the data area. TLVs are read up to the Terminator TLV,
+    /// skipping bytes reserved by Lock and Memory Control TLVs, and every
+    /// NDEF TLV is decoded into the returned messages. With a CC size byte
+    /// of 0xFF the data area runs to the end of `data`, so images of
+    /// extended capacity tags read back whole.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<(Self, Vec<NdefMessage>)> {
+        let cc: [u8; 4] = data
+            .get(..4)
+            .and_then(|cc| cc.try_into().ok())
+            .ok_or(NdefError::InvalidTagLength)?;
+        if cc[0] != T2T_MAGIC {
+            return Err(NdefError::InvalidTag);
+        }
+        if cc[1] >> 4 != T2T_MAJOR_VERSION {
+            return Err(NdefError::InvalidTagVersion);
+        }
+        if cc[2] == 0 {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        let size = match cc[2] {
+            T2T_EXTENDED_SIZE => (data.len() - 4).clamp(T2T_MAX_CC_SIZE, T2T_MAX_EXTENDED_SIZE) / 8 * 8,
+            units => units as usize * 8,
+        };
+        let area = &data[4..data.len().min(4 + size)];
+        let extended_size = (size > T2T_MAX_CC_SIZE).then_some(size as u16);
+
+        let tlvs = read_tlvs(area, T2T_DATA_AREA_OFFSET, vec![])?;
+        let messages = ndef_messages(&tlvs)?;
+        let tag = Self {
+            cc,
+            tlvs,
+            extended_size,
+            terminate: false,
+        };
+        Ok((tag, messages))
+    }
+
+    pub fn tlvs(&self) -> &[TlvValue] {
+        &self.tlvs
+    }
+
+    pub fn capacity_in_bytes(&self) -> u16 {
+        self.extended_size.unwrap_or(self.cc[2] as u16 * 8)
+    }
+
+    /// The CC size byte: the data area in 8-byte units, or 0xFF for an
+    /// extended data area.
+    pub fn capacity(&self) -> u8 {
+        self.cc[2]
+    }
+
+    pub fn version(&self) -> u8 {
+        self.cc[1]
+    }
+
+    /// Memory regions reserved by Lock Control and Memory Control TLVs, as
+    /// byte addresses counted from the start of tag memory (page 0).
+    pub fn reserved_areas(&self) -> Vec<Range<usize>> {
+        self.tlvs.iter().filter_map(|v| v.reserved_area()).collect()
+    }
+
+    /// Dynamic lock bits of a data area above 48 bytes: those declared by a
+    /// Lock Control TLV, or else the default ones right after the data area,
+    /// one bit per 8 bytes beyond the first 48.
+    pub fn dynamic_lock(&self) -> Option<LockControl> {
+        let declared = self.tlvs.iter().find_map(|tlv| match tlv {
+            TlvValue::LockControl(lock) => Some(*lock),
+            _ => None,
+        });
+        let area = self.capacity_in_bytes() as usize;
+        if declared.is_some() || area <= T2T_STATIC_AREA_SIZE {
+            return declared;
+        }
+        let bits = (area - T2T_STATIC_AREA_SIZE).div_ceil(8) as u16;
+        LockControl::new(T2T_DATA_AREA_OFFSET + area, bits, 8).ok()
+    }
+
+    /// Address of the first dynamic lock byte and the values that set every
+    /// dynamic lock bit, leaving the unused bits of the last byte clear.
+    pub fn dynamic_lock_bytes(&self) -> Option<(usize, Vec<u8>)> {
+        let lock = self.dynamic_lock()?;
+        let bits = lock.size_bits as usize;
+        let mut bytes = vec![0xff; bits / 8];
+        if bits % 8 != 0 {
+            bytes.push((1u8 << (bits % 8)) - 1);
+        }
+        Some((lock.reserved_area().start, bytes))
+    }
+
+    /// Where the CC, each TLV, the reserved areas and the free space lie.
+    /// TLVs crossing a reserved area are split around it. TLVs that do not
+    /// fit are still mapped, running past the end of the data area, so the
+    /// overflow shows.
+    pub fn memory_map(&self) -> crate::Result<Vec<MemoryRegion>> {
+        let reserved = self.reserved_areas();
+        let is_reserved = |address: usize| reserved.iter().any(|r| r.contains(&address));
+        let mut regions = vec![MemoryRegion {
+            range: T2T_DATA_AREA_OFFSET - 4..T2T_DATA_AREA_OFFSET,
+            kind: RegionKind::CapabilityContainer,
+        }];
+        let mut push = |address: usize, kind: RegionKind| match regions.last_mut() {
+            Some(last) if last.kind == kind && last.range.end == address => last.range.end += 1,
+            _ => regions.push(MemoryRegion {
+                range: address..address + 1,
+                kind,
+            }),
+        };
+
+        let mut address = T2T_DATA_AREA_OFFSET;
+        for (index, tlv) in self.tlvs.iter().enumerate() {
+            for _ in 0..tlv.to_bytes()?.len() {
+                while is_reserved(address) {
+                    push(address, RegionKind::Reserved);
+                    address += 1;
+                }
+                push(address, RegionKind::Tlv { index, tag: tlv.tag() });
+                address += 1;
+            }
+        }
+        let end = T2T_DATA_AREA_OFFSET + self.capacity_in_bytes() as usize;
+        while address < end {
+            push(address, if is_reserved(address) { RegionKind::Reserved } else { RegionKind::Free });
+            address += 1;
+        }
+        // Reserved areas past the data area, such as dynamic lock bytes.
+        for area in reserved.iter().filter(|area| area.start >= address) {
+            regions.push(MemoryRegion {
+                range: area.clone(),
+                kind: RegionKind::Reserved,
+            });
+        }
+        Ok(regions)
+    }
+
+    /// Replaces the message of the first NDEF TLV, or inserts an NDEF TLV
+    /// before the Terminator TLV if there is none. Every other TLV, Null
+    /// padding included, keeps its place. The tag is left unchanged if the
+    /// new message does not fit the data area.
+    pub fn replace_message(&mut self, message: &NdefMessage) -> crate::Result<()> {
+        let tlv = TlvValue::ndef_message(message)?;
+        let previous = self.tlvs.clone();
+        match self.tlvs.iter().position(|tlv| tlv.tag() == TlvTag::NDEFMessage) {
+            Some(index) => self.tlvs[index] = tlv,
+            None => {
+                let index = self
+                    .tlvs
+                    .iter()
+                    .position(|tlv| *tlv == TlvValue::Terminator)
+                    .unwrap_or(self.tlvs.len());
+                self.tlvs.insert(index, tlv);
+            }
+        }
+        if let Err(error) = self.data_area() {
+            self.tlvs = previous;
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Sets the CC write access to denied and returns the lock bytes that
+    /// make the tag permanently read-only once written after the image.
+    pub fn finalize_read_only(&mut self) -> T2tLockBytes {
+        self.cc[3] = (self.cc[3] & 0xf0) | 0x0f;
+        T2tLockBytes {
+            static_lock: [0xff, 0xff],
+            dynamic_lock: self.dynamic_lock_bytes(),
+        }
+    }
+
+    /// Lays the TLV stream out over the data area, leaving reserved bytes
+    /// zeroed and continuing the TLVs after them.
+    fn data_area(&self) -> crate::Result<Vec<u8>> {
+        let capacity = self.capacity_in_bytes() as usize;
+        fit_tlvs(&self.tlvs, T2T_DATA_AREA_OFFSET, &self.reserved_areas(), capacity, self.terminate)
+    }
+
+    /// The CC page followed by the data area. Fails with
+    /// [`NdefError::CapacityExceeded`] if the TLVs do not fit.
+    pub fn to_bytes(&self) -> crate::Result<Vec<u8>> {
+        let header = self.cc.to_vec();
+        Ok([header, self.data_area()?].concat())
+    }
+
+    /// The whole EEPROM from page 0 to the end of the data area: the UID
+    /// with its check bytes, the internal byte, the static lock bytes
+    /// (cleared), the CC and the data area, zero padded to the last page.
+    /// Pass an all-zero `uid` for a placeholder to patch per tag.
+    pub fn to_eeprom(&self, uid: &[u8; 7]) -> crate::Result<Vec<u8>> {
+        // Cascade tag 0x88 is folded into the first check byte.
+        let bcc0 = 0x88 ^ uid[0] ^ uid[1] ^ uid[2];
+        let bcc1 = uid[3] ^ uid[4] ^ uid[5] ^ uid[6];
+        let mut image = vec![uid[0], uid[1], uid[2], bcc0, uid[3], uid[4], uid[5], uid[6]];
+        image.extend([bcc1, T2T_INTERNAL, 0x00, 0x00]);
+        image.extend(self.to_bytes()?);
+        let end = T2T_DATA_AREA_OFFSET + self.capacity_in_bytes() as usize;
+        image.resize(end.next_multiple_of(T2T_PAGE_SIZE), 0x00);
+        Ok(image)
+    }
+
+    /// Splits the image into 4-byte pages numbered from the CC page (page 3).
+    /// Pages lying entirely inside a reserved area are left out so they are
+    /// never written.
+    pub fn to_pages(&self) -> crate::Result<Vec<(u16, [u8; 4])>> {
+        let reserved = self.reserved_areas();
+        let bytes = self.to_bytes()?;
+        let first_page = (T2T_DATA_AREA_OFFSET / T2T_PAGE_SIZE) - 1;
+        let pages = bytes
+            .chunks(T2T_PAGE_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut page = [0u8; T2T_PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                ((first_page + index) as u16, page)
+            })
+            .filter(|(number, _)| {
+                let start = *number as usize * T2T_PAGE_SIZE;
+                !reserved
+                    .iter()
+                    .any(|r| r.start <= start && start + T2T_PAGE_SIZE <= r.end)
+            })
+            .collect();
+        Ok(pages)
+    }
+}
+
+const T2T_PAGE_SIZE: usize = 4;
+const T2T_MAGIC: u8 = 0xe1;
+const T2T_MAJOR_VERSION: u8 = 1;
+/// Byte address of page 3, the capability container.
+pub(crate) const T2T_CC_OFFSET: usize = 12;
+/// Byte address of page 4, where the T2T data area starts.
+const T2T_DATA_AREA_OFFSET: usize = 16;
+/// Internal byte of page 2 as programmed on NTAG21x chips.
+const T2T_INTERNAL: u8 = 0x48;
+/// Data area bytes covered by the static lock bits.
+const T2T_STATIC_AREA_SIZE: usize = 48;
+/// Largest data area a CC size byte describes, 0xFF 8-byte units.
+const T2T_MAX_CC_SIZE: usize = 2040;
+/// CC size byte of a data area above 2040 bytes, whose size then comes
+/// from the chip rather than the CC.
+const T2T_EXTENDED_SIZE: u8 = 0xff;
+const T2T_MAX_EXTENDED_SIZE: usize = 0xfff8;
+
+pub struct TagBuilder {
+    nfc_header: u8,
+    nfc_version: u8,
+    memory_size: u16,
+    access: (u8, u8),
+    tlvs: Vec<TlvValue>,
+    terminate: bool,
+}
+
+impl TagBuilder {
+    fn new() -> Self {
+        Self {
+            nfc_header: T2T_MAGIC,
+            nfc_version: 0x10,
+            memory_size: 0x00,
+            access: (0x0, 0xf),
+            tlvs: vec![],
+            terminate: false,
+        }
+    }
+
+    /// Data area size in bytes, rounded up to a multiple of 8. The CC can
+    /// describe 8 to 2040 bytes; larger areas, up to 65528 bytes, set the CC
+    /// size byte to 0xFF and keep the real size in the tag.
+    pub fn size_in_bytes(mut self, num_of_bytes: u16) -> Self {
+        self.memory_size = num_of_bytes.div_ceil(8);
+        self
+    }
+
+    pub fn size_in_8bytes(mut self, num_of_8bytes: u8) -> Self {
+        self.memory_size = num_of_8bytes as u16;
+        self
+    }
+
+    /// Read and write access conditions (one nibble each). T2T defines read
+    /// access 0x0 and write access 0x0 (granted) or 0xF (denied); 0x8-0xE
+    /// are proprietary and accepted as is.
+    pub fn access(mut self, read: u8, write: u8) -> Self {
+        self.access = (read, write);
+        self
+    }
+
+    pub fn add_tlv(mut self, value: TlvValue) -> Self {
+        self.tlvs.push(value);
+        self
+    }
+
+    /// Append a Terminator TLV after the last TLV when the data area has
+    /// room for it, unless the TLVs already end with one.
+    pub fn auto_terminator(mut self, terminate: bool) -> Self {
+        self.terminate = terminate;
+        self
+    }
+
+    pub fn build(self) -> crate::Result<NFT2Tag> {
+        let size = self.memory_size as usize * 8;
+        if size == 0 || size > T2T_MAX_EXTENDED_SIZE {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        let (read, write) = self.access;
+        if !is_valid_access(read, write) {
+            return Err(NdefError::InvalidTagData);
+        }
+        Ok(NFT2Tag {
+            cc: [
+                self.nfc_header,
+                self.nfc_version,
+                self.memory_size.min(T2T_EXTENDED_SIZE as u16) as u8,
+                (read << 4) | write,
+            ],
+            tlvs: self.tlvs,
+            extended_size: (size > T2T_MAX_CC_SIZE).then_some(size as u16),
+            terminate: self.terminate,
+        })
+    }
+}
+
+/// Checks the access condition nibbles shared by the Type 1 and Type 2 CC:
+/// read access must be granted, write access granted or denied, and the
+/// proprietary values 0x8-0xE are accepted for both.
+fn is_valid_access(read: u8, write: u8) -> bool {
+    let is_valid = |nibble: u8, granted_only: bool| match nibble {
+        0x0 => true,
+        0x8..=0xe => true,
+        0xf => !granted_only,
+        _ => false,
+    };
+    is_valid(read, true) && is_valid(write, false)
+}
+
+const T1T_BLOCK_SIZE: usize = 8;
+const T1T_UID_LEN: usize = 7;
+/// Byte address of the CC, the first four bytes of block 1.
+const T1T_CC_OFFSET: usize = 8;
+/// Byte address of the data area, right after the CC.
+const T1T_DATA_AREA_OFFSET: usize = 12;
+/// Blocks 0xD to 0xF hold the reserved, lock and OTP bytes in both the
+/// static and the dynamic memory layout.
+const T1T_RESERVED_BLOCKS: Range<usize> = 0x68..0x80;
+/// TMS of the static memory layout: 120 bytes, blocks 0x0 to 0xE.
+const T1T_STATIC_TMS: u8 = 0x0e;
+
+/// Type 1 tag (Topaz/Jewel) memory image: the UID block, the CC at the
+/// start of block 1 and the TLV area after it.
+pub struct Type1Tag {
+    uid: [u8; T1T_UID_LEN],
+    cc: [u8; 4],
+    tlvs: Vec<TlvValue>,
+    terminate: bool,
+}
+
+impl Type1Tag {
+    pub fn builder() -> Type1TagBuilder {
+        Type1TagBuilder::new()
+    }
+
+    /// Parses a memory image in the [`Type1Tag::to_bytes`] layout. TLVs are
+    /// read up to the Terminator TLV, skipping blocks 0xD to 0xF and the
+    /// bytes reserved by Lock and Memory Control TLVs, and every NDEF TLV is
+    /// decoded into the returned messages.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<(Self, Vec<NdefMessage>)> {
+        if data.len() < T1T_DATA_AREA_OFFSET {
+            return Err(NdefError::InvalidTagLength);
+        }
+        let mut uid = [0u8; T1T_UID_LEN];
+        uid.copy_from_slice(&data[..T1T_UID_LEN]);
+        let mut cc = [0u8; 4];
+        cc.copy_from_slice(&data[T1T_CC_OFFSET..T1T_DATA_AREA_OFFSET]);
+        if cc[0] != T2T_MAGIC {
+            return Err(NdefError::InvalidTag);
+        }
+        if cc[1] >> 4 != T2T_MAJOR_VERSION {
+            return Err(NdefError::InvalidTagVersion);
+        }
+        if cc[2] < T1T_STATIC_TMS {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        let tag = Self {
+            uid,
+            cc,
+            tlvs: vec![],
+            terminate: false,
+        };
+        let area = &data[T1T_DATA_AREA_OFFSET..data.len().min(tag.memory_size())];
+
+        let tlvs = read_tlvs(area, T1T_DATA_AREA_OFFSET, vec![T1T_RESERVED_BLOCKS])?;
+        let messages = ndef_messages(&tlvs)?;
+        Ok((Self { tlvs, ..tag }, messages))
+    }
+
+    pub fn uid(&self) -> [u8; T1T_UID_LEN] {
+        self.uid
+    }
+
+    pub fn tlvs(&self) -> &[TlvValue] {
+        &self.tlvs
+    }
+
+    pub fn version(&self) -> u8 {
+        self.cc[1]
+    }
+
+    /// Whether the tag uses the static memory layout (120 bytes).
+    pub fn is_static(&self) -> bool {
+        self.cc[2] == T1T_STATIC_TMS
+    }
+
+    /// Total tag memory in bytes, `8 * (TMS + 1)`.
+    pub fn memory_size(&self) -> usize {
+        (self.cc[2] as usize + 1) * T1T_BLOCK_SIZE
+    }
+
+    /// Memory regions the TLV area skips: blocks 0xD to 0xF, then the areas
+    /// of the Lock Control and Memory Control TLVs.
+    pub fn reserved_areas(&self) -> Vec<Range<usize>> {
+        std::iter::once(T1T_RESERVED_BLOCKS)
+            .chain(self.tlvs.iter().filter_map(|v| v.reserved_area()))
+            .collect()
+    }
+
+    /// The full memory image, [`Type1Tag::memory_size`] bytes long, with
+    /// reserved and unused bytes zeroed.
+    pub fn to_bytes(&self) -> crate::Result<Vec<u8>> {
+        let mut image = self.uid.to_vec();
+        image.push(0x00);
+        image.extend_from_slice(&self.cc);
+        let capacity = self.memory_size() - T1T_DATA_AREA_OFFSET;
+        image.extend(fit_tlvs(
+            &self.tlvs,
+            T1T_DATA_AREA_OFFSET,
+            &self.reserved_areas(),
+            capacity,
+            self.terminate,
+        )?);
+        image.resize(self.memory_size(), 0x00);
+        Ok(image)
+    }
+
+    /// Splits the image into 8-byte blocks numbered from block 0.
+    pub fn to_blocks(&self) -> crate::Result<Vec<(u8, [u8; T1T_BLOCK_SIZE])>> {
+        let blocks = self
+            .to_bytes()?
+            .chunks(T1T_BLOCK_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| (index as u8, chunk.try_into().unwrap()))
+            .collect();
+        Ok(blocks)
+    }
+}
+
+pub struct Type1TagBuilder {
+    uid: [u8; T1T_UID_LEN],
+    memory_size: u16,
+    access: (u8, u8),
+    tlvs: Vec<TlvValue>,
+    terminate: bool,
 }
 
-impl TlvValue {
-    pub fn terminator() -> Self {
-        Self {
-            tag: TlvTag::Terminator,
-            value: None,
+impl Type1TagBuilder {
+    fn new() -> Self {
+        Self {
+            uid: [0; T1T_UID_LEN],
+            memory_size: T1T_STATIC_TMS as u16,
+            access: (0x0, 0x0),
+            tlvs: vec![],
+            terminate: false,
+        }
+    }
+
+    pub fn uid(mut self, uid: [u8; T1T_UID_LEN]) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    /// Total memory size in bytes, rounded up to a multiple of 8. Defaults to
+    /// the 120 bytes of the static layout; larger sizes use the dynamic
+    /// layout, up to 2048 bytes.
+    pub fn size_in_bytes(mut self, num_of_bytes: u16) -> Self {
+        self.memory_size = num_of_bytes.div_ceil(8).saturating_sub(1);
+        self
+    }
+
+    /// Read and write access conditions (one nibble each), as for
+    /// [`TagBuilder::access`].
+    pub fn access(mut self, read: u8, write: u8) -> Self {
+        self.access = (read, write);
+        self
+    }
+
+    pub fn add_tlv(mut self, value: TlvValue) -> Self {
+        self.tlvs.push(value);
+        self
+    }
+
+    /// Append a Terminator TLV after the last TLV when the data area has
+    /// room for it, unless the TLVs already end with one.
+    pub fn auto_terminator(mut self, terminate: bool) -> Self {
+        self.terminate = terminate;
+        self
+    }
+
+    pub fn build(self) -> crate::Result<Type1Tag> {
+        if self.memory_size < T1T_STATIC_TMS as u16 || self.memory_size > 0xff {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        let (read, write) = self.access;
+        if !is_valid_access(read, write) {
+            return Err(NdefError::InvalidTagData);
+        }
+        Ok(Type1Tag {
+            uid: self.uid,
+            cc: [T2T_MAGIC, 0x10, self.memory_size as u8, (read << 4) | write],
+            tlvs: self.tlvs,
+            terminate: self.terminate,
+        })
+    }
+}
+
+const T3T_BLOCK_SIZE: usize = 16;
+const T3T_MAPPING_VERSION: u8 = 0x10;
+const T3T_WRITE_DONE: u8 = 0x00;
+const T3T_WRITE_IN_PROGRESS: u8 = 0x0f;
+
+/// Attribute information block of a Type 3 (FeliCa) tag, block 0 of the
+/// NDEF system.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct T3tAttributes {
+    pub version: u8,
+    /// Blocks readable with one Check command.
+    pub nbr: u8,
+    /// Blocks writable with one Update command.
+    pub nbw: u8,
+    /// Number of blocks available for NDEF data.
+    pub nmaxb: u16,
+    /// WriteF: set while an NDEF write is in progress.
+    pub writing: bool,
+    /// RWFlag: the NDEF data may be updated.
+    pub writable: bool,
+    /// Ln: length of the stored NDEF message.
+    pub ln: u32,
+}
+
+impl T3tAttributes {
+    /// Parses the block, checking its checksum.
+    pub fn from_bytes(block: &[u8; T3T_BLOCK_SIZE]) -> crate::Result<Self> {
+        let checksum = block[..14].iter().map(|&b| b as u16).sum::<u16>();
+        if checksum.to_be_bytes() != block[14..] {
+            return Err(NdefError::InvalidTagData);
+        }
+        let writing = match block[9] {
+            T3T_WRITE_DONE => false,
+            T3T_WRITE_IN_PROGRESS => true,
+            _ => return Err(NdefError::InvalidTagData),
+        };
+        let writable = match block[10] {
+            0x00 => false,
+            0x01 => true,
+            _ => return Err(NdefError::InvalidTagData),
+        };
+        Ok(Self {
+            version: block[0],
+            nbr: block[1],
+            nbw: block[2],
+            nmaxb: u16::from_be_bytes([block[3], block[4]]),
+            writing,
+            writable,
+            ln: u32::from_be_bytes([0, block[11], block[12], block[13]]),
+        })
+    }
+
+    /// Encodes the block, with the checksum over bytes 0 to 13.
+    pub fn to_bytes(&self) -> [u8; T3T_BLOCK_SIZE] {
+        let mut block = [0u8; T3T_BLOCK_SIZE];
+        block[0] = self.version;
+        block[1] = self.nbr;
+        block[2] = self.nbw;
+        block[3..5].copy_from_slice(&self.nmaxb.to_be_bytes());
+        block[9] = if self.writing { T3T_WRITE_IN_PROGRESS } else { T3T_WRITE_DONE };
+        block[10] = self.writable as u8;
+        block[11..14].copy_from_slice(&self.ln.to_be_bytes()[1..]);
+        let checksum = block[..14].iter().map(|&b| b as u16).sum::<u16>();
+        block[14..].copy_from_slice(&checksum.to_be_bytes());
+        block
+    }
+}
+
+/// Type 3 tag (FeliCa) NDEF system image: the attribute information block
+/// followed by the NDEF message, with no TLV framing.
+pub struct Type3Tag {
+    attributes: T3tAttributes,
+    message: Vec<u8>,
+}
+
+impl Type3Tag {
+    pub fn builder() -> Type3TagBuilder {
+        Type3TagBuilder::new()
+    }
+
+    /// Builder with the block limits and size of a Type 3 `chip`.
+    pub fn builder_for(chip: ChipModel) -> crate::Result<Type3TagBuilder> {
+        if chip.tag_type() != TagType::Type3 {
+            return Err(NdefError::InvalidTagType);
+        }
+        Ok(Type3TagBuilder::new().max_blocks((chip.data_area_size() / T3T_BLOCK_SIZE) as u16))
+    }
+
+    /// Parses an image in the [`Type3Tag::to_bytes`] layout, returning the
+    /// stored message, if any. Images caught in the middle of a write are
+    /// rejected.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<(Self, Option<NdefMessage>)> {
+        let block = data
+            .get(..T3T_BLOCK_SIZE)
+            .and_then(|block| block.try_into().ok())
+            .ok_or(NdefError::InvalidTagLength)?;
+        let attributes = T3tAttributes::from_bytes(block)?;
+        if attributes.version >> 4 != T3T_MAPPING_VERSION >> 4 {
+            return Err(NdefError::InvalidTagVersion);
+        }
+        if attributes.writing {
+            return Err(NdefError::InvalidTagData);
+        }
+        let len = attributes.ln as usize;
+        if len > attributes.nmaxb as usize * T3T_BLOCK_SIZE {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        let message = data
+            .get(T3T_BLOCK_SIZE..T3T_BLOCK_SIZE + len)
+            .ok_or(NdefError::InvalidTagLength)?
+            .to_vec();
+        let decoded = match message.is_empty() {
+            true => None,
+            false => Some(NdefMessage::decode(&message)?),
+        };
+        Ok((Self { attributes, message }, decoded))
+    }
+
+    pub fn attributes(&self) -> &T3tAttributes {
+        &self.attributes
+    }
+
+    /// The attribute block followed by the message, zero padded to whole
+    /// blocks.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut image = self.attributes.to_bytes().to_vec();
+        image.extend_from_slice(&self.message);
+        image.resize(image.len().next_multiple_of(T3T_BLOCK_SIZE), 0x00);
+        image
+    }
+
+    /// Splits the image into 16-byte blocks numbered from the attribute
+    /// block (block 0).
+    pub fn to_blocks(&self) -> Vec<(u16, [u8; T3T_BLOCK_SIZE])> {
+        self.to_bytes()
+            .chunks(T3T_BLOCK_SIZE)
+            .enumerate()
+            .map(|(index, chunk)| (index as u16, chunk.try_into().unwrap()))
+            .collect()
+    }
+}
+
+pub struct Type3TagBuilder {
+    nbr: u8,
+    nbw: u8,
+    nmaxb: u16,
+    writable: bool,
+    message: Vec<u8>,
+}
+
+impl Type3TagBuilder {
+    fn new() -> Self {
+        Self {
+            nbr: 4,
+            nbw: 1,
+            nmaxb: 0,
+            writable: true,
+            message: vec![],
+        }
+    }
+
+    /// Blocks per Check command; FeliCa Lite-S reads 4.
+    pub fn nbr(mut self, nbr: u8) -> Self {
+        self.nbr = nbr;
+        self
+    }
+
+    /// Blocks per Update command; FeliCa Lite-S writes 1.
+    pub fn nbw(mut self, nbw: u8) -> Self {
+        self.nbw = nbw;
+        self
+    }
+
+    /// Blocks available for NDEF data after the attribute block.
+    pub fn max_blocks(mut self, nmaxb: u16) -> Self {
+        self.nmaxb = nmaxb;
+        self
+    }
+
+    pub fn writable(mut self, writable: bool) -> Self {
+        self.writable = writable;
+        self
+    }
+
+    pub fn message(mut self, message: &NdefMessage) -> crate::Result<Self> {
+        self.message = message.to_buffer()?;
+        Ok(self)
+    }
+
+    pub fn build(self) -> crate::Result<Type3Tag> {
+        if self.nbr == 0 || self.nbw == 0 {
+            return Err(NdefError::InvalidTagData);
+        }
+        if self.nmaxb == 0 || self.message.len() > self.nmaxb as usize * T3T_BLOCK_SIZE {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        Ok(Type3Tag {
+            attributes: T3tAttributes {
+                version: T3T_MAPPING_VERSION,
+                nbr: self.nbr,
+                nbw: self.nbw,
+                nmaxb: self.nmaxb,
+                writing: false,
+                writable: self.writable,
+                ln: self.message.len() as u32,
+            },
+            message: self.message,
+        })
+    }
+}
+
+const T4T_CC_LEN: usize = 15;
+const T4T_MAPPING_VERSION: u8 = 0x20;
+const T4T_NDEF_FILE_CONTROL_TLV: u8 = 0x04;
+/// File ID of the NDEF file unless the builder is told otherwise.
+const T4T_DEFAULT_NDEF_FILE_ID: [u8; 2] = [0xe1, 0x04];
+
+/// Capability container file of a Type 4 tag, with the NDEF File Control
+/// TLV as its only TLV.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct T4tCapabilityContainer {
+    /// Mapping version; 0x20 for version 2.0.
+    pub version: u8,
+    /// Largest R-APDU data size (MLe) for READ BINARY.
+    pub mle: u16,
+    /// Largest C-APDU data size (MLc) for UPDATE BINARY.
+    pub mlc: u16,
+    pub file_id: [u8; 2],
+    /// Size of the NDEF file, NLEN included.
+    pub max_ndef_size: u16,
+    /// 0x00 grants read access.
+    pub read_access: u8,
+    /// 0x00 grants write access, 0xFF denies it.
+    pub write_access: u8,
+}
+
+impl T4tCapabilityContainer {
+    /// Parses the first 15 bytes of the CC file; CCLEN may announce more
+    /// TLVs, which are ignored.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<Self> {
+        if data.len() < T4T_CC_LEN
+            || (u16::from_be_bytes([data[0], data[1]]) as usize) < T4T_CC_LEN
+            || data[7] != T4T_NDEF_FILE_CONTROL_TLV
+            || data[8] != 0x06
+        {
+            return Err(NdefError::InvalidTagData);
+        }
+        Ok(Self {
+            version: data[2],
+            mle: u16::from_be_bytes([data[3], data[4]]),
+            mlc: u16::from_be_bytes([data[5], data[6]]),
+            file_id: [data[9], data[10]],
+            max_ndef_size: u16::from_be_bytes([data[11], data[12]]),
+            read_access: data[13],
+            write_access: data[14],
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; T4T_CC_LEN] {
+        let mut cc = [0u8; T4T_CC_LEN];
+        cc[..2].copy_from_slice(&(T4T_CC_LEN as u16).to_be_bytes());
+        cc[2] = self.version;
+        cc[3..5].copy_from_slice(&self.mle.to_be_bytes());
+        cc[5..7].copy_from_slice(&self.mlc.to_be_bytes());
+        cc[7] = T4T_NDEF_FILE_CONTROL_TLV;
+        cc[8] = 0x06;
+        cc[9..11].copy_from_slice(&self.file_id);
+        cc[11..13].copy_from_slice(&self.max_ndef_size.to_be_bytes());
+        cc[13] = self.read_access;
+        cc[14] = self.write_access;
+        cc
+    }
+}
+
+/// Type 4 tag NDEF application contents: the CC file and the NDEF file.
+pub struct Type4Tag {
+    cc: T4tCapabilityContainer,
+    message: Vec<u8>,
+}
+
+impl Type4Tag {
+    pub fn builder() -> Type4TagBuilder {
+        Type4TagBuilder::new()
+    }
+
+    /// Builder with the NDEF file size of a Type 4 `chip`.
+    pub fn builder_for(chip: ChipModel) -> crate::Result<Type4TagBuilder> {
+        if chip.tag_type() != TagType::Type4 {
+            return Err(NdefError::InvalidTagType);
         }
+        Ok(Type4TagBuilder::new().max_ndef_size(chip.data_area_size() as u16))
     }
 
-    pub fn null() -> Self {
-        Self {
-            tag: TlvTag::NULL,
-            value: None,
+    /// Parses the CC file and the NDEF file as read from a tag, returning
+    /// the stored message, if any. The NDEF file may be longer than NLEN.
+    pub fn from_files(cc: &[u8], ndef: &[u8]) -> crate::Result<(Self, Option<NdefMessage>)> {
+        let cc = T4tCapabilityContainer::from_bytes(cc)?;
+        if cc.version >> 4 != T4T_MAPPING_VERSION >> 4 {
+            return Err(NdefError::InvalidTagVersion);
+        }
+        let len = match ndef {
+            [high, low, ..] => u16::from_be_bytes([*high, *low]) as usize,
+            _ => return Err(NdefError::InvalidTagLength),
+        };
+        if len + 2 > cc.max_ndef_size as usize {
+            return Err(NdefError::InvalidTagMemorySize);
         }
+        let message = ndef.get(2..2 + len).ok_or(NdefError::InvalidTagLength)?.to_vec();
+        let decoded = match message.is_empty() {
+            true => None,
+            false => Some(NdefMessage::decode(&message)?),
+        };
+        Ok((Self { cc, message }, decoded))
     }
 
-    pub fn lock_control(value: &[u8]) -> Self {
-        Self {
-            tag: TlvTag::LockControl,
-            value: Some(value.to_vec()),
-        }
+    pub fn capability_container(&self) -> &T4tCapabilityContainer {
+        &self.cc
     }
 
-    pub fn memory_control(value: &[u8]) -> Self {
+    pub fn cc_file(&self) -> [u8; T4T_CC_LEN] {
+        self.cc.to_bytes()
+    }
+
+    /// The NDEF file: NLEN as a big-endian `u16`, then the message.
+    pub fn ndef_file(&self) -> Vec<u8> {
+        let mut file = (self.message.len() as u16).to_be_bytes().to_vec();
+        file.extend_from_slice(&self.message);
+        file
+    }
+}
+
+pub struct Type4TagBuilder {
+    mle: u16,
+    mlc: u16,
+    file_id: [u8; 2],
+    max_ndef_size: u16,
+    access: (u8, u8),
+    message: Vec<u8>,
+}
+
+impl Type4TagBuilder {
+    fn new() -> Self {
         Self {
-            tag: TlvTag::MemoryControl,
-            value: Some(value.to_vec()),
+            mle: 0x00ff,
+            mlc: 0x00ff,
+            file_id: T4T_DEFAULT_NDEF_FILE_ID,
+            max_ndef_size: 0,
+            access: (0x00, 0x00),
+            message: vec![],
         }
     }
 
-    pub fn message(value: &[u8]) -> Self {
-        let value = if value.is_empty() {
-            Some(vec![])
-        } else {
-            Some(value.to_vec())
-        };
+    /// Largest READ BINARY response data, at least 15 bytes.
+    pub fn mle(mut self, mle: u16) -> Self {
+        self.mle = mle;
+        self
+    }
 
-        Self {
-            tag: TlvTag::NDEFMessage,
-            value,
+    /// Largest UPDATE BINARY command data, at least 1 byte.
+    pub fn mlc(mut self, mlc: u16) -> Self {
+        self.mlc = mlc;
+        self
+    }
+
+    pub fn file_id(mut self, file_id: [u8; 2]) -> Self {
+        self.file_id = file_id;
+        self
+    }
+
+    /// NDEF file size including the 2-byte NLEN, 5 to 0xFFFE bytes.
+    pub fn max_ndef_size(mut self, size: u16) -> Self {
+        self.max_ndef_size = size;
+        self
+    }
+
+    /// Read and write access bytes: 0x00 grants access, 0xFF denies write
+    /// access, and 0x80-0xFE are proprietary and accepted as is.
+    pub fn access(mut self, read: u8, write: u8) -> Self {
+        self.access = (read, write);
+        self
+    }
+
+    pub fn message(mut self, message: &NdefMessage) -> crate::Result<Self> {
+        self.message = message.to_buffer()?;
+        Ok(self)
+    }
+
+    pub fn build(self) -> crate::Result<Type4Tag> {
+        if self.mle < T4T_CC_LEN as u16 || self.mlc == 0 {
+            return Err(NdefError::InvalidTagData);
         }
+        // Reserved by ISO 7816-4 or the NFC Forum mapping.
+        if matches!(
+            self.file_id,
+            [0x00, 0x00] | [0xe1, 0x02] | [0xe1, 0x03] | [0x3f, 0x00] | [0x3f, 0xff] | [0xff, 0xff]
+        ) {
+            return Err(NdefError::InvalidTagData);
+        }
+        let size = self.max_ndef_size as usize;
+        if !(5..=0xfffe).contains(&size) || self.message.len() + 2 > size {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        let (read, write) = self.access;
+        if !matches!(read, 0x00 | 0x80..=0xfe) || !matches!(write, 0x00 | 0x80..=0xff) {
+            return Err(NdefError::InvalidTagData);
+        }
+        Ok(Type4Tag {
+            cc: T4tCapabilityContainer {
+                version: T4T_MAPPING_VERSION,
+                mle: self.mle,
+                mlc: self.mlc,
+                file_id: self.file_id,
+                max_ndef_size: self.max_ndef_size,
+                read_access: read,
+                write_access: write,
+            },
+            message: self.message,
+        })
     }
+}
 
-    pub fn ndef_message(message: &NdefMessage) -> Result<Self> {
-        let value = message.to_buffer()?;
+const T5T_MAGIC: u8 = 0xe1;
+/// Magic of a CC announcing that blocks need 2-byte addresses.
+const T5T_MAGIC_EXTENDED: u8 = 0xe2;
+const T5T_MAJOR_VERSION: u8 = 1;
+
+bitflags! {
+    /// Feature byte of a Type 5 tag CC.
+    #[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct T5tFeatures: u8 {
+        /// READ MULTIPLE BLOCKS is supported.
+        const MULTIPLE_BLOCK_READ = 0b0000_0001;
+        /// LOCK SINGLE BLOCK is supported.
+        const LOCK_BLOCK = 0b0000_1000;
+        /// Writes and locks need the option flag (special frame).
+        const SPECIAL_FRAME = 0b0001_0000;
+    }
+}
+
+/// Capability container of a Type 5 tag, in the 4-byte form or, when the
+/// data area exceeds 2040 bytes, the 8-byte form with a 16-bit MLEN.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct T5tCapabilityContainer {
+    /// Blocks need 2-byte addresses (magic 0xE2 instead of 0xE1).
+    pub extended_addressing: bool,
+    /// Major and minor mapping version, one nibble each.
+    pub version: u8,
+    /// Read access: 0b00 granted, 0b10 proprietary.
+    pub read_access: u8,
+    /// Write access: 0b00 granted, 0b10 proprietary, 0b11 denied.
+    pub write_access: u8,
+    /// Size of the memory in 8-byte units, the CC included, as vendor
+    /// datasheets give it.
+    pub mlen: u16,
+    pub features: T5tFeatures,
+    /// Use the 8-byte form even if MLEN fits one byte.
+    pub long_form: bool,
+}
+
+impl T5tCapabilityContainer {
+    /// Parses a 4-byte CC, or an 8-byte one when byte 2 is zero.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<Self> {
+        if data.len() < 4 {
+            return Err(NdefError::InvalidTagLength);
+        }
+        let extended_addressing = match data[0] {
+            T5T_MAGIC => false,
+            T5T_MAGIC_EXTENDED => true,
+            _ => return Err(NdefError::InvalidTag),
+        };
+        let long_form = data[2] == 0;
+        let mlen = match long_form {
+            false => data[2] as u16,
+            true => {
+                let mlen = data.get(6..8).ok_or(NdefError::InvalidTagLength)?;
+                u16::from_be_bytes([mlen[0], mlen[1]])
+            }
+        };
         Ok(Self {
-            tag: TlvTag::NDEFMessage,
-            value: Some(value),
+            extended_addressing,
+            version: ((data[1] >> 6) << 4) | ((data[1] >> 4) & 0x03),
+            read_access: (data[1] >> 2) & 0x03,
+            write_access: data[1] & 0x03,
+            mlen,
+            features: T5tFeatures::from_bits_retain(data[3]),
+            long_form,
         })
     }
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let buffer = vec![self.tag as u8];
-        let mut writer = Cursor::new(buffer);
-        writer.write_u8(self.tag as u8).unwrap();
-        if let Some(value) = &self.value {
-            if value.is_empty() {
-                writer.write_u8(0x00).unwrap();
-            } else if value.len() < 0xff {
-                writer.write_u8(value.len() as u8).unwrap();
-                writer.write_all(value).unwrap();
-            } else {
-                writer.write_u8(0xff).unwrap();
-                writer
-                    .write_u16::<LittleEndian>(value.len() as u16)
-                    .unwrap();
-                writer.write_all(value).unwrap();
+    /// 4 or 8 bytes, see [`T5tCapabilityContainer::len`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let magic = if self.extended_addressing { T5T_MAGIC_EXTENDED } else { T5T_MAGIC };
+        let access = ((self.version >> 4) << 6)
+            | ((self.version & 0x03) << 4)
+            | ((self.read_access & 0x03) << 2)
+            | (self.write_access & 0x03);
+        match self.len() {
+            4 => vec![magic, access, self.mlen as u8, self.features.bits()],
+            _ => {
+                let [high, low] = self.mlen.to_be_bytes();
+                vec![magic, access, 0x00, self.features.bits(), 0x00, 0x00, high, low]
             }
         }
-        writer.into_inner()
+    }
+
+    /// 8 when the long form is requested or MLEN does not fit one byte.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        if self.long_form || self.mlen > 0xff {
+            8
+        } else {
+            4
+        }
+    }
+
+    /// Bytes after the CC that MLEN leaves for the TLV area.
+    pub fn data_area_size(&self) -> usize {
+        (self.mlen as usize * 8).saturating_sub(self.len())
     }
 }
 
-pub struct NFT2Tag {
-    cc: [u8; 4],
+/// Type 5 tag (ISO 15693) memory image: the CC at the start of block 0,
+/// then the TLV area.
+pub struct Type5Tag {
+    cc: T5tCapabilityContainer,
     tlvs: Vec<TlvValue>,
+    terminate: bool,
 }
 
-impl NFT2Tag {
-    pub fn builder() -> TagBuilder {
-        TagBuilder::new()
+impl Type5Tag {
+    pub fn builder() -> Type5TagBuilder {
+        Type5TagBuilder::new()
     }
 
-    pub fn capacity_in_bytes(&self) -> u16 {
-        self.cc[2] as u16 * 8
+    /// Builder sized for the user memory of a Type 5 `chip`.
+    pub fn builder_for(chip: ChipModel) -> crate::Result<Type5TagBuilder> {
+        if chip.tag_type() != TagType::Type5 {
+            return Err(NdefError::InvalidTagType);
+        }
+        Ok(Type5TagBuilder::new().size_in_bytes(chip.data_area_size()))
     }
 
-    pub fn capacity(&self) -> u8 {
-        self.cc[2]
+    /// ST25TV512: 4-byte blocks, 60 byte data area.
+    pub fn st25tv512() -> Type5TagBuilder {
+        Self::preset(ChipModel::St25tv512)
     }
 
-    pub fn version(&self) -> u8 {
-        self.cc[1]
+    /// ST25TV02K: 4-byte blocks, 252 byte data area.
+    pub fn st25tv02k() -> Type5TagBuilder {
+        Self::preset(ChipModel::St25tv02k)
+    }
+
+    /// ST25DV64K: 2048 4-byte blocks, so the 8-byte CC with 2-byte block
+    /// addresses.
+    pub fn st25dv64k() -> Type5TagBuilder {
+        Self::preset(ChipModel::St25dv64k)
+    }
+
+    /// ICODE SLIX: 28 4-byte blocks, 108 byte data area.
+    pub fn icode_slix() -> Type5TagBuilder {
+        Self::preset(ChipModel::IcodeSlix)
+    }
+
+    /// ICODE SLIX2: 80 4-byte blocks, 316 byte data area.
+    pub fn icode_slix2() -> Type5TagBuilder {
+        Self::preset(ChipModel::IcodeSlix2)
+    }
+
+    /// Builder sized for `chip`, announcing READ MULTIPLE BLOCKS, with
+    /// 2-byte block addresses when the chip has more than 256 blocks.
+    fn preset(chip: ChipModel) -> Type5TagBuilder {
+        let block_size = chip.page_size().unwrap_or(4);
+        let blocks = (chip.data_area_size() + 8).div_ceil(block_size);
+        Type5TagBuilder::new()
+            .size_in_bytes(chip.data_area_size())
+            .features(T5tFeatures::MULTIPLE_BLOCK_READ)
+            .extended_addressing(blocks > 256)
     }
 
-    pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        if self.capacity_in_bytes() > 2048 {
-            return Err(anyhow::anyhow!("Invalid memory size"));
+    /// Parses an image in the [`Type5Tag::to_bytes`] layout. TLVs are read up
+    /// to the Terminator TLV, skipping bytes reserved by Lock and Memory
+    /// Control TLVs, and every NDEF TLV is decoded into the returned messages.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<(Self, Vec<NdefMessage>)> {
+        let cc = T5tCapabilityContainer::from_bytes(data)?;
+        if cc.version >> 4 != T5T_MAJOR_VERSION {
+            return Err(NdefError::InvalidTagVersion);
+        }
+        if cc.mlen == 0 {
+            return Err(NdefError::InvalidTagMemorySize);
         }
+        let area = &data[cc.len()..data.len().min(cc.len() + cc.data_area_size())];
+        let tlvs = read_tlvs(area, cc.len(), vec![])?;
+        let messages = ndef_messages(&tlvs)?;
+        let tag = Self {
+            cc,
+            tlvs,
+            terminate: false,
+        };
+        Ok((tag, messages))
+    }
+
+    pub fn capability_container(&self) -> &T5tCapabilityContainer {
+        &self.cc
+    }
+
+    pub fn tlvs(&self) -> &[TlvValue] {
+        &self.tlvs
+    }
+
+    /// Memory regions reserved by Lock Control and Memory Control TLVs, as
+    /// byte addresses counted from the start of block 0.
+    pub fn reserved_areas(&self) -> Vec<Range<usize>> {
+        self.tlvs.iter().filter_map(|v| v.reserved_area()).collect()
+    }
 
-        let buffer = self
-            .tlvs
-            .iter()
-            .flat_map(|v| v.to_bytes())
-            .collect::<Vec<_>>();
-        if self.capacity_in_bytes() < (buffer.len() as u16) {
-            return Err(anyhow::anyhow!("Invalid memory size"));
+    /// The CC followed by the TLV area, which starts in the block holding
+    /// the end of the CC.
+    pub fn to_bytes(&self) -> crate::Result<Vec<u8>> {
+        let mut image = self.cc.to_bytes();
+        let capacity = self.cc.data_area_size();
+        let area = fit_tlvs(&self.tlvs, image.len(), &self.reserved_areas(), capacity, self.terminate)?;
+        image.extend(area);
+        Ok(image)
+    }
+
+    /// Splits the image into blocks of `block_size` bytes (4 on most ICs,
+    /// up to 32), numbered from block 0.
+    pub fn to_blocks(&self, block_size: usize) -> crate::Result<Vec<(u16, Vec<u8>)>> {
+        if !(1..=32).contains(&block_size) {
+            return Err(NdefError::InvalidTagData);
         }
-        let header = self.cc.to_vec();
-        Ok([header, buffer].concat())
+        let blocks = self
+            .to_bytes()?
+            .chunks(block_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut block = chunk.to_vec();
+                block.resize(block_size, 0x00);
+                (index as u16, block)
+            })
+            .collect();
+        Ok(blocks)
     }
 }
 
-pub struct TagBuilder {
-    nfc_header: u8,
-    nfc_version: u8,
-    memory_size: u8,
-    access: u8,
+pub struct Type5TagBuilder {
+    size: usize,
+    access: (u8, u8),
+    features: T5tFeatures,
+    extended_addressing: bool,
+    long_form: bool,
     tlvs: Vec<TlvValue>,
+    terminate: bool,
 }
 
-impl TagBuilder {
+impl Type5TagBuilder {
     fn new() -> Self {
         Self {
-            nfc_header: 0xe1,
-            nfc_version: 0x10,
-            memory_size: 0x00,
-            access: 0x0f,
+            size: 0,
+            access: (0b00, 0b00),
+            features: T5tFeatures::empty(),
+            extended_addressing: false,
+            long_form: false,
             tlvs: vec![],
+            terminate: false,
         }
     }
 
-    pub fn size_in_bytes(mut self, num_of_bytes: u16) -> Self {
-        let n = (num_of_bytes - 1) / 8;
-        self.memory_size = n as u8 + 1;
+    /// Data area size in bytes, after the CC. MLEN covers the CC too, so
+    /// the two together are rounded down to a multiple of 8; memories above
+    /// 2040 bytes switch to the 8-byte CC.
+    pub fn size_in_bytes(mut self, num_of_bytes: usize) -> Self {
+        self.size = num_of_bytes;
         self
     }
 
-    pub fn size_in_8bytes(mut self, num_of_8bytes: u8) -> Self {
-        self.memory_size = num_of_8bytes;
+    /// Read and write access conditions (two bits each).
+    pub fn access(mut self, read: u8, write: u8) -> Self {
+        self.access = (read, write);
         self
     }
 
-    pub fn access(mut self, read: u8, write: u8) -> Self {
-        self.access = (read << 4) | write;
+    pub fn features(mut self, features: T5tFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Announce 2-byte block addresses (CC magic 0xE2), for ICs with more
+    /// than 256 blocks.
+    pub fn extended_addressing(mut self, extended: bool) -> Self {
+        self.extended_addressing = extended;
+        self
+    }
+
+    /// Use the 8-byte CC even for small data areas.
+    pub fn long_form(mut self, long_form: bool) -> Self {
+        self.long_form = long_form;
         self
     }
 
@@ -172,16 +1801,36 @@ impl TagBuilder {
         self
     }
 
-    pub fn build(self) -> NFT2Tag {
-        NFT2Tag {
-            cc: [
-                self.nfc_header,
-                self.nfc_version,
-                self.memory_size,
-                self.access,
-            ],
-            tlvs: self.tlvs,
+    /// Append a Terminator TLV after the last TLV when the data area has
+    /// room for it, unless the TLVs already end with one.
+    pub fn auto_terminator(mut self, terminate: bool) -> Self {
+        self.terminate = terminate;
+        self
+    }
+
+    pub fn build(self) -> crate::Result<Type5Tag> {
+        let cc_len = if self.long_form || (self.size + 4) / 8 > 0xff { 8 } else { 4 };
+        let mlen = ((self.size + cc_len) / 8).min(0xffff) as u16;
+        if self.size == 0 || mlen == 0 {
+            return Err(NdefError::InvalidTagMemorySize);
+        }
+        let (read, write) = self.access;
+        if !matches!(read, 0b00 | 0b10) || !matches!(write, 0b00 | 0b10 | 0b11) {
+            return Err(NdefError::InvalidTagData);
         }
+        Ok(Type5Tag {
+            cc: T5tCapabilityContainer {
+                extended_addressing: self.extended_addressing,
+                version: T5T_MAJOR_VERSION << 4,
+                read_access: read,
+                write_access: write,
+                mlen,
+                features: self.features,
+                long_form: self.long_form,
+            },
+            tlvs: self.tlvs,
+            terminate: self.terminate,
+        })
     }
 }
 
@@ -191,44 +1840,570 @@ mod tests {
 
     #[test]
     fn test_tlv_value() {
-        let tlv = TlvValue::lock_control(&[0x00, 0x00, 0x00, 0x00]);
-        assert_eq!(tlv.tag, TlvTag::LockControl);
-        assert_eq!(tlv.value, Some(vec![0x00, 0x00, 0x00, 0x00]));
+        let lock = LockControl::from_bytes(&[0xa0, 0x10, 0x44]).unwrap();
+        assert_eq!(16, lock.size_bits);
+        assert_eq!(160..162, lock.reserved_area());
+        assert_eq!([0xa0, 0x10, 0x44], lock.to_bytes());
+        assert_eq!(
+            Err(TlvError::InvalidLength {
+                tag: 0x01,
+                len: 4,
+                expected: 3
+            }),
+            TlvValue::parse(0x01, &[0x00, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(
+            Ok(TlvValue::Other {
+                tag: 0x42,
+                value: vec![]
+            }),
+            TlvValue::parse(0x42, &[])
+        );
+        assert!(TlvValue::parse(0xfe, &[0x00]).is_err());
+
+        let memory = TlvValue::parse(0x02, &[0x80, 0x00, 0x02]).unwrap();
+        assert_eq!(TlvTag::MemoryControl, memory.tag());
+        assert_eq!(Some(32..288), memory.reserved_area());
+
+        let tlv = TlvValue::parse(0x03, &[]).unwrap();
+        assert_eq!(TlvValue::Ndef(vec![]), tlv);
+        assert_eq!(vec![0x03, 0x00], tlv.to_bytes().unwrap());
 
-        let tlv = TlvValue::memory_control(&[0x00, 0x00, 0x00, 0x00]);
-        assert_eq!(tlv.tag, TlvTag::MemoryControl);
-        assert_eq!(tlv.value, Some(vec![0x00, 0x00, 0x00, 0x00]));
+        assert_eq!(vec![0xfe], TlvValue::Terminator.to_bytes().unwrap());
+        assert!(TlvValue::Proprietary(vec![0; 0x10000]).to_bytes().is_err());
+    }
+
+    #[test]
+    fn test_lock_control() {
+        // NTAG213: 12 dynamic lock bits at page 0x28, 2 pages per bit.
+        let lock = TlvValue::lock_control(0x28 * 4, 12, 8).unwrap();
+        assert_eq!(vec![0x01, 0x03, 0xa0, 0x0c, 0x34], lock.to_bytes().unwrap());
+        assert_eq!(Some(160..162), lock.reserved_area());
+        // NTAG216: page 0xe2 needs 64-byte pages and an offset.
+        let lock = LockControl::new(0xe2 * 4, 37, 64).unwrap();
+        assert_eq!((0xe, 0x8, 6), (lock.page_address, lock.byte_offset, lock.page_size_exp));
+        assert_eq!(0x388..0x38d, lock.reserved_area());
+        assert_eq!(lock, LockControl::from_bytes(&lock.to_bytes()).unwrap());
+
+        assert!(LockControl::new(0x80000, 8, 8).is_err());
+        assert!(LockControl::new(160, 0, 8).is_err());
+        assert!(LockControl::new(160, 8, 12).is_err());
+    }
+
+    #[test]
+    fn test_memory_control() {
+        let tlv = TlvValue::memory_control(32, 4, 4).unwrap();
+        assert_eq!(TlvValue::parse(0x02, &[0x80, 0x04, 0x02]).unwrap(), tlv);
+        let memory = MemoryControl::new(0x64, 256, 8).unwrap();
+        assert_eq!((0xc, 0x4, 0x00), (memory.page_address, memory.byte_offset, memory.to_bytes()[1]));
+        assert_eq!((0x64, 8), (memory.byte_address(), memory.bytes_per_page()));
+        assert_eq!(0x64..0x164, memory.reserved_area());
+        assert!(MemoryControl::new(0x64, 4, 4).is_err());
+        assert!(MemoryControl::new(32, 257, 4).is_err());
 
-        let tlv = TlvValue::message(&[0x00, 0x00, 0x00, 0x00]);
-        assert_eq!(tlv.tag, TlvTag::NDEFMessage);
-        assert_eq!(tlv.value, Some(vec![0x00, 0x00, 0x00, 0x00]));
+        // The reserved bytes are skipped when laying out and reading TLVs.
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(48)
+            .add_tlv(TlvValue::memory_control(24, 8, 4).unwrap())
+            .add_tlv(TlvValue::Proprietary(vec![0x11; 4]))
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let bytes = tag.to_bytes().unwrap();
+        assert_eq!("e110060f0203", hex::encode(&bytes[..6]));
+        // Image bytes start at page 3, so address 24 is at index 12.
+        assert_eq!("fd0411", hex::encode(&bytes[9..12]));
+        assert_eq!([0x00; 8], bytes[12..20]);
+        assert_eq!(tag.tlvs(), NFT2Tag::from_bytes(&bytes).unwrap().0.tlvs());
+    }
 
-        let tlv = TlvValue::message(&[]);
-        assert_eq!(tlv.tag, TlvTag::NDEFMessage);
-        assert_eq!(tlv.value, Some(vec![]));
+    #[test]
+    fn test_tlv_iter() {
+        let mut data = hex::decode("000103a0104403ff0100").unwrap();
+        data.extend([0x11; 256]);
+        data.extend([0xfe, 0x03]);
+        let tlvs = TlvIter::new(&data).collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            vec![TlvTag::NULL, TlvTag::LockControl, TlvTag::NDEFMessage, TlvTag::Terminator],
+            tlvs.iter().map(TlvValue::tag).collect::<Vec<_>>()
+        );
+        assert_eq!(TlvValue::Ndef(vec![0x11; 256]), tlvs[2]);
 
-        let tlv = TlvValue::terminator();
-        assert_eq!(tlv.tag, TlvTag::Terminator);
-        assert_eq!(tlv.value, None);
+        let mut iter = TlvIter::new(&data[..20]);
+        assert_eq!(Some(Ok(TlvValue::Null)), iter.next());
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(Some(Err(TlvError::Truncated { tag: 0x03 })), iter.next());
+        assert_eq!(None, iter.next());
+        assert_eq!((TlvValue::Ndef(vec![0xaa]), 3), TlvValue::decode(&[0x03, 0x01, 0xaa, 0xfe]).unwrap());
+    }
 
-        let bytes = tlv.to_bytes();
-        assert_eq!(bytes, vec![0xfe]);
+    #[test]
+    fn test_tlv_length_forms() {
+        for len in [0, 1, 0xfe, 0xff, 0x100, 0x1234, TLV_MAX_LENGTH] {
+            let tlv = TlvValue::Proprietary(vec![0x5a; len]);
+            let bytes = tlv.to_bytes().unwrap();
+            let header = if len < 0xff { vec![0xfd, len as u8] } else { vec![0xfd, 0xff, (len >> 8) as u8, len as u8] };
+            assert_eq!(header, bytes[..header.len()]);
+            assert_eq!(header.len() + len, bytes.len());
+            assert_eq!((tlv, bytes.len()), TlvValue::decode(&bytes).unwrap());
+        }
     }
 
     #[test]
     fn test_empty() {
-        let tag1 = TlvValue::message(&[]);
-        let tag2 = TlvValue::terminator();
+        let tag1 = TlvValue::Ndef(vec![]);
+        let tag2 = TlvValue::Terminator;
         let t2tag = NFT2Tag::builder()
             .size_in_bytes(48)
             .add_tlv(tag1)
             .add_tlv(tag2)
-            .build();
+            .build()
+            .unwrap();
         let bytes = t2tag.to_bytes().unwrap();
         let expect = "e110060f0300fe";
         assert_eq!(hex::decode(expect).unwrap(), bytes);
     }
 
+    #[test]
+    fn test_builder_validation() {
+        assert!(matches!(
+            NFT2Tag::builder().size_in_bytes(0).build(),
+            Err(NdefError::InvalidTagMemorySize)
+        ));
+        assert!(matches!(
+            NFT2Tag::builder().size_in_bytes(0xfff9).build(),
+            Err(NdefError::InvalidTagMemorySize)
+        ));
+        let tag = NFT2Tag::builder().size_in_bytes(2040).build().unwrap();
+        assert_eq!(2040, tag.capacity_in_bytes());
+        assert_eq!(0xff, tag.capacity());
+        let tag = NFT2Tag::builder().size_in_bytes(41).access(0, 0).build().unwrap();
+        assert_eq!(6, tag.capacity());
+        assert!(NFT2Tag::builder().size_in_bytes(48).access(0x10, 0).build().is_err());
+        assert!(NFT2Tag::builder().size_in_bytes(48).access(0xf, 0).build().is_err());
+        assert!(NFT2Tag::builder().size_in_bytes(48).access(0, 0x3).build().is_err());
+    }
+
+    #[test]
+    fn test_extended_capacity() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(2041)
+            .add_tlv(TlvValue::Proprietary(vec![0x11; 2100]))
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        assert_eq!((0xff, 2048), (tag.capacity(), tag.capacity_in_bytes()));
+        assert!(tag.to_bytes().is_err());
+
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(4096)
+            .add_tlv(TlvValue::Proprietary(vec![0x11; 2100]))
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let mut bytes = tag.to_bytes().unwrap();
+        assert_eq!([0xe1, 0x10, 0xff, 0x0f], bytes[..4]);
+        bytes.resize(4 + 4096, 0);
+        let (parsed, messages) = NFT2Tag::from_bytes(&bytes).unwrap();
+        assert_eq!(vec![message], messages);
+        assert_eq!(4096, parsed.capacity_in_bytes());
+
+        // A full 64 KiB area of Null TLVs reads in one pass.
+        let mut nulls = vec![0xe1, 0x10, 0xff, 0x00];
+        nulls.resize(4 + T2T_MAX_EXTENDED_SIZE, 0x00);
+        let (parsed, _) = NFT2Tag::from_bytes(&nulls).unwrap();
+        assert_eq!(T2T_MAX_EXTENDED_SIZE, parsed.tlvs().len());
+    }
+
+    #[test]
+    fn test_eeprom() {
+        let tag = NFT2Tag::builder_for(ChipModel::Ntag213)
+            .unwrap()
+            .add_tlv(TlvValue::Ndef(vec![]))
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let image = tag.to_eeprom(&[0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66]).unwrap();
+        assert_eq!(160, image.len());
+        assert_eq!("041122bf3344556644480000e110120f", hex::encode(&image[..16]));
+        assert_eq!([0x03, 0x00, 0xfe, 0x00], image[16..20]);
+        assert_eq!(0x88, tag.to_eeprom(&[0; 7]).unwrap()[3]);
+    }
+
+    #[test]
+    fn test_finalize_read_only() {
+        let mut tag = NFT2Tag::builder_for(ChipModel::Ntag213).unwrap().access(0, 0).build().unwrap();
+        let locks = tag.finalize_read_only();
+        assert_eq!([0xe1, 0x10, 0x12, 0x0f], tag.to_bytes().unwrap()[..4]);
+        assert_eq!([0xff, 0xff], locks.static_lock);
+        assert_eq!(Some((160, vec![0xff, 0x0f])), locks.dynamic_lock);
+
+        let mut tag = NFT2Tag::builder().size_in_bytes(48).access(0, 0).build().unwrap();
+        assert_eq!(None, tag.finalize_read_only().dynamic_lock);
+    }
+
+    #[test]
+    fn test_presets() {
+        let tag = NFT2Tag::ntag213()
+            .add_tlv(TlvValue::Ndef(vec![]))
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        assert_eq!("e11012000103a00c340300fe", hex::encode(tag.to_bytes().unwrap()));
+        assert_eq!(Some((160, vec![0xff, 0x0f])), tag.dynamic_lock_bytes());
+
+        let tag = NFT2Tag::ntag216().build().unwrap();
+        assert_eq!([0xe1, 0x10, 0x6d, 0x00], tag.to_bytes().unwrap()[..4]);
+        assert_eq!(Some((0x388, vec![0xff, 0x3f])), tag.dynamic_lock_bytes());
+        assert_eq!(0x3e, NFT2Tag::ntag215().build().unwrap().capacity());
+        assert_eq!(None, NFT2Tag::ultralight_ev1_48().build().unwrap().dynamic_lock());
+        assert_eq!(
+            Some((0x90, vec![0x1f])),
+            NFT2Tag::ultralight_ev1_128().build().unwrap().dynamic_lock_bytes()
+        );
+    }
+
+    #[test]
+    fn test_builder_for_chip() {
+        let tag = NFT2Tag::builder_for(ChipModel::Ntag213).unwrap().build().unwrap();
+        assert_eq!(ChipModel::Ntag213.capability_container().unwrap()[2], tag.capacity());
+        assert!(NFT2Tag::builder_for(ChipModel::St25tv02k).is_err());
+    }
+
+    #[test]
+    fn test_dynamic_lock() {
+        assert_eq!(None, NFT2Tag::builder().size_in_bytes(48).build().unwrap().dynamic_lock_bytes());
+        // NTAG213: 12 bits at page 0x28, the default position.
+        let tag = NFT2Tag::builder_for(ChipModel::Ntag213).unwrap().build().unwrap();
+        assert_eq!([0xa0, 0x0c, 0x34], tag.dynamic_lock().unwrap().to_bytes());
+        assert_eq!(Some((160, vec![0xff, 0x0f])), tag.dynamic_lock_bytes());
+
+        let lock = LockControl::new(0xe2 * 4, 37, 64).unwrap();
+        let tag = NFT2Tag::builder_for(ChipModel::Ntag216)
+            .unwrap()
+            .add_tlv(TlvValue::LockControl(lock))
+            .build()
+            .unwrap();
+        assert_eq!(Some(lock), tag.dynamic_lock());
+        assert_eq!(Some((0x388, vec![0xff, 0xff, 0xff, 0xff, 0x1f])), tag.dynamic_lock_bytes());
+    }
+
+    #[test]
+    fn test_reserved_area() {
+        // Memory control: page 8 (4 bytes per page), offset 0, 4 bytes reserved.
+        let memory_control = TlvValue::parse(0x02, &[0x80, 0x04, 0x02]).unwrap();
+        assert_eq!(Some(32..36), memory_control.reserved_area());
+        let lock_control = TlvValue::parse(0x01, &[0xa0, 0x30, 0x44]).unwrap();
+        assert_eq!(Some(160..166), lock_control.reserved_area());
+
+        let t2tag = NFT2Tag::builder()
+            .size_in_bytes(48)
+            .add_tlv(memory_control)
+            .add_tlv(TlvValue::Ndef(vec![0x11; 16]))
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let bytes = t2tag.to_bytes().unwrap();
+        let expect = "e110060f020380040203101111111111111111110000000011111111111111fe";
+        assert_eq!(expect, hex::encode(&bytes));
+
+        let pages = t2tag.to_pages().unwrap();
+        assert_eq!(
+            vec![3, 4, 5, 6, 7, 9, 10],
+            pages.iter().map(|(n, _)| *n).collect::<Vec<_>>()
+        );
+        assert_eq!([0x11, 0x11, 0x11, 0xfe], pages[6].1);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(48)
+            .add_tlv(TlvValue::parse(0x02, &[0x80, 0x04, 0x02]).unwrap())
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let mut bytes = tag.to_bytes().unwrap();
+        bytes.resize(52, 0);
+
+        let (parsed, messages) = NFT2Tag::from_bytes(&bytes).unwrap();
+        assert_eq!(vec![message], messages);
+        assert_eq!(tag.tlvs(), parsed.tlvs());
+        assert_eq!(tag.to_bytes().unwrap(), parsed.to_bytes().unwrap());
+
+        bytes[0] = 0xe2;
+        assert!(matches!(NFT2Tag::from_bytes(&bytes), Err(NdefError::InvalidTag)));
+        assert!(NFT2Tag::from_bytes(&[0xe1, 0x10, 0x06, 0x00, 0x03, 0x05, 0xd1]).is_err());
+    }
+
+    #[test]
+    fn test_type1_tag() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = Type1Tag::builder()
+            .uid([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07])
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        assert!(tag.is_static());
+        let bytes = tag.to_bytes().unwrap();
+        assert_eq!(120, bytes.len());
+        assert_eq!("0102030405060700e1100e000310", hex::encode(&bytes[..14]));
+        assert_eq!(15, tag.to_blocks().unwrap().len());
+
+        let (parsed, messages) = Type1Tag::from_bytes(&bytes).unwrap();
+        assert_eq!(vec![message], messages);
+        assert_eq!(tag.uid(), parsed.uid());
+        assert_eq!(tag.tlvs(), parsed.tlvs());
+
+        // The static data area ends at block 0xD.
+        let full = Type1Tag::builder().add_tlv(TlvValue::Ndef(vec![0x11; 90])).build().unwrap();
+        assert_eq!(0x68, full.to_bytes().unwrap().iter().rposition(|&b| b == 0x11).unwrap() + 1);
+        let over = Type1Tag::builder().add_tlv(TlvValue::Ndef(vec![0x11; 91])).build().unwrap();
+        assert!(matches!(
+            over.to_bytes(),
+            Err(NdefError::CapacityExceeded { needed: 117, available: 108 })
+        ));
+        let dynamic = Type1Tag::builder().size_in_bytes(512).add_tlv(TlvValue::Proprietary(vec![0x11; 91])).add_tlv(TlvValue::Terminator).build().unwrap();
+        let bytes = dynamic.to_bytes().unwrap();
+        assert_eq!([0x11, 0x00], bytes[0x67..0x69]);
+        assert_eq!(dynamic.tlvs(), Type1Tag::from_bytes(&bytes).unwrap().0.tlvs());
+
+        assert!(Type1Tag::builder().size_in_bytes(112).build().is_err());
+        assert!(matches!(Type1Tag::from_bytes(&bytes[..8]), Err(NdefError::InvalidTagLength)));
+    }
+
+    #[test]
+    fn test_type3_tag() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = Type3Tag::builder_for(ChipModel::FelicaLiteS)
+            .unwrap()
+            .message(&message)
+            .unwrap()
+            .build()
+            .unwrap();
+        let bytes = tag.to_bytes();
+        assert_eq!("100401000d0000000000010000100033", hex::encode(&bytes[..16]));
+        assert_eq!(32, bytes.len());
+        assert_eq!(2, tag.to_blocks().len());
+
+        let (parsed, decoded) = Type3Tag::from_bytes(&bytes).unwrap();
+        assert_eq!(Some(message), decoded);
+        assert_eq!(tag.attributes(), parsed.attributes());
+
+        let mut corrupt = bytes.clone();
+        corrupt[15] ^= 1;
+        assert!(matches!(Type3Tag::from_bytes(&corrupt), Err(NdefError::InvalidTagData)));
+        let writing = T3tAttributes { writing: true, ..*tag.attributes() };
+        assert!(Type3Tag::from_bytes(&writing.to_bytes()).is_err());
+        let long = NdefMessage::builder().uri("https://example.com/long").build().unwrap();
+        assert!(Type3Tag::builder().max_blocks(1).message(&long).unwrap().build().is_err());
+        assert!(Type3Tag::builder_for(ChipModel::Ntag213).is_err());
+    }
+
+    #[test]
+    fn test_type4_tag() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = Type4Tag::builder_for(ChipModel::DesfireEv2K)
+            .unwrap()
+            .mle(0x3b)
+            .mlc(0x34)
+            .message(&message)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!("000f20003b00340406e104070000", hex::encode(&tag.cc_file()[..14]));
+        let ndef = tag.ndef_file();
+        assert_eq!([0x00, 0x10], ndef[..2]);
+
+        let mut padded = ndef.clone();
+        padded.resize(64, 0);
+        let (parsed, decoded) = Type4Tag::from_files(&tag.cc_file(), &padded).unwrap();
+        assert_eq!(Some(message), decoded);
+        assert_eq!(tag.capability_container(), parsed.capability_container());
+        assert!(Type4Tag::from_files(&tag.cc_file(), &ndef[..10]).is_err());
+
+        assert!(Type4Tag::builder().max_ndef_size(0x80).file_id([0xe1, 0x03]).build().is_err());
+        assert!(Type4Tag::builder().max_ndef_size(0x80).access(0x00, 0x01).build().is_err());
+        assert!(Type4Tag::builder().max_ndef_size(4).build().is_err());
+        let read_only = Type4Tag::builder().max_ndef_size(0x80).access(0, 0xff).build().unwrap();
+        assert_eq!(0xff, read_only.cc_file()[14]);
+    }
+
+    #[test]
+    fn test_type5_tag() {
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        let tag = Type5Tag::builder_for(ChipModel::St25tv02k)
+            .unwrap()
+            .features(T5tFeatures::MULTIPLE_BLOCK_READ)
+            .add_tlv(TlvValue::ndef_message(&message).unwrap())
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let bytes = tag.to_bytes().unwrap();
+        assert_eq!("e14020010310", hex::encode(&bytes[..6]));
+        let (parsed, messages) = Type5Tag::from_bytes(&bytes).unwrap();
+        assert_eq!(vec![message], messages);
+        assert_eq!(tag.capability_container(), parsed.capability_container());
+        assert_eq!(6, tag.to_blocks(4).unwrap().len());
+
+        let large = Type5Tag::builder()
+            .size_in_bytes(8192)
+            .extended_addressing(true)
+            .access(0b00, 0b11)
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let bytes = large.to_bytes().unwrap();
+        assert_eq!("e243000000000401fe", hex::encode(&bytes));
+        let (parsed, _) = Type5Tag::from_bytes(&bytes).unwrap();
+        assert_eq!(8192, parsed.capability_container().data_area_size());
+        assert!(parsed.capability_container().extended_addressing);
+
+        assert!(Type5Tag::builder().size_in_bytes(64).access(0b01, 0).build().is_err());
+        assert!(Type5Tag::from_bytes(&[0xe1, 0x40, 0x00, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_type5_presets() {
+        let cc = |builder: Type5TagBuilder| builder.build().unwrap().to_bytes().unwrap();
+        assert_eq!(hex::decode("e1400e01").unwrap(), cc(Type5Tag::icode_slix()));
+        assert_eq!(hex::decode("e1402801").unwrap(), cc(Type5Tag::icode_slix2()));
+        assert_eq!(hex::decode("e1402001").unwrap(), cc(Type5Tag::st25tv02k()));
+        assert_eq!(hex::decode("e240000100000400").unwrap(), cc(Type5Tag::st25dv64k()));
+        for chip in ChipModel::ALL.into_iter().filter(|chip| chip.tag_type() == TagType::Type5) {
+            let tag = Type5Tag::builder_for(chip).unwrap().build().unwrap();
+            if let Some(factory) = chip.capability_container() {
+                assert_eq!(factory.to_vec(), tag.to_bytes().unwrap());
+            }
+            assert_eq!(chip.data_area_size(), tag.capability_container().data_area_size());
+        }
+        // The factory image of a ST25TV02K ends with the chip's 256 bytes.
+        let mut image = ChipModel::St25tv02k.capability_container().unwrap().to_vec();
+        image.resize(256, 0x00);
+        let (parsed, _) = Type5Tag::from_bytes(&image).unwrap();
+        assert_eq!(252, parsed.capability_container().data_area_size());
+        let tag = Type5Tag::st25dv64k().add_tlv(TlvValue::Terminator).build().unwrap();
+        assert_eq!(Some(4), ChipModel::St25dv64k.page_size());
+        assert_eq!(3, tag.to_blocks(4).unwrap().len());
+    }
+
+    #[test]
+    fn test_replace_message() {
+        let old = NdefMessage::builder().uri("https://example.com/old").build().unwrap();
+        let new = NdefMessage::builder().uri("https://example.com/").build().unwrap();
+        let tag = NFT2Tag::ntag213()
+            .add_tlv(TlvValue::Null)
+            .add_tlv(TlvValue::ndef_message(&old).unwrap())
+            .add_tlv(TlvValue::Proprietary(vec![0x42; 3]))
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let (mut parsed, _) = NFT2Tag::from_bytes(&tag.to_bytes().unwrap()).unwrap();
+        parsed.replace_message(&new).unwrap();
+        let (reparsed, messages) = NFT2Tag::from_bytes(&parsed.to_bytes().unwrap()).unwrap();
+        assert_eq!(vec![new], messages);
+        assert_eq!(
+            vec![TlvTag::LockControl, TlvTag::NULL, TlvTag::NDEFMessage, TlvTag::Proprietary, TlvTag::Terminator],
+            reparsed.tlvs().iter().map(TlvValue::tag).collect::<Vec<_>>()
+        );
+
+        let large = NdefMessage::builder().text("en", &"x".repeat(200)).build().unwrap();
+        let before = parsed.to_bytes().unwrap();
+        assert!(parsed.replace_message(&large).is_err());
+        assert_eq!(before, parsed.to_bytes().unwrap());
+
+        let mut empty = NFT2Tag::ntag213().add_tlv(TlvValue::Terminator).build().unwrap();
+        empty.replace_message(&old).unwrap();
+        assert_eq!(TlvTag::NDEFMessage, empty.tlvs()[1].tag());
+    }
+
+    #[test]
+    fn test_other_tlv() {
+        let vendor = TlvValue::Other {
+            tag: 0x42,
+            value: vec![0x01, 0x02, 0x03],
+        };
+        let bytes = hex::decode("e1100600420301020303000300fe").unwrap();
+        let (mut tag, _) = NFT2Tag::from_bytes(&bytes).unwrap();
+        assert_eq!(vendor, tag.tlvs()[0]);
+        assert_eq!(TlvTag::Other(0x42), tag.tlvs()[0].tag());
+
+        let message = NdefMessage::builder().uri("https://example.com").build().unwrap();
+        tag.replace_message(&message).unwrap();
+        let (parsed, messages) = NFT2Tag::from_bytes(&tag.to_bytes().unwrap()).unwrap();
+        assert_eq!(vec![message], messages);
+        assert_eq!(vendor, parsed.tlvs()[0]);
+        assert_eq!("42030102030310", hex::encode(&parsed.to_bytes().unwrap()[4..11]));
+    }
+
+    #[test]
+    fn test_auto_terminator() {
+        let build = |len| {
+            NFT2Tag::builder()
+                .size_in_bytes(48)
+                .auto_terminator(true)
+                .add_tlv(TlvValue::Ndef(vec![0x11; len]))
+                .build()
+                .unwrap()
+        };
+        let bytes = build(45).to_bytes().unwrap();
+        assert_eq!([0x11, 0xfe], bytes[4 + 46..4 + 48]);
+        // No room left for the Terminator TLV: the data area ends instead.
+        let bytes = build(46).to_bytes().unwrap();
+        assert_eq!((52, 0x11), (bytes.len(), bytes[51]));
+        assert!(matches!(
+            build(47).to_bytes(),
+            Err(NdefError::CapacityExceeded { needed: 49, available: 48 })
+        ));
+
+        let tag = Type5Tag::builder()
+            .size_in_bytes(64)
+            .auto_terminator(true)
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        assert_eq!(vec![0xe1, 0x40, 0x08, 0x00, 0xfe], tag.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_memory_map() {
+        let tag = NFT2Tag::builder()
+            .size_in_bytes(48)
+            .access(0, 0)
+            .add_tlv(TlvValue::memory_control(24, 8, 4).unwrap())
+            .add_tlv(TlvValue::Proprietary(vec![0x11; 4]))
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
+        let map = tag.memory_map().unwrap();
+        let proprietary = RegionKind::Tlv { index: 1, tag: TlvTag::Proprietary };
+        assert_eq!(
+            vec![
+                (12..16, RegionKind::CapabilityContainer),
+                (16..21, RegionKind::Tlv { index: 0, tag: TlvTag::MemoryControl }),
+                (21..24, proprietary),
+                (24..32, RegionKind::Reserved),
+                (32..35, proprietary),
+                (35..36, RegionKind::Tlv { index: 2, tag: TlvTag::Terminator }),
+                (36..64, RegionKind::Free),
+            ],
+            map.into_iter().map(|r| (r.range, r.kind)).collect::<Vec<_>>()
+        );
+
+        let tag = NFT2Tag::ntag213().add_tlv(TlvValue::Ndef(vec![0; 150])).build().unwrap();
+        let map = tag.memory_map().unwrap();
+        // 152 TLV bytes from 21, around the dynamic lock bytes at 160.
+        assert_eq!(21..160, map[2].range);
+        assert_eq!((160..162, RegionKind::Reserved), (map[3].range.clone(), map[3].kind));
+        assert_eq!(162..175, map[4].range);
+    }
+
     #[test]
     fn test_ndef_message() {
         use super::*;
@@ -253,8 +2428,9 @@ mod tests {
         let t2tag = NFT2Tag::builder()
             .size_in_bytes(256)
             .add_tlv(tlv)
-            .add_tlv(TlvValue::terminator())
-            .build();
+            .add_tlv(TlvValue::Terminator)
+            .build()
+            .unwrap();
         let bytes = t2tag.to_bytes().unwrap();
         let expect = "e110200f0339910115550077656978696e3a2f2f646c2f627573696e657373540f0e616e64726f69642e636f6d3a706b67636f6d2e74656e63656e742e6d6dfe";
         assert_eq!(expect, hex::encode(bytes));
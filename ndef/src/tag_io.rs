@@ -0,0 +1,334 @@
+use crate::{
+    apdu,
+    error::NdefError,
+    message::NdefMessage,
+    tag::{NFT2Tag, T4tCapabilityContainer, T5tCapabilityContainer, TlvValue, Type4Tag},
+    Result,
+};
+
+/// Raw command channel to a tag, typically backed by a reader driver.
+///
+/// `transceive` sends one command frame and returns the tag's response:
+/// native commands for Type 2 and Type 5 tags, ISO 7816 APDUs for Type 4.
+pub trait TagIo {
+    fn transceive(&mut self, command: &[u8]) -> Result<Vec<u8>>;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TagType {
+    Type2,
+    /// FeliCa based tags; not handled by [`auto_write`].
+    Type3,
+    Type4,
+    Type5,
+}
+
+const T2T_READ: u8 = 0x30;
+const T2T_WRITE: u8 = 0xa2;
+const T2T_DATA_PAGE: usize = 4;
+/// Pages and blocks a one-byte address reaches; sector select and 2-byte
+/// block addresses are not implemented.
+const MAX_ADDRESS: usize = 0xff;
+
+const T5T_FLAGS: u8 = 0x02;
+const T5T_READ_SINGLE_BLOCK: u8 = 0x20;
+const T5T_WRITE_SINGLE_BLOCK: u8 = 0x21;
+
+/// Detects the tag type from its response to each type's initial read,
+/// then writes `message` with that type's layout and commands.
+pub fn auto_write(io: &mut impl TagIo, message: &NdefMessage) -> Result<TagType> {
+    let tag_type = detect(io)?;
+    match tag_type {
+        TagType::Type2 => write_type2(io, message)?,
+        TagType::Type3 => return Err(NdefError::InvalidTagType),
+        TagType::Type4 => write_type4(io, message)?,
+        TagType::Type5 => write_type5(io, message)?,
+    }
+    Ok(tag_type)
+}
+
+/// Probes the tag: a Type 2 READ of the CC page, then the Type 4 NDEF
+/// application SELECT, then a Type 5 READ SINGLE BLOCK of the CC block.
+pub fn detect(io: &mut impl TagIo) -> Result<TagType> {
+    if let Ok(cc) = io.transceive(&[T2T_READ, 3]) {
+        if cc.len() == 16 && cc[0] == 0xe1 {
+            return Ok(TagType::Type2);
+        }
+    }
+    if select_application(io).is_ok() {
+        return Ok(TagType::Type4);
+    }
+    if let Ok(block) = read_block(io, 0) {
+        if block.len() >= 4 && (block[0] == 0xe1 || block[0] == 0xe2) {
+            return Ok(TagType::Type5);
+        }
+    }
+    Err(NdefError::InvalidTagType)
+}
+
+fn tlv_area(message: &NdefMessage) -> Result<Vec<u8>> {
+    let mut area = TlvValue::ndef_message(message)?.to_bytes()?;
+    area.extend(TlvValue::Terminator.to_bytes()?);
+    Ok(area)
+}
+
+fn write_type2(io: &mut impl TagIo, message: &NdefMessage) -> Result<()> {
+    let cc = io.transceive(&[T2T_READ, 3])?;
+    if cc.len() < 4 {
+        return Err(NdefError::InvalidTagLength);
+    }
+    if cc[1] >> 4 != 1 || cc[3] & 0x0f != 0 {
+        return Err(NdefError::InvalidTag);
+    }
+    let image = NFT2Tag::builder()
+        .size_in_8bytes(cc[2])
+        .add_tlv(TlvValue::ndef_message(message)?)
+        .add_tlv(TlvValue::Terminator)
+        .build()?
+        .to_bytes()?;
+    let data = &image[4..];
+    let available = (MAX_ADDRESS + 1 - T2T_DATA_PAGE) * 4;
+    if data.len() > available {
+        return Err(NdefError::CapacityExceeded {
+            needed: data.len(),
+            available,
+        });
+    }
+    for (index, page) in data.chunks(4).enumerate() {
+        let mut command = vec![T2T_WRITE, (T2T_DATA_PAGE + index) as u8];
+        command.extend_from_slice(page);
+        command.resize(6, 0x00);
+        io.transceive(&command)?;
+    }
+    Ok(())
+}
+
+/// Sends `command` and strips the status word, failing unless it is 9000.
+fn exchange(io: &mut impl TagIo, command: &[u8]) -> Result<Vec<u8>> {
+    let mut response = io.transceive(command)?;
+    if response.len() < 2 || response[response.len() - 2..] != apdu::SW_OK {
+        return Err(NdefError::InvalidTag);
+    }
+    response.truncate(response.len() - 2);
+    Ok(response)
+}
+
+fn select_application(io: &mut impl TagIo) -> Result<()> {
+    exchange(io, &apdu::select_application()).map(|_| ())
+}
+
+fn write_type4(io: &mut impl TagIo, message: &NdefMessage) -> Result<()> {
+    let mut cc = vec![];
+    for command in apdu::read_cc() {
+        cc = exchange(io, &command)?;
+    }
+    let cc = T4tCapabilityContainer::from_bytes(&cc)?;
+    if cc.write_access != 0x00 {
+        return Err(NdefError::InvalidTag);
+    }
+    let tag = Type4Tag::builder()
+        .mlc(cc.mlc.max(1))
+        .file_id(cc.file_id)
+        .max_ndef_size(cc.max_ndef_size)
+        .message(message)?
+        .build()?;
+    // The application is already selected.
+    for command in apdu::write_ndef(&tag)?.into_iter().skip(1) {
+        exchange(io, &command)?;
+    }
+    Ok(())
+}
+
+fn read_block(io: &mut impl TagIo, block: u8) -> Result<Vec<u8>> {
+    let mut response = io.transceive(&[T5T_FLAGS, T5T_READ_SINGLE_BLOCK, block])?;
+    if response.len() < 2 || response[0] != 0x00 {
+        return Err(NdefError::InvalidTag);
+    }
+    response.remove(0);
+    Ok(response)
+}
+
+fn write_type5(io: &mut impl TagIo, message: &NdefMessage) -> Result<()> {
+    let mut cc = read_block(io, 0)?;
+    let block_size = cc.len();
+    if cc.len() >= 4 && cc[2] == 0 && cc.len() < 8 {
+        cc.extend(read_block(io, 1)?);
+    }
+    let parsed = T5tCapabilityContainer::from_bytes(&cc)?;
+    if parsed.version >> 4 != 1 || parsed.write_access != 0 {
+        return Err(NdefError::InvalidTag);
+    }
+    let (cc_len, capacity) = (parsed.len(), parsed.data_area_size());
+
+    let area = tlv_area(message)?;
+    if area.len() > capacity {
+        return Err(NdefError::CapacityExceeded {
+            needed: area.len(),
+            available: capacity,
+        });
+    }
+    // The TLV area starts right after the CC, which may share its block.
+    let first = cc_len / block_size;
+    let mut data = cc[first * block_size..cc_len].to_vec();
+    data.extend(area);
+    let available = (MAX_ADDRESS + 1 - first) * block_size;
+    if data.len() > available {
+        return Err(NdefError::CapacityExceeded {
+            needed: data.len(),
+            available,
+        });
+    }
+    for (index, chunk) in data.chunks(block_size).enumerate() {
+        let mut command = vec![T5T_FLAGS, T5T_WRITE_SINGLE_BLOCK, (first + index) as u8];
+        command.extend_from_slice(chunk);
+        command.resize(3 + block_size, 0x00);
+        io.transceive(&command)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{payload::*, NdefRecord, TNF};
+
+    struct Type2Memory(Vec<u8>);
+
+    impl TagIo for Type2Memory {
+        fn transceive(&mut self, command: &[u8]) -> Result<Vec<u8>> {
+            match command[0] {
+                T2T_READ => {
+                    let start = (command[1] as usize * 4).min(self.0.len());
+                    Ok(self.0[start..(start + 16).min(self.0.len())].to_vec())
+                }
+                T2T_WRITE => {
+                    let start = command[1] as usize * 4;
+                    self.0[start..start + 4].copy_from_slice(&command[2..6]);
+                    Ok(vec![0x0a])
+                }
+                _ => Err(NdefError::InvalidTag),
+            }
+        }
+    }
+
+    /// 4-byte blocks behind READ and WRITE SINGLE BLOCK.
+    struct Type5Memory(Vec<u8>);
+
+    impl TagIo for Type5Memory {
+        fn transceive(&mut self, command: &[u8]) -> Result<Vec<u8>> {
+            if command.len() < 3 || command[0] != T5T_FLAGS {
+                return Err(NdefError::InvalidTag);
+            }
+            let start = command[2] as usize * 4;
+            match command[1] {
+                T5T_READ_SINGLE_BLOCK => Ok([&[0x00], &self.0[start..start + 4]].concat()),
+                T5T_WRITE_SINGLE_BLOCK => {
+                    self.0[start..start + 4].copy_from_slice(&command[3..7]);
+                    Ok(vec![0x00])
+                }
+                _ => Err(NdefError::InvalidTag),
+            }
+        }
+    }
+
+    struct Type4Files {
+        selected: Vec<u8>,
+        cc: Vec<u8>,
+        ndef: Vec<u8>,
+    }
+
+    impl TagIo for Type4Files {
+        fn transceive(&mut self, command: &[u8]) -> Result<Vec<u8>> {
+            let ok = apdu::SW_OK.to_vec();
+            match (command[0], command[1]) {
+                (0x00, 0xa4) => {
+                    self.selected = command[5..5 + command[4] as usize].to_vec();
+                    Ok(ok)
+                }
+                (0x00, 0xb0) if self.selected == apdu::CC_FILE_ID => Ok([self.cc.clone(), ok].concat()),
+                (0x00, 0xd6) if self.selected == [0xe1, 0x04] => {
+                    let offset = u16::from_be_bytes([command[2], command[3]]) as usize;
+                    let data = &command[5..5 + command[4] as usize];
+                    self.ndef[offset..offset + data.len()].copy_from_slice(data);
+                    Ok(ok)
+                }
+                _ => Ok(vec![0x6a, 0x82]),
+            }
+        }
+    }
+
+    fn message() -> NdefMessage {
+        NdefMessage::from(
+            NdefRecord::builder()
+                .tnf(TNF::WellKnown)
+                .payload(&UriPayload::from_static("https://example.com"))
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_auto_write_type2() {
+        let mut memory = vec![0u8; 64];
+        memory[12..16].copy_from_slice(&[0xe1, 0x10, 0x06, 0x00]);
+        let mut tag = Type2Memory(memory);
+        assert_eq!(TagType::Type2, auto_write(&mut tag, &message()).unwrap());
+        let expected = tlv_area(&message()).unwrap();
+        assert_eq!(expected, tag.0[16..16 + expected.len()]);
+    }
+
+    fn long_message() -> NdefMessage {
+        NdefMessage::builder().text("en", &"x".repeat(1200)).build().unwrap()
+    }
+
+    #[test]
+    fn test_auto_write_large_type2() {
+        // CC size byte 0xFF: 2040 bytes of data area, up to page 513.
+        let mut memory = vec![0u8; 16 + 2040];
+        memory[12..16].copy_from_slice(&[0xe1, 0x10, 0xff, 0x00]);
+        let mut tag = Type2Memory(memory.clone());
+        assert!(matches!(
+            auto_write(&mut tag, &long_message()),
+            Err(NdefError::CapacityExceeded { available: 1008, .. })
+        ));
+        assert_eq!(memory, tag.0);
+
+        assert_eq!(TagType::Type2, auto_write(&mut tag, &message()).unwrap());
+        let expected = tlv_area(&message()).unwrap();
+        assert_eq!(expected, tag.0[16..16 + expected.len()]);
+
+        let mut short = Type2Memory(vec![0xe1, 0x10]);
+        assert!(write_type2(&mut short, &message()).is_err());
+    }
+
+    #[test]
+    fn test_auto_write_large_type5() {
+        // 8 KiB behind the 8-byte CC with 2-byte block addresses.
+        let mut memory = vec![0u8; 8 + 8192];
+        memory[..8].copy_from_slice(&[0xe2, 0x40, 0x00, 0x01, 0x00, 0x00, 0x04, 0x00]);
+        let mut tag = Type5Memory(memory.clone());
+        assert!(matches!(
+            auto_write(&mut tag, &long_message()),
+            Err(NdefError::CapacityExceeded { available: 1016, .. })
+        ));
+        assert_eq!(memory, tag.0);
+
+        assert_eq!(TagType::Type5, auto_write(&mut tag, &message()).unwrap());
+        assert_eq!(memory[..8], tag.0[..8]);
+        let expected = tlv_area(&message()).unwrap();
+        assert_eq!(expected, tag.0[8..8 + expected.len()]);
+    }
+
+    #[test]
+    fn test_auto_write_type4() {
+        let mut tag = Type4Files {
+            selected: vec![],
+            cc: hex::decode("000f20003b00340406e10400ff0000").unwrap(),
+            ndef: vec![0u8; 255],
+        };
+        assert_eq!(TagType::Type4, auto_write(&mut tag, &message()).unwrap());
+        let bytes = message().to_buffer().unwrap();
+        assert_eq!((bytes.len() as u16).to_be_bytes(), tag.ndef[..2]);
+        assert_eq!(bytes, tag.ndef[2..2 + bytes.len()]);
+    }
+}
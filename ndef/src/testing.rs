@@ -0,0 +1,83 @@
+//! Proptest strategies for records and messages, both well-formed and
+//! deliberately broken, for testing code that consumes NDEF data.
+
+use crate::{audit::audit, payload::*, *};
+use proptest::{collection::vec, prelude::*, sample::Index};
+
+/// TNFs valid on a standalone record.
+pub fn tnf() -> impl Strategy<Value = TNF> {
+    prop_oneof![
+        Just(TNF::Empty),
+        Just(TNF::WellKnown),
+        Just(TNF::MimeMedia),
+        Just(TNF::AbsoluteUri),
+        Just(TNF::External),
+        Just(TNF::Unknown),
+    ]
+}
+
+/// Any record the builder accepts, with payloads long enough to need the
+/// 4-byte length form.
+pub fn record() -> impl Strategy<Value = NdefRecord> {
+    (tnf(), vec(any::<u8>(), 0..=32), vec(any::<u8>(), 0..=8), vec(any::<u8>(), 0..=300)).prop_map(
+        |(tnf, record_type, id, payload)| NdefRecord::sanitized(tnf, record_type, id, payload).unwrap(),
+    )
+}
+
+/// URI records with any abbreviation and text.
+pub fn uri_record() -> impl Strategy<Value = NdefRecord> {
+    (any::<Index>(), any::<String>()).prop_map(|(abbrev, uri)| {
+        let abbrev = URI_ABBREVIATIONS[abbrev.index(URI_ABBREVIATIONS.len())];
+        NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&UriPayload::with_abbrev(abbrev, uri))
+            .build()
+            .unwrap()
+    })
+}
+
+/// Messages of one to eight records.
+pub fn message() -> impl Strategy<Value = NdefMessage> {
+    vec(prop_oneof![record(), uri_record()], 1..=8).prop_map(NdefMessage::from_vec)
+}
+
+/// Encoded well-formed messages.
+pub fn message_bytes() -> impl Strategy<Value = Vec<u8>> {
+    message().prop_map(|message| message.to_buffer().unwrap())
+}
+
+/// Encoded messages broken in a way every decoder must reject: truncated,
+/// missing the ME flag, or with MB set on a record other than the first.
+pub fn malformed_message_bytes() -> impl Strategy<Value = Vec<u8>> {
+    (message_bytes(), 0..3u8, any::<Index>()).prop_map(|(mut bytes, kind, index)| {
+        let records = audit(&bytes).records;
+        match kind {
+            1 => bytes[records.last().unwrap().offset] &= !RecordFlags::ME.bits(),
+            2 if records.len() > 1 => {
+                let record = &records[1 + index.index(records.len() - 1)];
+                bytes[record.offset] |= RecordFlags::MB.bits();
+            }
+            _ => bytes.truncate(index.index(bytes.len())),
+        }
+        bytes
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn test_round_trip(message in message()) {
+            let bytes = message.to_buffer().unwrap();
+            let decoded = NdefMessage::decode(&bytes).unwrap();
+            prop_assert_eq!(message.records(), decoded.records());
+        }
+
+        #[test]
+        fn test_malformed_rejected(bytes in malformed_message_bytes()) {
+            prop_assert!(NdefMessage::decode(&bytes).is_err());
+        }
+    }
+}
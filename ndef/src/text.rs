@@ -0,0 +1,282 @@
+use crate::{error::NdefError, payload::RecordPayload, record::NdefRecord, *};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Status byte flag marking UTF-16 text.
+const UTF16_FLAG: u8 = 0x80;
+const LANGUAGE_LENGTH_MASK: u8 = 0x3f;
+
+/// IANA language tag (RFC 5646) as carried by Text records, e.g. `en` or
+/// `zh-Hant-TW`. Tags are stored in canonical case, so `EN-us` equals `en-US`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
+pub struct LanguageTag(String);
+
+impl LanguageTag {
+    /// Validates `tag` as dash-separated ASCII alphanumeric subtags of at most
+    /// 8 characters, 63 bytes in total (the Text record limit).
+    pub fn new(tag: &str) -> Result<Self> {
+        if tag.is_empty() || tag.len() > LANGUAGE_LENGTH_MASK as usize {
+            return Err(NdefError::InvalidLanguage);
+        }
+        let mut canonical = Vec::new();
+        for (index, subtag) in tag.split('-').enumerate() {
+            if subtag.is_empty() || subtag.len() > 8 || !subtag.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return Err(NdefError::InvalidLanguage);
+            }
+            let subtag = match subtag.len() {
+                _ if index == 0 => subtag.to_ascii_lowercase(),
+                2 => subtag.to_ascii_uppercase(),
+                4 if subtag.bytes().all(|b| b.is_ascii_alphabetic()) => {
+                    let lower = subtag.to_ascii_lowercase();
+                    lower[..1].to_ascii_uppercase() + &lower[1..]
+                }
+                _ => subtag.to_ascii_lowercase(),
+            };
+            canonical.push(subtag);
+        }
+        Ok(Self(canonical.join("-")))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Primary language subtag, e.g. `en` for `en-US`.
+    pub fn primary(&self) -> &str {
+        self.0.split('-').next().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Text record with its language code, encoded per the NFC Forum Text RTD
+/// (status byte, language code, then the text). Written as UTF-8; UTF-16
+/// records are decoded as well.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LocalizedTextPayload {
+    language: LanguageTag,
+    text: String,
+}
+
+impl LocalizedTextPayload {
+    pub fn new<T: Into<String>>(language: LanguageTag, text: T) -> Self {
+        Self {
+            language,
+            text: text.into(),
+        }
+    }
+
+    pub fn language(&self) -> &LanguageTag {
+        &self.language
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let status = *data.first().ok_or(NdefError::InvalidPayload)?;
+        let language_len = (status & LANGUAGE_LENGTH_MASK) as usize;
+        let language = data.get(1..1 + language_len).ok_or(NdefError::InvalidPayload)?;
+        let language = std::str::from_utf8(language).map_err(|_| NdefError::InvalidLanguage)?;
+        let text = &data[1 + language_len..];
+        let text = if status & UTF16_FLAG != 0 {
+            decode_utf16(text)?
+        } else {
+            String::from_utf8(text.to_vec()).map_err(|_| NdefError::InvalidEncoding)?
+        };
+        Ok(Self::new(LanguageTag::new(language)?, text))
+    }
+}
+
+/// UTF-16 text, big-endian unless a byte order mark says otherwise.
+fn decode_utf16(data: &[u8]) -> Result<String> {
+    if data.len() % 2 != 0 {
+        return Err(NdefError::InvalidEncoding);
+    }
+    let (data, little_endian) = match data {
+        [0xff, 0xfe, rest @ ..] => (rest, true),
+        [0xfe, 0xff, rest @ ..] => (rest, false),
+        _ => (data, false),
+    };
+    let units = data.chunks(2).map(|pair| {
+        if little_endian {
+            u16::from_le_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_be_bytes([pair[0], pair[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .collect::<std::result::Result<String, _>>()
+        .map_err(|_| NdefError::InvalidEncoding)
+}
+
+impl RecordPayload for LocalizedTextPayload {
+    fn tnf(&self) -> TNF {
+        TNF::WellKnown
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(RTD_TEXT.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        let mut buffer = vec![self.language.0.len() as u8];
+        buffer.extend_from_slice(self.language.0.as_bytes());
+        buffer.extend_from_slice(self.text.as_bytes());
+        Cow::Owned(buffer)
+    }
+}
+
+impl TryFrom<&NdefRecord> for LocalizedTextPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        if record.tnf() != TNF::WellKnown {
+            return Err(NdefError::InvalidTnf);
+        }
+        if record.record_type() != RTD_TEXT.as_bytes() {
+            return Err(NdefError::InvalidRecordType);
+        }
+        Self::decode(record.payload())
+    }
+}
+
+/// One Text record per language: `text` in `language` first, then each
+/// translation ordered by language tag. A translation for `language` itself
+/// is ignored.
+pub fn multilingual_records(
+    language: &LanguageTag,
+    text: &str,
+    translations: &HashMap<LanguageTag, String>,
+) -> Result<Vec<NdefRecord>> {
+    let mut others: Vec<_> = translations.iter().filter(|(tag, _)| *tag != language).collect();
+    others.sort_by(|a, b| a.0.cmp(b.0));
+    std::iter::once((language, text))
+        .chain(others.into_iter().map(|(tag, text)| (tag, text.as_str())))
+        .map(|(tag, text)| {
+            NdefRecord::builder()
+                .tnf(TNF::WellKnown)
+                .payload(&LocalizedTextPayload::new(tag.clone(), text))
+                .build()
+        })
+        .collect()
+}
+
+/// Texts of all Text records in `records` keyed by language. Other record
+/// types are skipped; for a repeated language the first record wins.
+pub fn multilingual_texts(records: &[NdefRecord]) -> Result<HashMap<LanguageTag, String>> {
+    let mut texts = HashMap::new();
+    for record in records {
+        if record.tnf() != TNF::WellKnown || record.record_type() != RTD_TEXT.as_bytes() {
+            continue;
+        }
+        let payload = LocalizedTextPayload::decode(record.payload())?;
+        texts.entry(payload.language).or_insert(payload.text);
+    }
+    Ok(texts)
+}
+
+/// Text record of `records` that best suits a reader preferring `language`.
+/// Lookup follows RFC 4647: the full tag, then the tag with trailing
+/// subtags removed (`zh-Hant-TW`, `zh-Hant`, `zh`); failing that, a record
+/// with the same primary language (`zh-TW` for `zh-CN`), and finally the
+/// first Text record. `None` only when there is no Text record at all.
+pub fn text_for(records: &[NdefRecord], language: &LanguageTag) -> Result<Option<LocalizedTextPayload>> {
+    let mut texts = vec![];
+    for record in records {
+        if record.tnf() == TNF::WellKnown && record.record_type() == RTD_TEXT.as_bytes() {
+            texts.push(LocalizedTextPayload::decode(record.payload())?);
+        }
+    }
+    let mut range = language.as_str();
+    loop {
+        if let Some(index) = texts.iter().position(|t| t.language.as_str() == range) {
+            return Ok(Some(texts.swap_remove(index)));
+        }
+        match range.rfind('-') {
+            Some(end) => range = &range[..end],
+            None => break,
+        }
+    }
+    let index = texts
+        .iter()
+        .position(|t| t.language.primary() == language.primary())
+        .unwrap_or(0);
+    Ok((index < texts.len()).then(|| texts.swap_remove(index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(value: &str) -> LanguageTag {
+        LanguageTag::new(value).unwrap()
+    }
+
+    #[test]
+    fn test_language_tag() {
+        assert_eq!("en-US", tag("EN-us").as_str());
+        assert_eq!("zh-Hant-TW", tag("zh-hant-tw").as_str());
+        assert_eq!("en", tag("en-GB").primary());
+        assert!(LanguageTag::new("").is_err());
+        assert!(LanguageTag::new("en_US").is_err());
+        assert!(LanguageTag::new("en--US").is_err());
+    }
+
+    #[test]
+    fn test_localized_text() {
+        let text = LocalizedTextPayload::new(tag("en"), "Hello");
+        assert_eq!("02656e48656c6c6f", hex::encode(text.payload()));
+        assert_eq!(text, LocalizedTextPayload::decode(&text.payload()).unwrap());
+
+        let utf16 = hex::decode("8264650048006900").unwrap();
+        assert!(LocalizedTextPayload::decode(&utf16).is_err());
+        let utf16 = hex::decode("826465feff00480069").unwrap();
+        assert_eq!("Hi", LocalizedTextPayload::decode(&utf16).unwrap().text());
+        assert!(LocalizedTextPayload::decode(&[0x05, b'e', b'n']).is_err());
+    }
+
+    #[test]
+    fn test_multilingual() {
+        let translations = HashMap::from([
+            (tag("fr"), "Bienvenue".to_string()),
+            (tag("de"), "Willkommen".to_string()),
+            (tag("en"), "ignored".to_string()),
+        ]);
+        let records = multilingual_records(&tag("en"), "Welcome", &translations).unwrap();
+        let languages: Vec<_> = records
+            .iter()
+            .map(|r| LocalizedTextPayload::try_from(r).unwrap().language().to_string())
+            .collect();
+        assert_eq!(vec!["en", "de", "fr"], languages);
+
+        let message = NdefMessage::decode(NdefMessage::from(&records).to_buffer().unwrap()).unwrap();
+        let texts = multilingual_texts(message.records()).unwrap();
+        assert_eq!(3, texts.len());
+        assert_eq!("Welcome", texts[&tag("en")]);
+        assert_eq!("Willkommen", texts[&tag("de")]);
+    }
+
+    #[test]
+    fn test_text_for() {
+        let records = multilingual_records(
+            &tag("en"),
+            "Exit",
+            &HashMap::from([(tag("zh-TW"), "出口".to_string()), (tag("fr"), "Sortie".to_string())]),
+        )
+        .unwrap();
+        let text = |language| text_for(&records, &tag(language)).unwrap().unwrap();
+        assert_eq!("Sortie", text("fr").text());
+        assert_eq!("Sortie", text("fr-CA").text());
+        assert_eq!("出口", text("zh-CN").text());
+        assert_eq!(tag("zh-TW"), *text("zh-Hant-TW").language());
+        assert_eq!("Exit", text("de").text());
+        assert_eq!(None, text_for(&[], &tag("en")).unwrap());
+    }
+}
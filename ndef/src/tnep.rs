@@ -0,0 +1,284 @@
+use crate::{error::NdefError, payload::RecordPayload, record::NdefRecord, *};
+use std::borrow::Cow;
+
+/// TNEP version 1.0.
+pub const TNEP_VERSION: u8 = 0x10;
+
+/// Single response communication mode.
+pub const TNEP_MODE_SINGLE_RESPONSE: u8 = 0x00;
+
+fn check_record(record: &NdefRecord, rtd: RTD) -> Result<()> {
+    if record.tnf() != TNF::WellKnown {
+        return Err(NdefError::InvalidTnf);
+    }
+    if record.record_type() != rtd.as_bytes() {
+        return Err(NdefError::InvalidRecordType);
+    }
+    Ok(())
+}
+
+fn service_name(data: &[u8]) -> Result<(String, &[u8])> {
+    let (len, rest) = data.split_first().ok_or(NdefError::InvalidPayload)?;
+    if rest.len() < *len as usize {
+        return Err(NdefError::InvalidPayload);
+    }
+    let (name, rest) = rest.split_at(*len as usize);
+    let name = std::str::from_utf8(name).map_err(|_| NdefError::InvalidEncoding)?;
+    Ok((name.to_string(), rest))
+}
+
+/// TNEP Service Parameter ("Tp") record, announcing a service offered by the tag.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ServiceParameterPayload {
+    version: u8,
+    service_name: String,
+    mode: u8,
+    wait_time: u8,
+    max_wait_extensions: u8,
+    max_message_size: u16,
+}
+
+impl ServiceParameterPayload {
+    pub fn new<T: Into<String>>(service_name: T) -> Result<Self> {
+        let service_name = service_name.into();
+        if service_name.len() > 0xff {
+            return Err(NdefError::InvalidUri);
+        }
+        Ok(Self {
+            version: TNEP_VERSION,
+            service_name,
+            mode: TNEP_MODE_SINGLE_RESPONSE,
+            wait_time: 0,
+            max_wait_extensions: 0,
+            max_message_size: 0,
+        })
+    }
+
+    /// Sets the minimum waiting time as the encoded WT_int value (0..=63).
+    pub fn with_wait_time(mut self, wait_time: u8) -> Self {
+        self.wait_time = wait_time & 0x3f;
+        self
+    }
+
+    /// Sets the maximum number of waiting time extensions (0..=15).
+    pub fn with_max_wait_extensions(mut self, extensions: u8) -> Self {
+        self.max_wait_extensions = extensions & 0x0f;
+        self
+    }
+
+    pub fn with_max_message_size(mut self, size: u16) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    pub fn wait_time(&self) -> u8 {
+        self.wait_time
+    }
+
+    /// Minimum waiting time in milliseconds: 2^(WT_int / 4 - 1).
+    pub fn wait_time_ms(&self) -> f64 {
+        2f64.powf(self.wait_time as f64 / 4.0 - 1.0)
+    }
+
+    pub fn max_wait_extensions(&self) -> u8 {
+        self.max_wait_extensions
+    }
+
+    pub fn max_message_size(&self) -> u16 {
+        self.max_message_size
+    }
+}
+
+impl RecordPayload for ServiceParameterPayload {
+    fn tnf(&self) -> TNF {
+        TNF::WellKnown
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(RTD_TNEP_SERVICE_PARAMETER.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        let mut buffer = vec![self.version, self.service_name.len() as u8];
+        buffer.extend_from_slice(self.service_name.as_bytes());
+        buffer.push(self.mode);
+        buffer.push(self.wait_time);
+        buffer.push(self.max_wait_extensions);
+        buffer.extend_from_slice(&self.max_message_size.to_be_bytes());
+        Cow::Owned(buffer)
+    }
+}
+
+impl TryFrom<&NdefRecord> for ServiceParameterPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        check_record(record, RTD_TNEP_SERVICE_PARAMETER)?;
+        let (version, rest) = record
+            .payload()
+            .split_first()
+            .ok_or(NdefError::InvalidPayload)?;
+        let (service_name, rest) = service_name(rest)?;
+        if rest.len() != 5 {
+            return Err(NdefError::InvalidPayload);
+        }
+        Ok(Self {
+            version: *version,
+            service_name,
+            mode: rest[0],
+            wait_time: rest[1],
+            max_wait_extensions: rest[2],
+            max_message_size: u16::from_be_bytes([rest[3], rest[4]]),
+        })
+    }
+}
+
+/// TNEP Service Select ("Ts") record, sent by the reader to pick a service.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ServiceSelectPayload {
+    service_name: String,
+}
+
+impl ServiceSelectPayload {
+    pub fn new<T: Into<String>>(service_name: T) -> Result<Self> {
+        let service_name = service_name.into();
+        if service_name.len() > 0xff {
+            return Err(NdefError::InvalidUri);
+        }
+        Ok(Self { service_name })
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+}
+
+impl RecordPayload for ServiceSelectPayload {
+    fn tnf(&self) -> TNF {
+        TNF::WellKnown
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(RTD_TNEP_SERVICE_SELECT.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        let mut buffer = vec![self.service_name.len() as u8];
+        buffer.extend_from_slice(self.service_name.as_bytes());
+        Cow::Owned(buffer)
+    }
+}
+
+impl TryFrom<&NdefRecord> for ServiceSelectPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        check_record(record, RTD_TNEP_SERVICE_SELECT)?;
+        let (service_name, rest) = service_name(record.payload())?;
+        if !rest.is_empty() {
+            return Err(NdefError::InvalidPayload);
+        }
+        Ok(Self { service_name })
+    }
+}
+
+/// TNEP Status ("Te") record returned by the tag.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StatusPayload(pub u8);
+
+impl StatusPayload {
+    pub const SUCCESS: StatusPayload = StatusPayload(0x00);
+    pub const PROTOCOL_ERROR: StatusPayload = StatusPayload(0x01);
+
+    pub fn is_success(&self) -> bool {
+        *self == Self::SUCCESS
+    }
+
+    /// Status values 0x80..=0xFE are reserved for service specific errors.
+    pub fn is_service_error(&self) -> bool {
+        (0x80..=0xfe).contains(&self.0)
+    }
+}
+
+impl RecordPayload for StatusPayload {
+    fn tnf(&self) -> TNF {
+        TNF::WellKnown
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(RTD_TNEP_STATUS.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(vec![self.0])
+    }
+}
+
+impl TryFrom<&NdefRecord> for StatusPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        check_record(record, RTD_TNEP_STATUS)?;
+        match record.payload() {
+            [status] => Ok(StatusPayload(*status)),
+            _ => Err(NdefError::InvalidPayload),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_parameter() {
+        let tp = ServiceParameterPayload::new("urn:nfc:sn:test")
+            .unwrap()
+            .with_wait_time(8)
+            .with_max_wait_extensions(3)
+            .with_max_message_size(1024);
+        assert_eq!(
+            "100f75726e3a6e66633a736e3a746573740008030400",
+            hex::encode(tp.payload())
+        );
+        assert_eq!(2.0, tp.wait_time_ms());
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&tp)
+            .build()
+            .unwrap();
+        assert_eq!(tp, ServiceParameterPayload::try_from(&record).unwrap());
+    }
+
+    #[test]
+    fn test_select_and_status() {
+        let ts = ServiceSelectPayload::new("urn:nfc:sn:test").unwrap();
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&ts)
+            .build()
+            .unwrap();
+        assert_eq!(ts, ServiceSelectPayload::try_from(&record).unwrap());
+        assert!(StatusPayload::try_from(&record).is_err());
+
+        let record = NdefRecord::builder()
+            .tnf(TNF::WellKnown)
+            .payload(&StatusPayload::SUCCESS)
+            .build()
+            .unwrap();
+        assert!(StatusPayload::try_from(&record).unwrap().is_success());
+        assert!(StatusPayload(0x80).is_service_error());
+    }
+}
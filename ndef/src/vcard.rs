@@ -0,0 +1,256 @@
+use crate::{error::NdefError, payload::RecordPayload, record::NdefRecord, *};
+use std::borrow::Cow;
+
+pub const MIME_VCARD: &str = "text/vcard";
+const MIME_VCARD_LEGACY: &str = "text/x-vcard";
+
+/// Contact card carried as a `text/vcard` MIME record (vCard 3.0).
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct VCardPayload {
+    name: String,
+    phones: Vec<String>,
+    emails: Vec<String>,
+    org: Option<String>,
+}
+
+impl VCardPayload {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn phone<T: Into<String>>(mut self, phone: T) -> Self {
+        self.phones.push(phone.into());
+        self
+    }
+
+    pub fn email<T: Into<String>>(mut self, email: T) -> Self {
+        self.emails.push(email.into());
+        self
+    }
+
+    pub fn org<T: Into<String>>(mut self, org: T) -> Self {
+        self.org = Some(org.into());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn phones(&self) -> &[String] {
+        &self.phones
+    }
+
+    pub fn emails(&self) -> &[String] {
+        &self.emails
+    }
+
+    pub fn organization(&self) -> Option<&str> {
+        self.org.as_deref()
+    }
+
+    pub fn to_vcard(&self) -> String {
+        let mut card = String::from("BEGIN:VCARD\r\nVERSION:3.0\r\n");
+        push_folded(&mut card, &format!("N:{};;;;", escape(&self.name)));
+        push_folded(&mut card, &format!("FN:{}", escape(&self.name)));
+        if let Some(org) = self.org.as_ref() {
+            push_folded(&mut card, &format!("ORG:{}", escape(org)));
+        }
+        for phone in self.phones.iter() {
+            push_folded(&mut card, &format!("TEL:{}", escape(phone)));
+        }
+        for email in self.emails.iter() {
+            push_folded(&mut card, &format!("EMAIL:{}", escape(email)));
+        }
+        card.push_str("END:VCARD\r\n");
+        card
+    }
+
+    /// Parses a vCard (2.1, 3.0 or 4.0). Only the fields modelled by this
+    /// type are kept; parameters such as `TYPE=CELL` are ignored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut card = Self::default();
+        let mut structured_name = None;
+        let mut in_card = false;
+        for line in unfold(text) {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let name = name.split(';').next().unwrap_or_default().to_ascii_uppercase();
+            match name.as_str() {
+                "BEGIN" if value.eq_ignore_ascii_case("VCARD") => in_card = true,
+                "END" if value.eq_ignore_ascii_case("VCARD") => {
+                    if !in_card {
+                        break;
+                    }
+                    if card.name.is_empty() {
+                        card.name = structured_name.unwrap_or_default();
+                    }
+                    return Ok(card);
+                }
+                _ if !in_card => {}
+                "FN" => card.name = unescape(value),
+                "N" => {
+                    let parts = split_unescaped(value, Some(';'));
+                    let given = parts.get(1).cloned().unwrap_or_default();
+                    let family = parts.first().cloned().unwrap_or_default();
+                    structured_name = Some(
+                        [given, family]
+                            .into_iter()
+                            .filter(|s| !s.is_empty())
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    );
+                }
+                "ORG" => card.org = split_unescaped(value, Some(';')).into_iter().next(),
+                "TEL" => card.phones.push(unescape(value)),
+                "EMAIL" => card.emails.push(unescape(value)),
+                _ => {}
+            }
+        }
+        Err(NdefError::InvalidPayload)
+    }
+}
+
+impl RecordPayload for VCardPayload {
+    fn tnf(&self) -> TNF {
+        TNF::MimeMedia
+    }
+
+    fn record_type(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(MIME_VCARD.as_bytes())
+    }
+
+    fn payload(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.to_vcard().into_bytes())
+    }
+}
+
+impl TryFrom<&NdefRecord> for VCardPayload {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        if record.tnf() != TNF::MimeMedia {
+            return Err(NdefError::InvalidTnf);
+        }
+        let mime_type = record.record_type();
+        if !mime_type.eq_ignore_ascii_case(MIME_VCARD.as_bytes())
+            && !mime_type.eq_ignore_ascii_case(MIME_VCARD_LEGACY.as_bytes())
+        {
+            return Err(NdefError::InvalidRecordType);
+        }
+        let text = std::str::from_utf8(record.payload()).map_err(|_| NdefError::InvalidEncoding)?;
+        Self::parse(text)
+    }
+}
+
+pub(crate) fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Appends `line` and a CRLF, folded so that no line exceeds 75 octets;
+/// continuation lines start with a space, as [`unfold`] expects.
+pub(crate) fn push_folded(text: &mut String, line: &str) {
+    let mut start = 0;
+    let mut limit = 75;
+    for (index, c) in line.char_indices() {
+        if index - start + c.len_utf8() > limit {
+            text.push_str(&line[start..index]);
+            text.push_str("\r\n ");
+            start = index;
+            limit = 74;
+        }
+    }
+    text.push_str(&line[start..]);
+    text.push_str("\r\n");
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | ',' | ';' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    split_unescaped(value, None).concat()
+}
+
+/// Splits on unescaped `separator` and unescapes each part.
+fn split_unescaped(value: &str, separator: Option<char>) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => parts.last_mut().unwrap().push('\n'),
+                Some(other) => parts.last_mut().unwrap().push(other),
+                None => {}
+            },
+            c if Some(c) == separator => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vcard_round_trip() {
+        let card = VCardPayload::new("Tang, Cheng")
+            .phone("+86 123 4567")
+            .email("tang@example.com")
+            .org("Example; Inc.");
+        let record = NdefRecord::builder()
+            .tnf(TNF::MimeMedia)
+            .payload(&card)
+            .build()
+            .unwrap();
+        assert_eq!(MIME_VCARD.as_bytes(), record.record_type());
+        assert!(std::str::from_utf8(record.payload())
+            .unwrap()
+            .contains("FN:Tang\\, Cheng\r\n"));
+        assert_eq!(card, VCardPayload::try_from(&record).unwrap());
+
+        let card = VCardPayload::new("Dr. Maximilian Alexander von und zu Irgendwo-Sonstwo-Anderswo-Nirgendwo, Ph.D. (\u{1f4de})");
+        let text = card.to_vcard();
+        assert!(text.split("\r\n").all(|line| line.len() <= 75));
+        assert!(text.contains("\r\n "));
+        assert_eq!(card, VCardPayload::parse(&text).unwrap());
+    }
+
+    #[test]
+    fn test_vcard_parse() {
+        let text = "BEGIN:VCARD\nVERSION:2.1\nN:Doe;John\nTEL;CELL:+1 555\n 0100\nEMAIL;TYPE=work:john@example.com\nEND:VCARD\n";
+        let card = VCardPayload::parse(text).unwrap();
+        assert_eq!("John Doe", card.name());
+        assert_eq!(&["+1 5550100".to_string()], card.phones());
+        assert_eq!(&["john@example.com".to_string()], card.emails());
+        assert_eq!(None, card.organization());
+
+        assert!(VCardPayload::parse("FN:nobody").is_err());
+    }
+}
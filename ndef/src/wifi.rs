@@ -0,0 +1,276 @@
+use crate::{error::NdefError, record::NdefRecord, *};
+use crate::handover::{CarrierConfiguration, MIME_WIFI_WSC};
+
+const ATTR_VERSION: u16 = 0x104a;
+const ATTR_CREDENTIAL: u16 = 0x100e;
+const ATTR_NETWORK_INDEX: u16 = 0x1026;
+const ATTR_MAC_ADDRESS: u16 = 0x1020;
+const ATTR_VENDOR_EXTENSION: u16 = 0x1049;
+const ATTR_SSID: u16 = 0x1045;
+const ATTR_AUTH_TYPE: u16 = 0x1003;
+const ATTR_ENCRYPTION_TYPE: u16 = 0x100f;
+const ATTR_NETWORK_KEY: u16 = 0x1027;
+/// WFA vendor extension carrying Version2 = 2.0.
+const WFA_VERSION2: [u8; 6] = [0x00, 0x37, 0x2a, 0x00, 0x01, 0x20];
+const MAX_SSID_LEN: usize = 32;
+const MAX_NETWORK_KEY_LEN: usize = 64;
+
+/// Authentication type of a Wi-Fi Simple Configuration credential.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WifiAuth {
+    Open,
+    WpaPersonal,
+    Shared,
+    WpaEnterprise,
+    Wpa2Enterprise,
+    Wpa2Personal,
+    /// WPA/WPA2 mixed mode.
+    WpaWpa2Personal,
+    Other(u16),
+}
+
+impl WifiAuth {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            0x0001 => WifiAuth::Open,
+            0x0002 => WifiAuth::WpaPersonal,
+            0x0004 => WifiAuth::Shared,
+            0x0008 => WifiAuth::WpaEnterprise,
+            0x0010 => WifiAuth::Wpa2Enterprise,
+            0x0020 => WifiAuth::Wpa2Personal,
+            0x0022 => WifiAuth::WpaWpa2Personal,
+            other => WifiAuth::Other(other),
+        }
+    }
+
+    pub fn as_u16(&self) -> u16 {
+        match self {
+            WifiAuth::Open => 0x0001,
+            WifiAuth::WpaPersonal => 0x0002,
+            WifiAuth::Shared => 0x0004,
+            WifiAuth::WpaEnterprise => 0x0008,
+            WifiAuth::Wpa2Enterprise => 0x0010,
+            WifiAuth::Wpa2Personal => 0x0020,
+            WifiAuth::WpaWpa2Personal => 0x0022,
+            WifiAuth::Other(value) => *value,
+        }
+    }
+}
+
+/// Encryption type of a Wi-Fi Simple Configuration credential.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WifiCipher {
+    None,
+    Wep,
+    Tkip,
+    Aes,
+    AesTkip,
+    Other(u16),
+}
+
+impl WifiCipher {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            0x0001 => WifiCipher::None,
+            0x0002 => WifiCipher::Wep,
+            0x0004 => WifiCipher::Tkip,
+            0x0008 => WifiCipher::Aes,
+            0x000c => WifiCipher::AesTkip,
+            other => WifiCipher::Other(other),
+        }
+    }
+
+    pub fn as_u16(&self) -> u16 {
+        match self {
+            WifiCipher::None => 0x0001,
+            WifiCipher::Wep => 0x0002,
+            WifiCipher::Tkip => 0x0004,
+            WifiCipher::Aes => 0x0008,
+            WifiCipher::AesTkip => 0x000c,
+            WifiCipher::Other(value) => *value,
+        }
+    }
+}
+
+/// Network credential carried in an `application/vnd.wfa.wsc` record.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WifiCredential {
+    pub ssid: String,
+    pub passphrase: String,
+    pub auth: WifiAuth,
+    pub cipher: WifiCipher,
+}
+
+impl WifiCredential {
+    /// Reads the first Credential attribute of a WSC payload.
+    pub fn from_wsc(data: &[u8]) -> Result<Self> {
+        let credential = attributes(data)?
+            .into_iter()
+            .find(|(id, _)| *id == ATTR_CREDENTIAL)
+            .ok_or(NdefError::InvalidPayload)?
+            .1;
+        let mut ssid = None;
+        let mut passphrase = String::new();
+        let mut auth = WifiAuth::Open;
+        let mut cipher = WifiCipher::None;
+        for (id, value) in attributes(credential)? {
+            match id {
+                ATTR_SSID => ssid = Some(String::from_utf8_lossy(value).into_owned()),
+                ATTR_NETWORK_KEY => passphrase = String::from_utf8_lossy(value).into_owned(),
+                ATTR_AUTH_TYPE => auth = WifiAuth::from_u16(u16_value(value)?),
+                ATTR_ENCRYPTION_TYPE => cipher = WifiCipher::from_u16(u16_value(value)?),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            ssid: ssid.ok_or(NdefError::InvalidPayload)?,
+            passphrase,
+            auth,
+            cipher,
+        })
+    }
+
+    pub fn new<S: Into<String>, P: Into<String>>(ssid: S, passphrase: P, auth: WifiAuth, cipher: WifiCipher) -> Self {
+        Self {
+            ssid: ssid.into(),
+            passphrase: passphrase.into(),
+            auth,
+            cipher,
+        }
+    }
+
+    /// WSC payload holding this credential, as written by the Wi-Fi
+    /// Alliance NFC configuration token format. The MAC address is the
+    /// broadcast address, which enrollees accept for any access point.
+    pub fn to_wsc(&self) -> Result<Vec<u8>> {
+        if self.ssid.is_empty() || self.ssid.len() > MAX_SSID_LEN {
+            return Err(NdefError::InvalidPayload);
+        }
+        if self.passphrase.len() > MAX_NETWORK_KEY_LEN {
+            return Err(NdefError::InvalidPayload);
+        }
+        let mut credential = vec![];
+        push_attribute(&mut credential, ATTR_NETWORK_INDEX, &[0x01]);
+        push_attribute(&mut credential, ATTR_SSID, self.ssid.as_bytes());
+        push_attribute(&mut credential, ATTR_AUTH_TYPE, &self.auth.as_u16().to_be_bytes());
+        push_attribute(&mut credential, ATTR_ENCRYPTION_TYPE, &self.cipher.as_u16().to_be_bytes());
+        push_attribute(&mut credential, ATTR_NETWORK_KEY, self.passphrase.as_bytes());
+        push_attribute(&mut credential, ATTR_MAC_ADDRESS, &[0xff; 6]);
+
+        let mut data = vec![];
+        push_attribute(&mut data, ATTR_VERSION, &[0x10]);
+        push_attribute(&mut data, ATTR_CREDENTIAL, &credential);
+        push_attribute(&mut data, ATTR_VENDOR_EXTENSION, &WFA_VERSION2);
+        Ok(data)
+    }
+
+    pub fn to_record(&self) -> Result<NdefRecord> {
+        NdefRecord::builder()
+            .tnf(TNF::MimeMedia)
+            .payload(&CarrierConfiguration::wsc(self.to_wsc()?))
+            .build()
+    }
+
+    /// Single-record message ready to be written to a tag.
+    pub fn to_message(&self) -> Result<NdefMessage> {
+        Ok(NdefMessage::from(self.to_record()?))
+    }
+
+    /// Reads the credential of the first WSC record in `message`.
+    pub fn from_message(message: &NdefMessage) -> Result<Self> {
+        let record = message
+            .records()
+            .iter()
+            .find(|record| {
+                record.tnf() == TNF::MimeMedia
+                    && record.record_type().eq_ignore_ascii_case(MIME_WIFI_WSC.as_bytes())
+            })
+            .ok_or(NdefError::InvalidMessage)?;
+        Self::try_from(record)
+    }
+}
+
+impl TryFrom<&NdefRecord> for WifiCredential {
+    type Error = NdefError;
+
+    fn try_from(record: &NdefRecord) -> Result<Self> {
+        if record.tnf() != TNF::MimeMedia {
+            return Err(NdefError::InvalidTnf);
+        }
+        if !record.record_type().eq_ignore_ascii_case(MIME_WIFI_WSC.as_bytes()) {
+            return Err(NdefError::InvalidRecordType);
+        }
+        Self::from_wsc(record.payload())
+    }
+}
+
+fn u16_value(value: &[u8]) -> Result<u16> {
+    match value {
+        [high, low] => Ok(u16::from_be_bytes([*high, *low])),
+        _ => Err(NdefError::InvalidPayload),
+    }
+}
+
+fn push_attribute(buffer: &mut Vec<u8>, id: u16, value: &[u8]) {
+    buffer.extend_from_slice(&id.to_be_bytes());
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+/// Splits WSC attributes (2-byte id, 2-byte length, value; big-endian).
+fn attributes(mut data: &[u8]) -> Result<Vec<(u16, &[u8])>> {
+    let mut attributes = vec![];
+    while !data.is_empty() {
+        if data.len() < 4 {
+            return Err(NdefError::InvalidPayload);
+        }
+        let id = u16::from_be_bytes([data[0], data[1]]);
+        let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let value = data.get(4..4 + len).ok_or(NdefError::InvalidPayload)?;
+        attributes.push((id, value));
+        data = &data[4 + len..];
+    }
+    Ok(attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_wsc() {
+        let data = hex::decode(concat!(
+            "104a000110",
+            "100e002f",
+            "1026000101",
+            "10450004486f6d65",
+            "100300020020",
+            "100f00020008",
+            "102700087365637265743132",
+            "10200006000000000000",
+        ))
+        .unwrap();
+        let credential = WifiCredential::from_wsc(&data).unwrap();
+        assert_eq!("Home", credential.ssid);
+        assert_eq!("secret12", credential.passphrase);
+        assert_eq!(WifiAuth::Wpa2Personal, credential.auth);
+        assert_eq!(WifiCipher::Aes, credential.cipher);
+
+        assert!(WifiCredential::from_wsc(&data[..data.len() - 1]).is_err());
+        assert!(WifiCredential::from_wsc(&data[..5]).is_err());
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let credential = WifiCredential::new("Home", "secret12", WifiAuth::Wpa2Personal, WifiCipher::Aes);
+        let message = credential.to_message().unwrap();
+        assert_eq!(MIME_WIFI_WSC.as_bytes(), message.records()[0].record_type());
+        let bytes = message.to_buffer().unwrap();
+        let decoded = NdefMessage::decode(bytes).unwrap();
+        assert_eq!(credential, WifiCredential::from_message(&decoded).unwrap());
+
+        let long = WifiCredential::new("x".repeat(33), "", WifiAuth::Open, WifiCipher::None);
+        assert!(long.to_message().is_err());
+        assert!(WifiCredential::from_message(&NdefMessage::default()).is_err());
+    }
+}